@@ -13,7 +13,7 @@ fn main() {
     // Check if we're building with rust-src
     let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
     let output = Command::new(&rustc)
-        .args(&["--print", "sysroot"])
+        .args(["--print", "sysroot"])
         .output()
         .expect("Failed to get rustc sysroot");
     
@@ -0,0 +1,277 @@
+//! Compiler builtins for Rusty Refactor
+//!
+//! This module lists the names every crate can use without an import: primitive types,
+//! built-in attributes and derive macros, macros implemented by the compiler itself, and
+//! the `std` prelude of each edition.
+
+use std::collections::HashMap;
+
+use crate::{ImportableItem, ItemKind, ItemSource, ItemVisibility};
+
+/// Primitive types
+const PRIMITIVES: &[&str] = &[
+    "bool", "char", "str", "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64",
+    "u128", "usize", "f32", "f64", "!",
+];
+
+/// Stable attributes the compiler defines, usable without any path
+const ATTRIBUTES: &[&str] = &[
+    "allow",
+    "automatically_derived",
+    "cfg",
+    "cfg_attr",
+    "cold",
+    "collapse_debuginfo",
+    "crate_name",
+    "crate_type",
+    "debugger_visualizer",
+    "deny",
+    "deprecated",
+    "derive",
+    "doc",
+    "expect",
+    "export_name",
+    "forbid",
+    "global_allocator",
+    "ignore",
+    "inline",
+    "instruction_set",
+    "link",
+    "link_name",
+    "link_section",
+    "macro_export",
+    "macro_use",
+    "must_use",
+    "no_implicit_prelude",
+    "no_link",
+    "no_main",
+    "no_mangle",
+    "no_std",
+    "non_exhaustive",
+    "panic_handler",
+    "path",
+    "proc_macro",
+    "proc_macro_attribute",
+    "proc_macro_derive",
+    "recursion_limit",
+    "repr",
+    "should_panic",
+    "target_feature",
+    "test",
+    "track_caller",
+    "type_length_limit",
+    "used",
+    "warn",
+    "windows_subsystem",
+];
+
+/// Derive macros of the prelude
+const DERIVES: &[&str] = &[
+    "Clone",
+    "Copy",
+    "Debug",
+    "Default",
+    "Eq",
+    "Hash",
+    "Ord",
+    "PartialEq",
+    "PartialOrd",
+];
+
+/// Macros the compiler expands itself rather than through a `macro_rules!` definition
+const MACROS: &[&str] = &[
+    "assert",
+    "cfg",
+    "column",
+    "compile_error",
+    "concat",
+    "env",
+    "file",
+    "format_args",
+    "include",
+    "include_bytes",
+    "include_str",
+    "line",
+    "module_path",
+    "option_env",
+    "stringify",
+];
+
+/// Primitive types, built-in attributes and derives, and compiler-implemented macros
+///
+/// None of them has a path to import, so each item's `full_path` is just its name.
+pub fn compiler_items() -> Vec<ImportableItem> {
+    let groups: [(&[&str], ItemKind, &str); 4] = [
+        (PRIMITIVES, ItemKind::Primitive, "Primitive type"),
+        (ATTRIBUTES, ItemKind::AttributeMacro, "Built-in attribute"),
+        (DERIVES, ItemKind::DeriveMacro, "Built-in derive macro"),
+        (MACROS, ItemKind::Macro, "Built-in macro"),
+    ];
+    groups
+        .into_iter()
+        .flat_map(|(names, kind, docs)| names.iter().map(move |name| (name, kind, docs)))
+        .map(|(name, kind, docs)| ImportableItem {
+            full_path: name.to_string(),
+            name: name.to_string(),
+            kind,
+            source: ItemSource::Compiler,
+            visibility: ItemVisibility::Public,
+            docs: Some(docs.to_string()),
+            is_macro: kind.is_macro(),
+            deprecation: None,
+            unstable: false,
+            trait_info: None,
+        })
+        .collect()
+}
+
+/// The `std` prelude as (path, kind, first edition that has it)
+const PRELUDE: &[(&str, ItemKind, u16)] = &[
+    ("std::marker::Copy", ItemKind::Trait, 2015),
+    ("std::marker::Send", ItemKind::Trait, 2015),
+    ("std::marker::Sized", ItemKind::Trait, 2015),
+    ("std::marker::Sync", ItemKind::Trait, 2015),
+    ("std::marker::Unpin", ItemKind::Trait, 2015),
+    ("std::ops::Drop", ItemKind::Trait, 2015),
+    ("std::ops::Fn", ItemKind::Trait, 2015),
+    ("std::ops::FnMut", ItemKind::Trait, 2015),
+    ("std::ops::FnOnce", ItemKind::Trait, 2015),
+    ("std::mem::drop", ItemKind::Function, 2015),
+    ("std::boxed::Box", ItemKind::Struct, 2015),
+    ("std::borrow::ToOwned", ItemKind::Trait, 2015),
+    ("std::clone::Clone", ItemKind::Trait, 2015),
+    ("std::cmp::PartialEq", ItemKind::Trait, 2015),
+    ("std::cmp::PartialOrd", ItemKind::Trait, 2015),
+    ("std::cmp::Eq", ItemKind::Trait, 2015),
+    ("std::cmp::Ord", ItemKind::Trait, 2015),
+    ("std::convert::AsRef", ItemKind::Trait, 2015),
+    ("std::convert::AsMut", ItemKind::Trait, 2015),
+    ("std::convert::Into", ItemKind::Trait, 2015),
+    ("std::convert::From", ItemKind::Trait, 2015),
+    ("std::default::Default", ItemKind::Trait, 2015),
+    ("std::iter::Iterator", ItemKind::Trait, 2015),
+    ("std::iter::Extend", ItemKind::Trait, 2015),
+    ("std::iter::IntoIterator", ItemKind::Trait, 2015),
+    ("std::iter::DoubleEndedIterator", ItemKind::Trait, 2015),
+    ("std::iter::ExactSizeIterator", ItemKind::Trait, 2015),
+    ("std::option::Option", ItemKind::Enum, 2015),
+    ("std::option::Option::Some", ItemKind::Variant, 2015),
+    ("std::option::Option::None", ItemKind::Variant, 2015),
+    ("std::result::Result", ItemKind::Enum, 2015),
+    ("std::result::Result::Ok", ItemKind::Variant, 2015),
+    ("std::result::Result::Err", ItemKind::Variant, 2015),
+    ("std::string::String", ItemKind::Struct, 2015),
+    ("std::string::ToString", ItemKind::Trait, 2015),
+    ("std::vec::Vec", ItemKind::Struct, 2015),
+    ("std::convert::TryFrom", ItemKind::Trait, 2021),
+    ("std::convert::TryInto", ItemKind::Trait, 2021),
+    ("std::iter::FromIterator", ItemKind::Trait, 2021),
+    ("std::future::Future", ItemKind::Trait, 2024),
+    ("std::future::IntoFuture", ItemKind::Trait, 2024),
+];
+
+/// Items of the `std` prelude of `edition`, taken from `items` where they are listed there
+pub fn prelude_items(edition: u16, items: &[ImportableItem]) -> Vec<ImportableItem> {
+    let listed: HashMap<&str, &ImportableItem> = items
+        .iter()
+        .map(|item| (item.full_path.as_str(), item))
+        .collect();
+    PRELUDE
+        .iter()
+        .filter(|(_, _, since)| *since <= edition)
+        .map(|&(path, kind, _)| match listed.get(path) {
+            Some(item) => (*item).clone(),
+            None => ImportableItem {
+                full_path: path.to_string(),
+                name: path.rsplit("::").next().unwrap_or(path).to_string(),
+                kind,
+                source: ItemSource::Std,
+                visibility: ItemVisibility::Public,
+                docs: None,
+                is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
+            },
+        })
+        .collect()
+}
+
+/// Whether an item is in scope in every module of a crate of `edition` without an import
+///
+/// That covers compiler builtins, the edition's prelude (under its `std`, `core` or `alloc`
+/// path) and the macros `std` exports at its root, which its `#[macro_use]` brings in.
+pub fn in_scope_by_default(item: &ImportableItem, edition: u16) -> bool {
+    match item.source {
+        ItemSource::Compiler => true,
+        ItemSource::Std | ItemSource::Core => {
+            let path = std_path(&item.full_path);
+            let root_macro = item.kind == ItemKind::Macro && path.matches("::").count() == 1;
+            root_macro
+                || PRELUDE
+                    .iter()
+                    .any(|(prelude, _, since)| *prelude == path && *since <= edition)
+        }
+        _ => false,
+    }
+}
+
+// Helper functions
+
+/// A `core::` or `alloc::` path under the `std::` path that re-exports it
+fn std_path(path: &str) -> String {
+    match path.split_once("::") {
+        Some(("core" | "alloc", rest)) => format!("std::{}", rest),
+        _ => path.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(path: &str, kind: ItemKind, source: ItemSource) -> ImportableItem {
+        ImportableItem {
+            full_path: path.to_string(),
+            name: path.rsplit("::").next().unwrap().to_string(),
+            kind,
+            source,
+            visibility: ItemVisibility::Public,
+            docs: None,
+            is_macro: kind.is_macro(),
+            deprecation: None,
+            unstable: false,
+            trait_info: None,
+        }
+    }
+
+    #[test]
+    fn test_in_scope_by_default_follows_the_edition() {
+        let option = item("core::option::Option", ItemKind::Enum, ItemSource::Core);
+        assert!(in_scope_by_default(&option, 2015));
+
+        let try_from = item("std::convert::TryFrom", ItemKind::Trait, ItemSource::Std);
+        assert!(!in_scope_by_default(&try_from, 2018));
+        assert!(in_scope_by_default(&try_from, 2021));
+        let future = item("std::future::Future", ItemKind::Trait, ItemSource::Std);
+        assert!(!in_scope_by_default(&future, 2021));
+        assert!(in_scope_by_default(&future, 2024));
+
+        let println = item("std::println", ItemKind::Macro, ItemSource::Std);
+        assert!(in_scope_by_default(&println, 2015));
+        let map = item(
+            "std::collections::HashMap",
+            ItemKind::Struct,
+            ItemSource::Std,
+        );
+        assert!(!in_scope_by_default(&map, 2024));
+
+        let items = compiler_items();
+        let u8 = items.iter().find(|item| item.name == "u8").unwrap();
+        assert_eq!(u8.kind, ItemKind::Primitive);
+        assert!(in_scope_by_default(u8, 2015));
+        assert!(items
+            .iter()
+            .any(|item| item.name == "include_str" && item.is_macro));
+    }
+}
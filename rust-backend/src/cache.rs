@@ -9,13 +9,14 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use zstd::bulk::Compressor;
 use dashmap::DashMap;
 
 /// Base directory for all cache files
-const CACHE_DIR: &str = ".rusty-cache";
+pub(crate) const CACHE_DIR: &str = ".rusty-cache";
 
 /// Current cache version - bump this to invalidate all existing caches
 const CACHE_VERSION: u32 = 1;
@@ -94,6 +95,8 @@ pub struct IncrementalCache {
     index: Arc<RwLock<CacheIndex>>,
     /// In-memory cache for hot entries
     memory_cache: Arc<DashMap<String, CacheEntry>>,
+    /// Hits served from `memory_cache`, counted apart from the index to keep reads lock-free
+    memory_hits: Arc<AtomicU64>,
     /// File system options
     fs_options: CacheOptions,
     /// Compressor for data
@@ -158,6 +161,7 @@ impl IncrementalCache {
             base_dir,
             index: Arc::new(RwLock::new(index)),
             memory_cache: Arc::new(DashMap::new()),
+            memory_hits: Arc::new(AtomicU64::new(0)),
             fs_options: options,
             compressor: Arc::new(RwLock::new(compressor)),
         })
@@ -168,11 +172,12 @@ impl IncrementalCache {
         // Check memory cache first
         let key = self.get_cache_key(file_path)?;
         if let Some(entry) = self.memory_cache.get(&key) {
+            self.memory_hits.fetch_add(1, Ordering::Relaxed);
             return Ok(Some(entry.clone()));
         }
 
         // Check file system cache
-        let cache_file = self.base_dir.join(&format!("{}.cache", key));
+        let cache_file = self.base_dir.join(format!("{}.cache", key));
         if !cache_file.exists() {
             return Ok(None);
         }
@@ -300,19 +305,23 @@ impl IncrementalCache {
             index.entries.clear();
             index.stats = CacheStats::default();
         }
+        self.memory_hits.store(0, Ordering::Relaxed);
 
         Ok(())
     }
 
     /// Get cache statistics
     pub fn stats(&self) -> CacheStats {
-        self.index.read().stats.clone()
+        let mut stats = self.index.read().stats.clone();
+        stats.hits += self.memory_hits.load(Ordering::Relaxed);
+        stats
     }
 
     /// Save the index to disk
     pub fn save_index(&self) -> Result<()> {
-        let index = self.index.read();
-        let serialized = bincode::serialize(&*index)?;
+        let mut index = self.index.read().clone();
+        index.stats.hits += self.memory_hits.load(Ordering::Relaxed);
+        let serialized = bincode::serialize(&index)?;
         std::fs::write(self.base_dir.join("index.bin"), serialized)?;
         Ok(())
     }
@@ -396,7 +405,7 @@ impl IncrementalCache {
     }
 }
 
-/// Helper functions
+// Helper functions
 
 fn current_timestamp() -> u64 {
     SystemTime::now()
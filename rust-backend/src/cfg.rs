@@ -0,0 +1,133 @@
+//! Conditional compilation for Rusty Refactor
+//!
+//! This module evaluates `#[cfg(...)]` predicates against the host target and a set of
+//! enabled features, so indexed dependencies only list what the compiler would see.
+
+use std::collections::HashSet;
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Meta, Token};
+
+/// The configuration options that are set, e.g. `unix` and `feature = "std"`
+#[derive(Debug, Clone, Default)]
+pub struct CfgSet {
+    names: HashSet<String>,
+    values: HashSet<(String, String)>,
+}
+
+impl CfgSet {
+    /// Options for a non-test build on the host target with `features` enabled
+    pub fn host(features: impl IntoIterator<Item = String>) -> Self {
+        let mut cfg = Self::default();
+        cfg.names.insert(std::env::consts::FAMILY.to_string());
+        cfg.names.insert("debug_assertions".to_string());
+
+        let pointer_width = (usize::BITS).to_string();
+        let endian = if cfg!(target_endian = "little") {
+            "little"
+        } else {
+            "big"
+        };
+        let env = if cfg!(target_env = "gnu") {
+            "gnu"
+        } else if cfg!(target_env = "msvc") {
+            "msvc"
+        } else if cfg!(target_env = "musl") {
+            "musl"
+        } else {
+            ""
+        };
+        for (key, value) in [
+            ("target_os", std::env::consts::OS),
+            ("target_family", std::env::consts::FAMILY),
+            ("target_arch", std::env::consts::ARCH),
+            ("target_pointer_width", pointer_width.as_str()),
+            ("target_endian", endian),
+            ("target_env", env),
+            ("panic", "unwind"),
+        ] {
+            cfg.values.insert((key.to_string(), value.to_string()));
+        }
+        for width in ["8", "16", "32", "64", "ptr"] {
+            cfg.values
+                .insert(("target_has_atomic".to_string(), width.to_string()));
+        }
+        for feature in features {
+            cfg.values.insert(("feature".to_string(), feature));
+        }
+        cfg
+    }
+
+    /// Whether every `#[cfg(...)]` among `attrs` holds
+    pub fn is_enabled(&self, attrs: &[Attribute]) -> bool {
+        attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("cfg"))
+            .all(|attr| {
+                attr.parse_args::<Meta>()
+                    .is_ok_and(|predicate| self.eval(&predicate))
+            })
+    }
+
+    fn eval(&self, predicate: &Meta) -> bool {
+        match predicate {
+            Meta::Path(path) => path
+                .get_ident()
+                .is_some_and(|name| self.names.contains(&name.to_string())),
+            Meta::NameValue(pair) => {
+                let (Some(key), syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(value),
+                    ..
+                })) = (pair.path.get_ident(), &pair.value)
+                else {
+                    return false;
+                };
+                self.values.contains(&(key.to_string(), value.value()))
+            }
+            Meta::List(list) => {
+                let Ok(inner) =
+                    list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+                else {
+                    return false;
+                };
+                if list.path.is_ident("all") {
+                    inner.iter().all(|p| self.eval(p))
+                } else if list.path.is_ident("any") {
+                    inner.iter().any(|p| self.eval(p))
+                } else if list.path.is_ident("not") {
+                    inner.len() == 1 && !self.eval(&inner[0])
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled(cfg: &CfgSet, attr: &str) -> bool {
+        let item: syn::ItemFn = syn::parse_str(&format!("{attr} fn f() {{}}")).unwrap();
+        cfg.is_enabled(&item.attrs)
+    }
+
+    #[test]
+    fn test_cfg_predicates() {
+        let cfg = CfgSet::host(["std".to_string()]);
+
+        assert!(enabled(&cfg, "#[cfg(feature = \"std\")]"));
+        assert!(!enabled(&cfg, "#[cfg(feature = \"alloc\")]"));
+        assert!(!enabled(&cfg, "#[cfg(test)]"));
+        assert!(enabled(&cfg, "#[cfg(not(test))]"));
+        assert!(enabled(&cfg, "#[cfg(all(feature = \"std\", debug_assertions))]"));
+        assert!(enabled(&cfg, "#[cfg(any(docsrs, feature = \"std\"))]"));
+        assert!(!enabled(&cfg, "#[cfg(feature = \"std\")] #[cfg(docsrs)]"));
+        assert_eq!(
+            enabled(&cfg, "#[cfg(unix)]"),
+            cfg!(unix),
+            "target family follows the host"
+        );
+        assert!(enabled(&cfg, "#[inline]"));
+    }
+}
@@ -0,0 +1,886 @@
+//! Local crate indexing for Rusty Refactor
+//!
+//! This module walks each target of a package from its root file, following `mod`
+//! declarations and `#[path]` attributes the way rustc does, and records every item and
+//! `use` declaration the module tree contains together with its visibility.
+
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{ForeignItem, Item, ItemMod, Token};
+use toml::Value;
+
+use crate::cfg::CfgSet;
+use crate::manifest::PackageManifest;
+use crate::syntax::{doc_summary, flatten_use, item_name, type_key, SourceFile, UseLeaf};
+use crate::{ItemKind, ItemVisibility};
+
+/// An item declared somewhere in a crate's module tree
+#[derive(Debug, Clone)]
+pub struct IndexedItem {
+    pub name: String,
+    pub kind: ItemKind,
+    /// Module the item is declared in, relative to the crate root
+    pub module_path: Vec<String>,
+    pub visibility: ItemVisibility,
+    pub docs: Option<String>,
+    /// File the declaration is in
+    pub file: PathBuf,
+    /// Marked `#[unstable]`, directly or through an enclosing module
+    pub unstable: bool,
+    /// Marked `#[doc(hidden)]`, directly or through an enclosing module
+    pub hidden: bool,
+    /// Names of the methods a trait declares, empty for other items
+    pub methods: Vec<String>,
+}
+
+impl IndexedItem {
+    /// `crate::`-relative path of the item
+    pub fn path(&self) -> String {
+        module_path_string(&self.module_path) + "::" + &self.name
+    }
+}
+
+/// A module of the tree, inline or backed by its own file
+#[derive(Debug, Clone)]
+pub struct IndexedModule {
+    /// Module path relative to the crate root, empty for the root itself
+    pub path: Vec<String>,
+    /// File the module's items are in
+    pub file: PathBuf,
+    pub visibility: ItemVisibility,
+    /// Last line of the `mod` declaration in the parent's file, 0 for the crate root
+    pub declared_at: usize,
+    /// Marked `#[macro_use]`, so its `macro_rules!` macros stay in scope after it
+    pub macro_use: bool,
+}
+
+/// A `macro_rules!` definition, in scope by source order rather than by path
+#[derive(Debug, Clone)]
+pub struct IndexedMacro {
+    pub name: String,
+    /// Module the definition is in
+    pub module_path: Vec<String>,
+    /// Last line of the definition
+    pub line: usize,
+    /// Marked `#[macro_export]`, so also nameable as `crate::name`
+    pub exported: bool,
+    pub docs: Option<String>,
+}
+
+/// One leaf of a `use` declaration, re-exported or not depending on its visibility
+#[derive(Debug, Clone)]
+pub struct IndexedReexport {
+    /// Module the `use` is declared in
+    pub module_path: Vec<String>,
+    pub leaf: UseLeaf,
+    pub visibility: ItemVisibility,
+    pub unstable: bool,
+    pub hidden: bool,
+}
+
+/// An `impl Trait for Type` block
+#[derive(Debug, Clone)]
+pub struct IndexedImpl {
+    /// Module the `impl` is in
+    pub module_path: Vec<String>,
+    /// Path of the trait as written
+    pub trait_path: Vec<String>,
+    /// `type_key` of the implementing type, `_` for a blanket impl over a type parameter
+    pub self_type: String,
+}
+
+/// Every module and item reachable from one crate root
+#[derive(Debug, Clone, Default)]
+pub struct CrateIndex {
+    pub modules: Vec<IndexedModule>,
+    pub items: Vec<IndexedItem>,
+    pub reexports: Vec<IndexedReexport>,
+    /// `extern crate` declarations as (crate, name it is bound to)
+    pub extern_crates: Vec<(String, String)>,
+    /// Crates declared with `#[macro_use] extern crate`, whose exported macros every module sees
+    pub macro_use_crates: Vec<String>,
+    pub impls: Vec<IndexedImpl>,
+    pub macros: Vec<IndexedMacro>,
+    /// Item-level macro invocations that may declare `pub` items the index cannot see into,
+    /// as (module, macro path)
+    pub unexpanded_macros: Vec<(Vec<String>, String)>,
+}
+
+impl CrateIndex {
+    /// Index the module tree rooted at `root_file` (`lib.rs`, `main.rs`, ...)
+    ///
+    /// `#[cfg(test)]` items are skipped. Module files that are missing or fail to parse
+    /// are left out rather than failing the whole index, since they are often mid-edit.
+    pub fn build(root_file: &Path) -> Result<Self> {
+        Self::build_with(root_file, None)
+    }
+
+    /// Index a module tree, leaving out items whose `#[cfg]` does not hold in `cfg`
+    ///
+    /// Without a `CfgSet` only `#[cfg(test)]` items are left out.
+    pub fn build_with(root_file: &Path, cfg: Option<&CfgSet>) -> Result<Self> {
+        Self::walk(
+            root_file,
+            Walker {
+                cfg: cfg.cloned(),
+                ..Walker::default()
+            },
+        )
+    }
+
+    /// Index a module tree including `#[cfg(test)]` items, for refactorings that must see
+    /// every file of a crate
+    pub fn build_all(root_file: &Path) -> Result<Self> {
+        Self::walk(
+            root_file,
+            Walker {
+                include_tests: true,
+                ..Walker::default()
+            },
+        )
+    }
+
+    fn walk(root_file: &Path, mut walker: Walker) -> Result<Self> {
+        let source = SourceFile::read(root_file)
+            .with_context(|| format!("Failed to parse {}", root_file.display()))?;
+        let dir = root_file.parent().unwrap_or(Path::new("")).to_path_buf();
+
+        walker.visited.insert(root_file.to_path_buf());
+        walker.modules.push(IndexedModule {
+            path: Vec::new(),
+            file: root_file.to_path_buf(),
+            visibility: ItemVisibility::Public,
+            declared_at: 0,
+            macro_use: false,
+        });
+        let scope = Scope {
+            file: root_file.to_path_buf(),
+            module_path: Vec::new(),
+            child_dir: dir.clone(),
+            path_base: dir,
+            unstable: is_unstable(&source.ast.attrs),
+            hidden: is_doc_hidden(&source.ast.attrs),
+        };
+        walker.walk_items(&source.ast.items, &scope);
+
+        Ok(Self {
+            modules: walker.modules,
+            items: walker.items,
+            reexports: walker.reexports,
+            extern_crates: walker.extern_crates,
+            macro_use_crates: walker.macro_use_crates,
+            impls: walker.impls,
+            macros: walker.macros,
+            unexpanded_macros: walker.unexpanded_macros,
+        })
+    }
+
+    /// `macro_rules!` macros in textual scope at a 1-based line of `module`
+    ///
+    /// A definition is in scope after itself in its module and in the child modules declared
+    /// after it; `#[macro_use]` on a module keeps its macros in scope in the parent after the
+    /// `mod` declaration.
+    pub fn macros_in_scope(&self, module: &[String], line: usize) -> Vec<&IndexedMacro> {
+        let declared = |path: &[String]| self.modules.iter().find(|m| m.path == path);
+        self.macros
+            .iter()
+            .filter(|indexed| {
+                // Each module the macro is in scope in, with the line its scope there starts at
+                let mut scopes = vec![(indexed.module_path.as_slice(), indexed.line)];
+                let mut current = indexed.module_path.as_slice();
+                while let Some(parent) = current.split_last().map(|(_, parent)| parent) {
+                    match declared(current) {
+                        Some(m) if m.macro_use => scopes.push((parent, m.declared_at)),
+                        _ => break,
+                    }
+                    current = parent;
+                }
+                scopes.iter().any(|(scope, start)| {
+                    if !module.starts_with(scope) {
+                        return false;
+                    }
+                    // Where `module` is entered from the scope's own text
+                    let entry = match module.get(scope.len()) {
+                        None => line,
+                        Some(_) => declared(&module[..=scope.len()]).map_or(0, |m| m.declared_at),
+                    };
+                    entry > *start
+                })
+            })
+            .collect()
+    }
+}
+
+/// Root files of a package's library and binary targets
+///
+/// Follows Cargo's target discovery: `[lib]`/`[[bin]]` paths, `src/lib.rs`, `src/main.rs`,
+/// `src/bin/*.rs` and `src/bin/*/main.rs`. The library, if any, comes first.
+pub fn target_roots(package: &PackageManifest) -> Vec<PathBuf> {
+    let dir = package
+        .manifest_path
+        .parent()
+        .unwrap_or(Path::new(""))
+        .to_path_buf();
+    let mut roots = Vec::new();
+    let mut push = |path: PathBuf| {
+        if path.is_file() && !roots.contains(&path) {
+            roots.push(path);
+        }
+    };
+
+    if let Some(lib) = lib_root(package) {
+        push(lib);
+    }
+
+    let bins = package.table.get("bin").and_then(Value::as_array);
+    for bin in bins.into_iter().flatten() {
+        if let Some(path) = bin.get("path").and_then(Value::as_str) {
+            push(dir.join(path));
+        }
+    }
+    push(dir.join("src/main.rs"));
+
+    if let Ok(entries) = std::fs::read_dir(dir.join("src/bin")) {
+        let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+        paths.sort();
+        for path in paths {
+            if path.is_dir() {
+                push(path.join("main.rs"));
+            } else if path.extension().is_some_and(|ext| ext == "rs") {
+                push(path);
+            }
+        }
+    }
+
+    roots
+}
+
+/// Root file of a package's library target, if it has one
+pub fn lib_root(package: &PackageManifest) -> Option<PathBuf> {
+    let dir = package.manifest_path.parent().unwrap_or(Path::new(""));
+    let lib_path = package
+        .table
+        .get("lib")
+        .and_then(|lib| lib.get("path"))
+        .and_then(Value::as_str);
+    Some(lib_path.map_or_else(|| dir.join("src/lib.rs"), |path| dir.join(path)))
+        .filter(|path| path.is_file())
+}
+
+/// `crate::a::b` form of a module path
+pub fn module_path_string(path: &[String]) -> String {
+    std::iter::once("crate")
+        .chain(path.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// Visibility of an item as declared
+pub fn item_visibility(vis: &syn::Visibility) -> ItemVisibility {
+    match vis {
+        syn::Visibility::Public(_) => ItemVisibility::Public,
+        syn::Visibility::Inherited => ItemVisibility::Private,
+        syn::Visibility::Restricted(restricted) => {
+            let path = restricted
+                .path
+                .segments
+                .iter()
+                .map(|segment| segment.ident.to_string())
+                .collect::<Vec<_>>()
+                .join("::");
+            match path.as_str() {
+                "crate" => ItemVisibility::Crate,
+                "self" => ItemVisibility::Private,
+                "super" => ItemVisibility::Super,
+                _ => ItemVisibility::Restricted { path },
+            }
+        }
+    }
+}
+
+/// Where the items being walked live and what they inherit from enclosing modules
+#[derive(Clone)]
+struct Scope {
+    file: PathBuf,
+    module_path: Vec<String>,
+    /// Where `mod x;` looks for `x.rs`/`x/mod.rs`
+    child_dir: PathBuf,
+    /// What `#[path]` is relative to; differs from `child_dir` only at the top level of a
+    /// non-`mod.rs` file
+    path_base: PathBuf,
+    unstable: bool,
+    hidden: bool,
+}
+
+impl Scope {
+    /// The scope's flags combined with an item's own attributes
+    fn flags(&self, attrs: &[syn::Attribute]) -> (bool, bool) {
+        let stable = attrs.iter().any(|attr| attr.path().is_ident("stable"));
+        (
+            !stable && (self.unstable || is_unstable(attrs)),
+            self.hidden || is_doc_hidden(attrs),
+        )
+    }
+}
+
+/// Recursive walk over a module tree
+#[derive(Default)]
+struct Walker {
+    modules: Vec<IndexedModule>,
+    items: Vec<IndexedItem>,
+    reexports: Vec<IndexedReexport>,
+    extern_crates: Vec<(String, String)>,
+    macro_use_crates: Vec<String>,
+    impls: Vec<IndexedImpl>,
+    macros: Vec<IndexedMacro>,
+    unexpanded_macros: Vec<(Vec<String>, String)>,
+    /// Files already indexed, so `#[path]` cycles terminate
+    visited: HashSet<PathBuf>,
+    cfg: Option<CfgSet>,
+    /// Keep `#[cfg(test)]` items when there is no `cfg`
+    include_tests: bool,
+}
+
+impl Walker {
+    /// Index the items of one module
+    fn walk_items(&mut self, items: &[Item], scope: &Scope) {
+        for item in items {
+            if !self.included(item_attrs(item)) {
+                continue;
+            }
+            match item {
+                Item::Mod(item_mod) => {
+                    self.push(scope, item);
+                    self.walk_module(item_mod, scope);
+                }
+                Item::Use(item_use) => {
+                    let (unstable, hidden) = scope.flags(&item_use.attrs);
+                    for leaf in flatten_use(&item_use.tree) {
+                        self.reexports.push(IndexedReexport {
+                            module_path: scope.module_path.clone(),
+                            leaf,
+                            visibility: item_visibility(&item_use.vis),
+                            unstable,
+                            hidden,
+                        });
+                    }
+                }
+                Item::ExternCrate(extern_crate) => {
+                    let alias = extern_crate
+                        .rename
+                        .as_ref()
+                        .map_or(&extern_crate.ident, |r| &r.1);
+                    self.extern_crates
+                        .push((extern_crate.ident.to_string(), alias.to_string()));
+                    if has_attr(&extern_crate.attrs, "macro_use") {
+                        self.macro_use_crates.push(extern_crate.ident.to_string());
+                    }
+                }
+                Item::Macro(item_macro) => {
+                    let Some(ident) = &item_macro.ident else {
+                        self.walk_invocation(&item_macro.mac, scope);
+                        continue;
+                    };
+                    let exported = has_attr(&item_macro.attrs, "macro_export");
+                    self.macros.push(IndexedMacro {
+                        name: ident.to_string(),
+                        module_path: scope.module_path.clone(),
+                        line: item_macro.span().end().line,
+                        exported,
+                        docs: doc_summary(&item_macro.attrs),
+                    });
+                    // Only exported `macro_rules!` macros are nameable by path, at the root
+                    if exported {
+                        self.items.push(IndexedItem {
+                            name: ident.to_string(),
+                            kind: ItemKind::Macro,
+                            module_path: Vec::new(),
+                            visibility: ItemVisibility::Public,
+                            docs: doc_summary(&item_macro.attrs),
+                            file: scope.file.clone(),
+                            unstable: is_unstable(&item_macro.attrs),
+                            hidden: is_doc_hidden(&item_macro.attrs),
+                            methods: Vec::new(),
+                        });
+                    }
+                }
+                Item::ForeignMod(foreign) => {
+                    for foreign_item in &foreign.items {
+                        let (ident, vis, attrs, kind) = match foreign_item {
+                            ForeignItem::Fn(f) => {
+                                (&f.sig.ident, &f.vis, &f.attrs, ItemKind::Function)
+                            }
+                            ForeignItem::Static(s) => {
+                                (&s.ident, &s.vis, &s.attrs, ItemKind::Static)
+                            }
+                            ForeignItem::Type(t) => {
+                                (&t.ident, &t.vis, &t.attrs, ItemKind::TypeAlias)
+                            }
+                            _ => continue,
+                        };
+                        if !self.included(attrs) {
+                            continue;
+                        }
+                        let (unstable, hidden) = scope.flags(attrs);
+                        self.items.push(IndexedItem {
+                            name: ident.to_string(),
+                            kind,
+                            module_path: scope.module_path.clone(),
+                            visibility: item_visibility(vis),
+                            docs: doc_summary(attrs),
+                            file: scope.file.clone(),
+                            unstable,
+                            hidden,
+                            methods: Vec::new(),
+                        });
+                    }
+                }
+                Item::Impl(item_impl) => {
+                    let Some((None, trait_path, _)) = &item_impl.trait_ else {
+                        continue;
+                    };
+                    let Some(mut self_type) = type_key(&item_impl.self_ty) else {
+                        continue;
+                    };
+                    if item_impl
+                        .generics
+                        .type_params()
+                        .any(|param| param.ident == self_type)
+                    {
+                        self_type = "_".to_string();
+                    }
+                    self.impls.push(IndexedImpl {
+                        module_path: scope.module_path.clone(),
+                        trait_path: trait_path
+                            .segments
+                            .iter()
+                            .map(|segment| segment.ident.to_string())
+                            .collect(),
+                        self_type,
+                    });
+                }
+                _ => self.push(scope, item),
+            }
+        }
+    }
+
+    /// Index the items an item-level macro invocation is given, when they parse as items
+    ///
+    /// Macros such as tokio's `cfg_rt! { pub mod runtime; }` emit their input with extra
+    /// attributes, so its items are indexed as written; whatever the macro adds (a `#[cfg]`
+    /// among them) is not seen. Invocations whose input is not a list of items, such as
+    /// `lazy_static!` or `bitflags!`, are recorded in `unexpanded_macros` when they mention
+    /// `pub`, since their items are missing from the index.
+    fn walk_invocation(&mut self, mac: &syn::Macro, scope: &Scope) {
+        match syn::parse2::<syn::File>(mac.tokens.clone()) {
+            Ok(file) if matches!(mac.delimiter, syn::MacroDelimiter::Brace(_)) => {
+                self.walk_items(&file.items, scope);
+            }
+            _ if mentions_pub(&mac.tokens) => {
+                let path = mac
+                    .path
+                    .segments
+                    .iter()
+                    .map(|segment| segment.ident.to_string())
+                    .collect::<Vec<_>>()
+                    .join("::");
+                self.unexpanded_macros
+                    .push((scope.module_path.clone(), path));
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether an item is compiled in the configuration being indexed
+    fn included(&self, attrs: &[syn::Attribute]) -> bool {
+        match &self.cfg {
+            Some(cfg) => cfg.is_enabled(attrs),
+            None => self.include_tests || !is_cfg_test(attrs),
+        }
+    }
+
+    /// Record a named item, ignoring `impl` blocks, macro invocations and friends
+    fn push(&mut self, scope: &Scope, item: &Item) {
+        let (kind, vis) = match item {
+            Item::Fn(item) => (proc_macro_kind(&item.attrs), &item.vis),
+            Item::Struct(item) => (ItemKind::Struct, &item.vis),
+            Item::Enum(item) => (ItemKind::Enum, &item.vis),
+            Item::Union(item) => (ItemKind::Union, &item.vis),
+            Item::Trait(item) => (ItemKind::Trait, &item.vis),
+            Item::TraitAlias(item) => (ItemKind::Trait, &item.vis),
+            Item::Type(item) => (ItemKind::TypeAlias, &item.vis),
+            Item::Const(item) => (ItemKind::Constant, &item.vis),
+            Item::Static(item) => (ItemKind::Static, &item.vis),
+            Item::Mod(item) => (ItemKind::Module, &item.vis),
+            _ => return,
+        };
+        let attrs = item_attrs(item);
+        // A derive is named by its attribute rather than by the function
+        let Some(name) = derive_name(attrs).or_else(|| item_name(item)) else {
+            return;
+        };
+        let (unstable, hidden) = scope.flags(attrs);
+        let methods = match item {
+            Item::Trait(item) => item
+                .items
+                .iter()
+                .filter_map(|trait_item| match trait_item {
+                    syn::TraitItem::Fn(method) => Some(method.sig.ident.to_string()),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        self.items.push(IndexedItem {
+            name,
+            kind,
+            module_path: scope.module_path.clone(),
+            visibility: item_visibility(vis),
+            docs: doc_summary(attrs),
+            file: scope.file.clone(),
+            unstable,
+            hidden,
+            methods,
+        });
+    }
+
+    /// Descend into an inline module or the file an out-of-line one points at
+    fn walk_module(&mut self, item_mod: &ItemMod, parent: &Scope) {
+        let name = item_mod.ident.to_string();
+        let mut path = parent.module_path.clone();
+        path.push(name.clone());
+        let (unstable, hidden) = parent.flags(&item_mod.attrs);
+        let visibility = item_visibility(&item_mod.vis);
+
+        let declared_at = item_mod.span().end().line;
+        let macro_use = has_attr(&item_mod.attrs, "macro_use");
+
+        if let Some((_, items)) = &item_mod.content {
+            let dir = parent.child_dir.join(&name);
+            self.modules.push(IndexedModule {
+                path: path.clone(),
+                file: parent.file.clone(),
+                visibility,
+                declared_at,
+                macro_use,
+            });
+            let scope = Scope {
+                file: parent.file.clone(),
+                module_path: path,
+                child_dir: dir.clone(),
+                path_base: dir,
+                unstable,
+                hidden,
+            };
+            self.walk_items(items, &scope);
+            return;
+        }
+
+        let explicit = path_attr(&item_mod.attrs);
+        let target = match &explicit {
+            Some(relative) => parent.path_base.join(relative),
+            None => {
+                let candidates = [
+                    parent.child_dir.join(format!("{name}.rs")),
+                    parent.child_dir.join(&name).join("mod.rs"),
+                ];
+                match candidates.into_iter().find(|path| path.is_file()) {
+                    Some(path) => path,
+                    None => return,
+                }
+            }
+        };
+        if !self.visited.insert(target.clone()) {
+            return;
+        }
+        let Ok(source) = SourceFile::read(&target) else {
+            return;
+        };
+        if !self.included(&source.ast.attrs) {
+            return;
+        }
+
+        // Files loaded through `#[path]` and `mod.rs` files own their directory; any other
+        // file's children live in a directory named after it
+        let target_dir = target.parent().unwrap_or(Path::new("")).to_path_buf();
+        let is_mod_rs = target.file_name().is_some_and(|n| n == "mod.rs");
+        let child_dir = if explicit.is_some() || is_mod_rs {
+            target_dir.clone()
+        } else {
+            target_dir.join(&name)
+        };
+
+        self.modules.push(IndexedModule {
+            path: path.clone(),
+            file: target.clone(),
+            visibility,
+            declared_at,
+            macro_use,
+        });
+        let scope = Scope {
+            file: target,
+            module_path: path,
+            child_dir,
+            path_base: target_dir,
+            unstable: unstable || is_unstable(&source.ast.attrs),
+            hidden: hidden || is_doc_hidden(&source.ast.attrs),
+        };
+        self.walk_items(&source.ast.items, &scope);
+    }
+}
+
+// Helper functions
+
+/// Value of a `#[path = "..."]` attribute
+fn path_attr(attrs: &[syn::Attribute]) -> Option<String> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("path"))
+        .find_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                value:
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(path),
+                        ..
+                    }),
+                ..
+            }) => Some(path.value()),
+            _ => None,
+        })
+}
+
+/// Whether a token stream contains the `pub` keyword, at any depth
+fn mentions_pub(tokens: &proc_macro2::TokenStream) -> bool {
+    tokens.clone().into_iter().any(|token| match token {
+        proc_macro2::TokenTree::Ident(ident) => ident == "pub",
+        proc_macro2::TokenTree::Group(group) => mentions_pub(&group.stream()),
+        _ => false,
+    })
+}
+
+/// Whether `attrs` include `#[name]` or `#[name(...)]`
+fn has_attr(attrs: &[syn::Attribute], name: &str) -> bool {
+    attrs.iter().any(|attr| attr.path().is_ident(name))
+}
+
+/// Kind of a function item: a macro when it defines a proc macro
+fn proc_macro_kind(attrs: &[syn::Attribute]) -> ItemKind {
+    if has_attr(attrs, "proc_macro") {
+        ItemKind::Macro
+    } else if has_attr(attrs, "proc_macro_derive") {
+        ItemKind::DeriveMacro
+    } else if has_attr(attrs, "proc_macro_attribute") {
+        ItemKind::AttributeMacro
+    } else {
+        ItemKind::Function
+    }
+}
+
+/// Name given by `#[proc_macro_derive(Name, attributes(...))]`
+fn derive_name(attrs: &[syn::Attribute]) -> Option<String> {
+    let attr = attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("proc_macro_derive"))?;
+    let args = attr
+        .parse_args_with(Punctuated::<syn::Meta, Token![,]>::parse_terminated)
+        .ok()?;
+    args.first()?.path().get_ident().map(ToString::to_string)
+}
+
+/// Outer attributes of an item
+fn item_attrs(item: &Item) -> &[syn::Attribute] {
+    match item {
+        Item::Const(item) => &item.attrs,
+        Item::Enum(item) => &item.attrs,
+        Item::ExternCrate(item) => &item.attrs,
+        Item::Fn(item) => &item.attrs,
+        Item::ForeignMod(item) => &item.attrs,
+        Item::Impl(item) => &item.attrs,
+        Item::Macro(item) => &item.attrs,
+        Item::Mod(item) => &item.attrs,
+        Item::Static(item) => &item.attrs,
+        Item::Struct(item) => &item.attrs,
+        Item::Trait(item) => &item.attrs,
+        Item::TraitAlias(item) => &item.attrs,
+        Item::Type(item) => &item.attrs,
+        Item::Union(item) => &item.attrs,
+        Item::Use(item) => &item.attrs,
+        _ => &[],
+    }
+}
+
+/// Whether an item carries the standard library's `#[unstable(...)]`
+fn is_unstable(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| attr.path().is_ident("unstable"))
+}
+
+/// Whether an item is `#[doc(hidden)]`
+fn is_doc_hidden(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("doc")
+            && attr
+                .parse_args::<syn::Ident>()
+                .is_ok_and(|ident| ident == "hidden")
+    })
+}
+
+/// Whether an item is only compiled for tests
+fn is_cfg_test(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("cfg")
+            && attr
+                .parse_args::<syn::Ident>()
+                .is_ok_and(|ident| ident == "test")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::Workspace;
+    use crate::test_support::write;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_index_follows_mod_declarations_and_path_attributes() -> Result<()> {
+        let dir = TempDir::new()?;
+        let root = dir.path();
+        write(root, "Cargo.toml", "[package]\nname = \"app\"\n")?;
+        write(
+            root,
+            "src/lib.rs",
+            "pub mod models;\n#[path = \"generated/api.rs\"]\npub mod api;\n\
+             mod util {\n    pub(crate) fn helper() {}\n    mod nested;\n}\n\
+             /// The crate root type.\n///\n/// More detail.\npub struct Root;\n\
+             #[macro_export]\nmacro_rules! ensure { () => {}; }\n\
+             #[cfg(test)]\nmod tests {\n    fn hidden() {}\n}\n",
+        )?;
+        write(root, "src/models/mod.rs", "pub mod user;\n")?;
+        write(
+            root,
+            "src/models/user.rs",
+            "pub struct User;\npub(super) enum Role {}\nmod detail;\n",
+        )?;
+        write(
+            root,
+            "src/models/user/detail.rs",
+            "pub(in crate::models) const MAX: u32 = 1;\n",
+        )?;
+        write(root, "src/generated/api.rs", "pub fn call() {}\nmod v1;\n")?;
+        write(root, "src/generated/v1.rs", "pub type Id = u64;\n")?;
+        write(root, "src/util/nested.rs", "static COUNT: u8 = 0;\n")?;
+
+        let workspace = Workspace::load(root)?;
+        let roots = target_roots(&workspace.packages[0]);
+        assert_eq!(roots, vec![root.join("src/lib.rs")]);
+
+        let index = CrateIndex::build(&roots[0])?;
+        let find = |path: &str| index.items.iter().find(|item| item.path() == path);
+
+        let user = find("crate::models::user::User").expect("User is indexed");
+        assert_eq!(user.kind, ItemKind::Struct);
+        assert_eq!(user.visibility, ItemVisibility::Public);
+        assert_eq!(
+            find("crate::models::user::Role").unwrap().visibility,
+            ItemVisibility::Super
+        );
+        assert_eq!(
+            find("crate::models::user::detail::MAX").unwrap().visibility,
+            ItemVisibility::Restricted {
+                path: "crate::models".to_string()
+            }
+        );
+        assert!(find("crate::api::call").is_some());
+        assert!(find("crate::api::v1::Id").is_some());
+        assert_eq!(
+            find("crate::util::helper").unwrap().visibility,
+            ItemVisibility::Crate
+        );
+        assert_eq!(
+            find("crate::util::nested::COUNT").unwrap().visibility,
+            ItemVisibility::Private
+        );
+        assert_eq!(
+            find("crate::Root").unwrap().docs.as_deref(),
+            Some("The crate root type.")
+        );
+        assert_eq!(find("crate::ensure").unwrap().kind, ItemKind::Macro);
+        assert!(find("crate::tests").is_none() && find("crate::tests::hidden").is_none());
+
+        assert_eq!(index.modules.len(), 8);
+        Ok(())
+    }
+
+    #[test]
+    fn test_macros_follow_textual_scope() -> Result<()> {
+        let dir = TempDir::new()?;
+        let root = dir.path();
+        write(
+            root,
+            "src/lib.rs",
+            "mod early;\nmacro_rules! local { () => {}; }\nmod late;\n\
+             #[macro_use]\nmod helpers {\n    macro_rules! helper { () => {}; }\n}\nfn run() {}\n\
+             #[proc_macro_derive(Builder, attributes(builder))]\npub fn derive_builder() {}\n\
+             #[proc_macro]\npub fn sql() {}\n#[proc_macro_attribute]\npub fn route() {}\n\
+             #[macro_use]\nextern crate serde;\n",
+        )?;
+        write(root, "src/early.rs", "")?;
+        write(root, "src/late.rs", "")?;
+
+        let index = CrateIndex::build(&root.join("src/lib.rs"))?;
+        let in_scope = |module: &[&str], line: usize| -> Vec<String> {
+            let module: Vec<String> = module.iter().map(|s| s.to_string()).collect();
+            index
+                .macros_in_scope(&module, line)
+                .into_iter()
+                .map(|indexed| indexed.name.clone())
+                .collect()
+        };
+
+        assert_eq!(in_scope(&[], 8), vec!["local", "helper"]);
+        assert!(in_scope(&[], 1).is_empty());
+        // Declared before the definition, and before `helpers` ends
+        assert!(in_scope(&["early"], 1).is_empty());
+        assert_eq!(in_scope(&["late"], 1), vec!["local"]);
+        assert_eq!(in_scope(&["helpers"], 7), vec!["local", "helper"]);
+
+        let find = |path: &str| index.items.iter().find(|item| item.path() == path);
+        assert_eq!(find("crate::Builder").unwrap().kind, ItemKind::DeriveMacro);
+        assert_eq!(find("crate::sql").unwrap().kind, ItemKind::Macro);
+        assert_eq!(find("crate::route").unwrap().kind, ItemKind::AttributeMacro);
+        assert!(find("crate::local").is_none());
+        assert_eq!(index.macro_use_crates, vec!["serde"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_target_roots_include_binaries() -> Result<()> {
+        let dir = TempDir::new()?;
+        let root = dir.path();
+        write(
+            root,
+            "Cargo.toml",
+            "[package]\nname = \"app\"\n\n[lib]\npath = \"lib/app.rs\"\n",
+        )?;
+        write(root, "lib/app.rs", "")?;
+        write(root, "src/main.rs", "fn main() {}\n")?;
+        write(root, "src/bin/tool.rs", "fn main() {}\n")?;
+        write(root, "src/bin/server/main.rs", "fn main() {}\n")?;
+
+        let workspace = Workspace::load(root)?;
+        assert_eq!(
+            target_roots(&workspace.packages[0]),
+            vec![
+                root.join("lib/app.rs"),
+                root.join("src/main.rs"),
+                root.join("src/bin/server/main.rs"),
+                root.join("src/bin/tool.rs"),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,630 @@
+//! Dependency indexing for Rusty Refactor
+//!
+//! This module reads the sources of the workspace's dependencies from the Cargo registry
+//! cache or a vendor directory and lists each crate's public API, evaluating `#[cfg]` with
+//! the features the workspace enables so suggestions match what will compile offline.
+
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+use crate::cfg::CfgSet;
+use crate::crate_index::CrateIndex;
+use crate::lockfile::Lockfile;
+use crate::manifest::Workspace;
+use crate::reexports::{CrateGraph, PathFilter};
+use crate::std_catalog::{cached, CACHE_VERSION};
+use crate::{ImportableItem, ItemSource, ItemVisibility};
+
+/// Directories dependency sources are looked up in
+#[derive(Debug, Clone, Default)]
+pub struct SourceDirs {
+    /// `~/.cargo/registry/src/<index>` directories
+    pub registry: Vec<PathBuf>,
+    /// Vendor directories named in `.cargo/config.toml`, plus `vendor/`
+    pub vendor: Vec<PathBuf>,
+}
+
+impl SourceDirs {
+    /// Source directories of `$CARGO_HOME` and the workspace's vendor configuration
+    pub fn discover(workspace_root: &Path) -> Self {
+        let mut dirs = Self::default();
+
+        let cargo_home = std::env::var_os("CARGO_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cargo")));
+        if let Some(Ok(entries)) =
+            cargo_home.map(|home| std::fs::read_dir(home.join("registry/src")))
+        {
+            let mut registry: Vec<PathBuf> = entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir())
+                .collect();
+            registry.sort();
+            dirs.registry = registry;
+        }
+
+        for config in [".cargo/config.toml", ".cargo/config"] {
+            let Ok(text) = std::fs::read_to_string(workspace_root.join(config)) else {
+                continue;
+            };
+            let Ok(table) = text.parse::<Table>() else {
+                continue;
+            };
+            let sources = table.get("source").and_then(Value::as_table);
+            for source in sources.into_iter().flat_map(|sources| sources.values()) {
+                if let Some(directory) = source.get("directory").and_then(Value::as_str) {
+                    dirs.vendor.push(workspace_root.join(directory));
+                }
+            }
+        }
+        let default_vendor = workspace_root.join("vendor");
+        if default_vendor.is_dir() && !dirs.vendor.contains(&default_vendor) {
+            dirs.vendor.push(default_vendor);
+        }
+        dirs
+    }
+
+    /// Directory holding `package` at `version`, or the newest one matching `requirement`
+    /// when the version is not locked
+    pub fn locate(
+        &self,
+        package: &str,
+        version: Option<&str>,
+        requirement: &str,
+    ) -> Option<PathBuf> {
+        if let Some(version) = version {
+            let versioned = format!("{}-{}", package, version);
+            for dir in &self.vendor {
+                if dir.join(&versioned).join("Cargo.toml").is_file() {
+                    return Some(dir.join(&versioned));
+                }
+                let plain = dir.join(package);
+                let manifest = read_manifest(&plain).ok();
+                let plain_version = manifest
+                    .as_ref()
+                    .and_then(|m| m.get("package"))
+                    .and_then(|p| p.get("version"))
+                    .and_then(Value::as_str);
+                if plain_version == Some(version) {
+                    return Some(plain);
+                }
+            }
+            return self
+                .registry
+                .iter()
+                .map(|dir| dir.join(&versioned))
+                .find(|dir| dir.join("Cargo.toml").is_file());
+        }
+
+        let requirement = semver::VersionReq::parse(requirement).ok();
+        let prefix = format!("{}-", package);
+        let mut best: Option<(semver::Version, PathBuf)> = None;
+        for dir in self.vendor.iter().chain(&self.registry) {
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let Some(Ok(version)) = name.strip_prefix(&prefix).map(semver::Version::parse)
+                else {
+                    continue;
+                };
+                let matches = requirement.as_ref().is_none_or(|req| req.matches(&version));
+                if matches && best.as_ref().is_none_or(|(newest, _)| version > *newest) {
+                    best = Some((version, entry.path()));
+                }
+            }
+        }
+        if best.is_none() {
+            // An unversioned vendor directory is the only copy there is
+            return self
+                .vendor
+                .iter()
+                .map(|dir| dir.join(package))
+                .find(|dir| dir.join("Cargo.toml").is_file());
+        }
+        best.map(|(_, path)| path)
+    }
+}
+
+/// A dependency as the workspace uses it, with features unified across members
+#[derive(Debug, Default)]
+struct Request {
+    package: String,
+    locked: Option<String>,
+    requirement: String,
+    /// Directory of a `path` dependency
+    path: Option<PathBuf>,
+    features: BTreeSet<String>,
+    default_features: bool,
+}
+
+/// Public items of the workspace's dependencies
+#[derive(Debug, Clone, Default)]
+pub struct DependencyItems {
+    pub items: Vec<ImportableItem>,
+    /// Crates with item-level macro invocations (`lazy_static!`, `bitflags!`, ...) that may
+    /// declare public items, which are missing from `items` since macros are not expanded
+    pub partially_indexed: Vec<String>,
+}
+
+/// Public items of every dependency the workspace containing `workspace_root` declares
+///
+/// Dependencies whose sources are not on disk (not yet downloaded, or from git) are
+/// skipped. With a `cache_dir`, the items of registry and vendored crates are cached there
+/// per crate version and feature set; `path` dependencies are always re-read.
+///
+/// Macro invocations are not expanded: the items passed to one are indexed as written when
+/// they parse as items (tokio's `cfg_rt! { pub mod runtime; }`), ignoring any `#[cfg]` the
+/// macro adds, and crates with other invocations are listed as `partially_indexed`.
+pub fn dependency_items(
+    workspace_root: &Path,
+    sources: &SourceDirs,
+    cache_dir: Option<&Path>,
+) -> Result<DependencyItems> {
+    let workspace = Workspace::load(workspace_root)?;
+    let mut crates = workspace.dependencies()?;
+    let lockfile = Lockfile::read(&workspace)?;
+    if let Some(lockfile) = &lockfile {
+        lockfile.resolve(&workspace, &mut crates);
+    }
+
+    let mut requests: BTreeMap<(String, String), Request> = BTreeMap::new();
+    for dep in &crates {
+        let locked = dep.resolved.as_ref().map(|r| r.version.clone());
+        let key = (
+            dep.name.clone(),
+            locked.clone().unwrap_or_else(|| dep.version.clone()),
+        );
+        let request = requests.entry(key).or_insert_with(|| Request {
+            package: dep.package.clone(),
+            locked,
+            requirement: dep.version.clone(),
+            path: dep.source.strip_prefix("path+").map(PathBuf::from),
+            ..Request::default()
+        });
+        request.features.extend(dep.features.iter().cloned());
+        request.default_features |= dep.default_features;
+    }
+
+    let mut dependencies = DependencyItems::default();
+    let mut seen = HashSet::new();
+    for ((name, _), request) in &requests {
+        let dir = match &request.path {
+            Some(path) => Some(path.clone()),
+            None => sources.locate(
+                &request.package,
+                request.locked.as_deref(),
+                &request.requirement,
+            ),
+        };
+        let Some(dir) = dir else {
+            continue;
+        };
+
+        let build = || index_dependency(&dir, name, request, sources, lockfile.as_ref());
+        let found = match (cache_dir, &request.path) {
+            (Some(cache_dir), None) => {
+                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                (
+                    CACHE_VERSION,
+                    name,
+                    &dir,
+                    &request.features,
+                    request.default_features,
+                )
+                    .hash(&mut hasher);
+                let file = cache_dir.join(format!("dependency-{:016x}.json", hasher.finish()));
+                cached(&file, build)
+            }
+            _ => build(),
+        };
+        // A crate that fails to parse should not hide the others
+        let Ok((items, partial)) = found else {
+            continue;
+        };
+        if partial && !dependencies.partially_indexed.contains(name) {
+            dependencies.partially_indexed.push(name.clone());
+        }
+        dependencies.items.extend(
+            items
+                .into_iter()
+                .filter(|item| seen.insert(item.full_path.clone())),
+        );
+    }
+    Ok(dependencies)
+}
+
+/// Public items of one dependency, named the way the workspace refers to the crate, and
+/// whether macro invocations may hide some of them
+fn index_dependency(
+    dir: &Path,
+    name: &str,
+    request: &Request,
+    sources: &SourceDirs,
+    lockfile: Option<&Lockfile>,
+) -> Result<(Vec<ImportableItem>, bool)> {
+    let manifest = read_manifest(dir)?;
+    let Some(root) = lib_root(dir, &manifest) else {
+        return Ok((Vec::new(), false));
+    };
+    let requested: Vec<String> = request.features.iter().cloned().collect();
+    let features = enabled_features(&manifest, &requested, request.default_features);
+    let index = CrateIndex::build_with(&root, Some(&CfgSet::host(features.iter().cloned())))?;
+
+    // Crates this one may re-export from, indexed with the features it asks them for
+    let package = manifest.get("package");
+    let version = package
+        .and_then(|p| p.get("version"))
+        .and_then(Value::as_str);
+    let mut secondary = Vec::new();
+    for (key, spec) in normal_dependencies(&manifest) {
+        let optional = spec.get("optional").and_then(Value::as_bool) == Some(true);
+        if optional && !features.contains(key) && !features.contains(&format!("dep:{}", key)) {
+            continue;
+        }
+        let dep_package = spec.get("package").and_then(Value::as_str).unwrap_or(key);
+        let locked = lockfile
+            .zip(version)
+            .and_then(|(lockfile, version)| {
+                lockfile.dependency_of(&request.package, version, dep_package)
+            })
+            .map(|locked| locked.version.clone());
+        let requirement = spec
+            .as_str()
+            .or_else(|| spec.get("version").and_then(Value::as_str))
+            .unwrap_or("*");
+        let dep_dir = match spec.get("path").and_then(Value::as_str) {
+            Some(path) => Some(dir.join(path)),
+            None => sources.locate(dep_package, locked.as_deref(), requirement),
+        };
+        let Some(dep_dir) = dep_dir else {
+            continue;
+        };
+        let Ok(dep_manifest) = read_manifest(&dep_dir) else {
+            continue;
+        };
+        let Some(dep_root) = lib_root(&dep_dir, &dep_manifest) else {
+            continue;
+        };
+        let dep_features = enabled_features(
+            &dep_manifest,
+            &string_array(spec.get("features")),
+            spec.get("default-features").and_then(Value::as_bool) != Some(false),
+        );
+        let cfg = CfgSet::host(dep_features);
+        if let Ok(dep_index) = CrateIndex::build_with(&dep_root, Some(&cfg)) {
+            secondary.push((key.replace('-', "_"), dep_index));
+        }
+    }
+
+    let graph = CrateGraph::new(
+        std::iter::once((name.to_string(), &index))
+            .chain(secondary.iter().map(|(key, index)| (key.clone(), index)))
+            .collect(),
+    );
+    let filter = PathFilter {
+        crate_visible: false,
+        stable_only: true,
+        from: None,
+    };
+    let indexes: Vec<&CrateIndex> = std::iter::once(&index)
+        .chain(secondary.iter().map(|(_, index)| index))
+        .collect();
+
+    let traits = graph.trait_infos();
+    let mut items: Vec<ImportableItem> = graph
+        .public_paths(0, filter)
+        .into_iter()
+        .filter_map(|((krate, i), paths)| {
+            let item = &indexes[krate].items[i];
+            let path = paths.into_iter().next()?;
+            Some(ImportableItem {
+                full_path: path.join("::"),
+                name: path.last().cloned().unwrap_or_default(),
+                kind: item.kind,
+                source: ItemSource::External {
+                    crate_name: name.to_string(),
+                },
+                visibility: ItemVisibility::Public,
+                docs: item.docs.clone(),
+                is_macro: item.kind.is_macro(),
+                deprecation: None,
+                unstable: false,
+                trait_info: traits.get(&(krate, i)).cloned(),
+            })
+        })
+        .collect();
+    items.sort_by(|a, b| a.full_path.cmp(&b.full_path));
+    let partial = indexes
+        .iter()
+        .any(|index| !index.unexpanded_macros.is_empty());
+    Ok((items, partial))
+}
+
+/// Features turned on by `requested` (and `default`), following the `[features]` table
+///
+/// Enabling an optional dependency also enables its implicit feature of the same name.
+fn enabled_features(manifest: &Table, requested: &[String], default: bool) -> HashSet<String> {
+    let table = manifest.get("features").and_then(Value::as_table);
+    let mut enabled = HashSet::new();
+    let mut pending: Vec<String> = requested.to_vec();
+    if default {
+        pending.push("default".to_string());
+    }
+
+    while let Some(feature) = pending.pop() {
+        // `dep/feature` enables the dependency too; `dep?/feature` only configures it
+        let feature = match feature.split_once('/') {
+            Some((dep, _)) if dep.ends_with('?') => continue,
+            Some((dep, _)) => dep.to_string(),
+            None => feature,
+        };
+        if !enabled.insert(feature.clone()) {
+            continue;
+        }
+        if let Some(implied) = table.and_then(|t| t.get(&feature)) {
+            pending.extend(string_array(Some(implied)));
+        }
+    }
+    enabled
+}
+
+/// `[dependencies]` entries, including target-specific ones
+fn normal_dependencies(manifest: &Table) -> Vec<(&str, &Value)> {
+    let mut tables: Vec<&Table> = Vec::new();
+    tables.extend(manifest.get("dependencies").and_then(Value::as_table));
+    let targets = manifest.get("target").and_then(Value::as_table);
+    for target in targets.into_iter().flat_map(|targets| targets.values()) {
+        tables.extend(target.get("dependencies").and_then(Value::as_table));
+    }
+
+    let mut deps: HashMap<&str, &Value> = HashMap::new();
+    for table in tables {
+        for (key, spec) in table {
+            deps.entry(key.as_str()).or_insert(spec);
+        }
+    }
+    let mut deps: Vec<(&str, &Value)> = deps.into_iter().collect();
+    deps.sort_by_key(|(key, _)| *key);
+    deps
+}
+
+// Helper functions
+
+fn read_manifest(dir: &Path) -> Result<Table> {
+    let path = dir.join("Cargo.toml");
+    let text = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    text.parse()
+        .with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Root file of a package's library target, if it has one
+fn lib_root(dir: &Path, manifest: &Table) -> Option<PathBuf> {
+    let path = manifest
+        .get("lib")
+        .and_then(|lib| lib.get("path"))
+        .and_then(Value::as_str)
+        .unwrap_or("src/lib.rs");
+    Some(dir.join(path)).filter(|path| path.is_file())
+}
+
+fn string_array(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_array)
+        .map(|values| {
+            values
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::write;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const LOCK: &str = r#"
+version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["fancy", "plain"]
+
+[[package]]
+name = "fancy"
+version = "1.2.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+dependencies = ["fancy-macros"]
+
+[[package]]
+name = "fancy-macros"
+version = "1.0.3"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "plain"
+version = "0.2.1"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#;
+
+    /// A workspace using one registry crate and one vendored crate
+    fn sample() -> Result<(TempDir, SourceDirs)> {
+        let dir = TempDir::new()?;
+        let root = dir.path();
+        write(
+            root,
+            "app/Cargo.toml",
+            "[package]\nname = \"app\"\n\n[dependencies]\n\
+             fancy = { version = \"1\", features = [\"extra\"] }\n\
+             plain = { version = \"0.2\", default-features = false }\n",
+        )?;
+        write(root, "app/Cargo.lock", LOCK)?;
+        write(
+            root,
+            "app/.cargo/config.toml",
+            "[source.vendored-sources]\ndirectory = \"third-party\"\n",
+        )?;
+
+        let registry = root.join("registry/src/index.crates.io-6f17d22bba15001f");
+        write(
+            &registry,
+            "fancy-1.2.0/Cargo.toml",
+            "[package]\nname = \"fancy\"\nversion = \"1.2.0\"\n\n\
+             [dependencies]\nfancy-macros = { version = \"1\", optional = true }\n\n\
+             [features]\ndefault = [\"std\"]\nstd = []\nextra = [\"derive\"]\n\
+             derive = [\"dep:fancy-macros\"]\nunused = []\n",
+        )?;
+        write(
+            &registry,
+            "fancy-1.2.0/src/lib.rs",
+            "pub mod value;\npub use value::Value;\n\
+             #[cfg(feature = \"derive\")]\npub use fancy_macros::Fancy;\n\
+             #[cfg(feature = \"unused\")]\npub fn unused() {}\n\
+             #[cfg(test)]\nmod tests {}\n\
+             #[doc(hidden)]\npub mod __private { pub fn helper() {} }\n",
+        )?;
+        write(
+            &registry,
+            "fancy-1.2.0/src/value.rs",
+            "/// A dynamically typed value.\npub enum Value {}\n\
+             #[cfg(feature = \"std\")]\npub fn from_reader() {}\n",
+        )?;
+        write(
+            &registry,
+            "fancy-macros-1.0.3/Cargo.toml",
+            "[package]\nname = \"fancy-macros\"\nversion = \"1.0.3\"\n",
+        )?;
+        write(
+            &registry,
+            "fancy-macros-1.0.3/src/lib.rs",
+            "pub struct Fancy;\n",
+        )?;
+        // An older copy that the lockfile does not select
+        write(
+            &registry,
+            "fancy-1.1.0/Cargo.toml",
+            "[package]\nname = \"fancy\"\nversion = \"1.1.0\"\n",
+        )?;
+        write(&registry, "fancy-1.1.0/src/lib.rs", "pub struct Old;\n")?;
+
+        write(
+            root,
+            "app/third-party/plain/Cargo.toml",
+            "[package]\nname = \"plain\"\nversion = \"0.2.1\"\n\n[features]\ndefault = [\"std\"]\nstd = []\n",
+        )?;
+        write(
+            root,
+            "app/third-party/plain/src/lib.rs",
+            "pub fn always() {}\n#[cfg(feature = \"std\")]\npub fn with_std() {}\n",
+        )?;
+
+        let mut sources = SourceDirs::discover(&root.join("app"));
+        sources.registry = vec![registry];
+        Ok((dir, sources))
+    }
+
+    #[test]
+    fn test_dependency_items_respect_lockfile_features_and_cfg() -> Result<()> {
+        let (dir, sources) = sample()?;
+        assert_eq!(sources.vendor, vec![dir.path().join("app/third-party")]);
+
+        let items = dependency_items(&dir.path().join("app"), &sources, None)?.items;
+        let paths: Vec<&str> = items.iter().map(|item| item.full_path.as_str()).collect();
+
+        assert!(paths.contains(&"fancy::Value"));
+        assert!(paths.contains(&"fancy::value::from_reader"));
+        assert!(
+            paths.contains(&"fancy::Fancy"),
+            "re-exported from an enabled optional dependency"
+        );
+        assert!(paths.contains(&"plain::always"));
+        assert!(
+            !paths.contains(&"plain::with_std"),
+            "default features are off for plain"
+        );
+        assert!(!paths.contains(&"fancy::unused"));
+        assert!(!paths.contains(&"fancy::Old"));
+        assert!(paths.iter().all(|path| !path.contains("__private")));
+
+        let value = items
+            .iter()
+            .find(|item| item.full_path == "fancy::Value")
+            .unwrap();
+        assert_eq!(value.docs.as_deref(), Some("A dynamically typed value."));
+        assert!(
+            matches!(&value.source, ItemSource::External { crate_name } if crate_name == "fancy")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unlocked_dependency_uses_newest_matching_version() -> Result<()> {
+        let (dir, sources) = sample()?;
+        fs::remove_file(dir.path().join("app/Cargo.lock"))?;
+
+        let fancy = sources.locate("fancy", None, "1").unwrap();
+        assert!(fancy.ends_with("fancy-1.2.0"));
+        assert!(sources
+            .locate("fancy", None, "~1.1")
+            .unwrap()
+            .ends_with("fancy-1.1.0"));
+        assert!(sources.locate("fancy", Some("1.3.0"), "1").is_none());
+
+        let cache = TempDir::new()?;
+        let items = dependency_items(&dir.path().join("app"), &sources, Some(cache.path()))?.items;
+        assert!(items.iter().any(|item| item.full_path == "fancy::Value"));
+        assert_eq!(fs::read_dir(cache.path())?.count(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_items_declared_in_macro_invocations() -> Result<()> {
+        let (dir, sources) = sample()?;
+        let registry = &sources.registry[0];
+        write(
+            registry,
+            "plain-0.2.1/Cargo.toml",
+            "[package]\nname = \"plain\"\nversion = \"0.2.1\"\n",
+        )?;
+        fs::remove_dir_all(dir.path().join("app/third-party"))?;
+        write(
+            registry,
+            "plain-0.2.1/src/lib.rs",
+            "macro_rules! cfg_rt { ($($item:item)*) => { $(#[cfg(feature = \"rt\")] $item)* } }\n\
+             cfg_rt! {\n    pub mod runtime;\n}\n\
+             lazy_static! {\n    pub static ref GLOBAL: u8 = 0;\n}\n\
+             impl_ops!(u8, u16);\n",
+        )?;
+        write(
+            registry,
+            "plain-0.2.1/src/runtime.rs",
+            "pub struct Runtime;\n",
+        )?;
+
+        let dependencies = dependency_items(&dir.path().join("app"), &sources, None)?;
+        let paths: Vec<&str> = dependencies
+            .items
+            .iter()
+            .map(|item| item.full_path.as_str())
+            .collect();
+        assert!(paths.contains(&"plain::runtime::Runtime"));
+        assert!(!paths.contains(&"plain::GLOBAL"));
+        assert_eq!(dependencies.partially_indexed, vec!["plain".to_string()]);
+
+        Ok(())
+    }
+}
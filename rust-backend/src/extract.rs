@@ -0,0 +1,1466 @@
+//! Extract-function refactoring for Rusty Refactor
+//!
+//! This module lifts a range of statements out of a function body into a new function.
+//! Parameters are inferred from the free variables of the selection, the return value from
+//! bindings that are still used after it, and each parameter is passed by value, `&` or
+//! `&mut` depending on how the selection uses it.
+
+use anyhow::{anyhow, bail, Result};
+use proc_macro2::{Span, TokenTree};
+use std::collections::{HashMap, HashSet};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use syn::{
+    BinOp, Block, Expr, FnArg, GenericParam, ImplItemFn, Item, ItemFn, Lit, Local, Pat, ReturnType,
+    Signature, Stmt, TraitItemFn, Type,
+};
+
+use crate::syntax::{flatten_use, span_encloses, span_info, SourceFile};
+use crate::{ExtractionResult, LifetimeSuggestion, SpanInfo};
+
+/// Extract the statements covered by `range` into a new function called `new_name`
+pub fn extract_function(
+    source: &SourceFile,
+    range: &SpanInfo,
+    new_name: &str,
+) -> Result<ExtractionResult> {
+    syn::parse_str::<syn::Ident>(new_name)
+        .map_err(|_| anyhow!("`{}` is not a valid function name", new_name))?;
+
+    let host = find_host(source, range)?;
+    let selection = find_selection(source, host.block, range)?;
+    let plan = ExtractionPlan::analyze(source, &host, &selection)?;
+
+    let indent = source.line_indent(source.byte_range(host.item_span).start);
+    let function = plan.render_function(source, &selection, new_name, "");
+    let placed = plan.render_function(source, &selection, new_name, indent);
+    let call = plan.render_call(new_name);
+
+    // Replace the selection with the call, then insert the new function after its host
+    let item_end = source.byte_range(host.item_span).end;
+    let mut modified = String::with_capacity(source.text.len() + placed.len() + call.len());
+    modified.push_str(&source.text[..selection.start]);
+    modified.push_str(&call);
+    modified.push_str(&source.text[selection.end..item_end]);
+    modified.push_str("\n\n");
+    modified.push_str(&placed);
+    modified.push_str(&source.text[item_end..]);
+
+    Ok(ExtractionResult {
+        required_imports: required_imports(source, &function),
+        suggested_lifetimes: plan.suggested_lifetimes(new_name),
+        extracted_code: function,
+        modified_file: modified,
+    })
+}
+
+/// A function whose body contains the selection
+struct HostFn<'ast> {
+    sig: &'ast Signature,
+    block: &'ast Block,
+    item_span: Span,
+    /// Whether the function is an associated item of an `impl` or `trait` block
+    is_associated: bool,
+}
+
+/// The selected statements of a block, with their byte range in the source text
+struct Selection<'ast> {
+    block: &'ast Block,
+    stmts: &'ast [Stmt],
+    start: usize,
+    end: usize,
+    /// Whether the selection ends with the block's tail expression
+    is_tail: bool,
+}
+
+fn find_host<'ast>(source: &'ast SourceFile, range: &SpanInfo) -> Result<HostFn<'ast>> {
+    let mut finder = HostFinder { hosts: Vec::new() };
+    finder.visit_file(&source.ast);
+
+    finder
+        .hosts
+        .into_iter()
+        .filter(|host| span_encloses(&span_info(host.block.span()), range))
+        .max_by_key(|host| source.byte_range(host.block.span()).start)
+        .ok_or_else(|| anyhow!("The selection is not inside a function body"))
+}
+
+fn find_selection<'ast>(
+    source: &SourceFile,
+    host: &'ast Block,
+    range: &SpanInfo,
+) -> Result<Selection<'ast>> {
+    let mut finder = BlockFinder { blocks: Vec::new() };
+    finder.visit_block(host);
+
+    // The innermost block enclosing the selection owns the selected statements
+    let block = finder
+        .blocks
+        .into_iter()
+        .filter(|block| span_encloses(&span_info(block.span()), range))
+        .max_by_key(|block| {
+            let span = span_info(block.span());
+            (span.line_start, span.column_start)
+        })
+        .unwrap_or(host);
+
+    let mut first = None;
+    let mut last = None;
+    for (i, stmt) in block.stmts.iter().enumerate() {
+        let span = span_info(stmt.span());
+        if span_encloses(range, &span) {
+            first.get_or_insert(i);
+            last = Some(i);
+        } else if overlaps(range, &span) {
+            bail!("The selection must cover complete statements");
+        }
+    }
+
+    let (first, last) = match (first, last) {
+        (Some(first), Some(last)) => (first, last),
+        _ => bail!("No complete statements are selected"),
+    };
+    let stmts = &block.stmts[first..=last];
+
+    Ok(Selection {
+        block,
+        stmts,
+        start: source.byte_range(stmts[0].span()).start,
+        end: source.byte_range(stmts[stmts.len() - 1].span()).end,
+        is_tail: last == block.stmts.len() - 1 && matches!(stmts.last(), Some(Stmt::Expr(_, None))),
+    })
+}
+
+fn overlaps(a: &SpanInfo, b: &SpanInfo) -> bool {
+    (a.line_start, a.column_start) < (b.line_end, b.column_end)
+        && (b.line_start, b.column_start) < (a.line_end, a.column_end)
+}
+
+/// How a parameter is handed to the extracted function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PassMode {
+    Value,
+    Ref,
+    RefMut,
+}
+
+/// A local binding visible at the start of the selection
+#[derive(Debug, Clone)]
+struct Binding {
+    ty: Option<String>,
+    mutable: bool,
+}
+
+#[derive(Debug)]
+struct Param {
+    name: String,
+    ty: String,
+    mode: PassMode,
+}
+
+#[derive(Debug)]
+struct Output {
+    name: String,
+    ty: String,
+    mutable: bool,
+}
+
+/// Everything needed to render the extracted function and its call site
+struct ExtractionPlan {
+    receiver: Option<&'static str>,
+    is_associated: bool,
+    is_async: bool,
+    params: Vec<Param>,
+    outputs: Vec<Output>,
+    generics: Vec<String>,
+    /// Return type when the selection ends with the block's tail expression
+    tail_type: Option<String>,
+    /// Wrapper (`Result<.., E>` text and constructor) when the selection uses `?`
+    try_wrapper: Option<(String, &'static str)>,
+    /// Text edits to apply to the selection (byte offset, inserted text, replaced length)
+    body_edits: Vec<(usize, String, usize)>,
+}
+
+impl ExtractionPlan {
+    fn analyze(source: &SourceFile, host: &HostFn, selection: &Selection) -> Result<Self> {
+        // What the selection itself does
+        let mut inside = Usage::default();
+        for stmt in selection.stmts {
+            inside.visit_stmt(stmt);
+        }
+        if inside.has_return {
+            bail!("Cannot extract statements containing `return`");
+        }
+        if inside.has_escape {
+            bail!("Cannot extract statements containing `break` or `continue` for a loop outside the selection");
+        }
+
+        // Which names are used once the selection is done
+        let mut whole = Usage::default();
+        whole.visit_block(host.block);
+        let used_after: HashSet<&str> = whole
+            .uses
+            .iter()
+            .filter(|(_, span)| source.byte_range(*span).start >= selection.end)
+            .map(|(name, _)| name.as_str())
+            .collect();
+
+        let bindings = bindings_before(source, host, selection.start);
+
+        // Parameters: outer bindings used before being redeclared inside the selection
+        let mut params: Vec<Param> = Vec::new();
+        let mut body_edits = Vec::new();
+        for (name, span) in &inside.uses {
+            let Some(binding) = bindings.get(name) else {
+                continue;
+            };
+            if params.iter().any(|p| &p.name == name) {
+                continue;
+            }
+            let use_start = source.byte_range(*span).start;
+            if inside
+                .declared
+                .iter()
+                .any(|(declared, at)| declared == name && source.byte_range(*at).start < use_start)
+            {
+                continue;
+            }
+
+            let ty = binding.ty.clone().ok_or_else(|| {
+                anyhow!("Cannot infer the type of `{}`; annotate its binding before extracting", name)
+            })?;
+            let mutated = inside.assigned.contains(name)
+                || inside.mut_borrowed.contains(name)
+                || (binding.mutable && inside.receivers.contains(name));
+            // Values that are not needed after the selection can simply be moved
+            let mode = if mutated {
+                if ty.starts_with("&mut ") {
+                    PassMode::Value
+                } else {
+                    PassMode::RefMut
+                }
+            } else if is_copy(&ty) || !used_after.contains(name.as_str()) {
+                PassMode::Value
+            } else {
+                PassMode::Ref
+            };
+
+            // The binding becomes a reference, so rewrite the places that relied on it being a value
+            match mode {
+                PassMode::RefMut => {
+                    for (target, at) in inside
+                        .direct_assigns
+                        .iter()
+                        .chain(&inside.direct_mut_borrows)
+                    {
+                        if target == name {
+                            body_edits.push((source.byte_range(*at).start, "*".to_string(), 0));
+                        }
+                    }
+                }
+                PassMode::Ref => {
+                    for (target, at) in &inside.direct_borrows {
+                        if target == name {
+                            let at = source.byte_range(*at);
+                            body_edits.push((at.start, name.clone(), at.len()));
+                        }
+                    }
+                }
+                PassMode::Value => {}
+            }
+
+            params.push(Param {
+                name: name.clone(),
+                ty,
+                mode,
+            });
+        }
+
+        // Outputs: bindings declared at the top level of the selection and used afterwards
+        let mut outputs: Vec<Output> = Vec::new();
+        for stmt in selection.stmts {
+            if let Stmt::Local(local) = stmt {
+                for (name, binding) in local_bindings(source, local) {
+                    if used_after.contains(name.as_str()) {
+                        let ty = binding.ty.ok_or_else(|| {
+                            anyhow!("Cannot infer the type of `{}`; annotate its binding before extracting", name)
+                        })?;
+                        outputs.retain(|o| o.name != name);
+                        outputs.push(Output {
+                            name,
+                            ty,
+                            mutable: binding.mutable,
+                        });
+                    }
+                }
+            }
+        }
+
+        let receiver = match host.sig.receiver() {
+            Some(_) if inside.uses.iter().any(|(name, _)| name == "self") => {
+                let host_kind = match host.sig.receiver().map(|r| r.ty.as_ref()) {
+                    Some(Type::Reference(r)) if r.mutability.is_none() => "&self",
+                    _ => "&mut self",
+                };
+                let mutates_self = inside.assigned.contains("self")
+                    || inside.mut_borrowed.contains("self")
+                    || inside.receivers.contains("self");
+                Some(if host_kind == "&mut self" && mutates_self {
+                    "&mut self"
+                } else {
+                    "&self"
+                })
+            }
+            _ => None,
+        };
+
+        let host_return = match &host.sig.output {
+            ReturnType::Default => "()".to_string(),
+            ReturnType::Type(_, ty) => source.snippet(ty.span()).to_string(),
+        };
+        let tail_type = match selection.stmts.last() {
+            Some(Stmt::Expr(tail, None)) if selection.is_tail => {
+                Some(if std::ptr::eq(selection.block, host.block) {
+                    host_return.clone()
+                } else {
+                    infer_type(source, tail).ok_or_else(|| {
+                        anyhow!(
+                            "Cannot infer the type of the tail expression `{}`",
+                            source.snippet(tail.span())
+                        )
+                    })?
+                })
+            }
+            _ => None,
+        };
+        let try_wrapper = if inside.has_try && tail_type.is_none() {
+            Some(try_wrapper(source, &host.sig.output)?)
+        } else {
+            None
+        };
+
+        let mut plan = Self {
+            receiver,
+            is_associated: host.is_associated,
+            is_async: host.sig.asyncness.is_some() && inside.has_await,
+            params,
+            outputs,
+            generics: Vec::new(),
+            tail_type,
+            try_wrapper,
+            body_edits,
+        };
+        plan.generics = plan.used_generics(source, host.sig);
+        Ok(plan)
+    }
+
+    /// Keep only the host's generic parameters that the new signature mentions
+    fn used_generics(&self, source: &SourceFile, sig: &Signature) -> Vec<String> {
+        let mut signature = self
+            .params
+            .iter()
+            .map(|p| p.ty.as_str())
+            .chain(self.outputs.iter().map(|o| o.ty.as_str()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        signature.push(' ');
+        signature.push_str(&self.return_type().unwrap_or_default());
+
+        sig.generics
+            .params
+            .iter()
+            .filter(|param| {
+                let name = match param {
+                    GenericParam::Type(t) => t.ident.to_string(),
+                    GenericParam::Lifetime(l) => l.lifetime.to_string(),
+                    GenericParam::Const(c) => c.ident.to_string(),
+                };
+                mentions(&signature, &name)
+            })
+            .map(|param| source.snippet(param.span()).to_string())
+            .collect()
+    }
+
+    fn return_type(&self) -> Option<String> {
+        if let Some(tail) = &self.tail_type {
+            return (tail != "()").then(|| tail.clone());
+        }
+
+        let inner = match self.outputs.as_slice() {
+            [] => "()".to_string(),
+            [single] => single.ty.clone(),
+            many => format!(
+                "({})",
+                many.iter()
+                    .map(|o| o.ty.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+
+        match &self.try_wrapper {
+            Some((wrapper, _)) => Some(wrapper.replace("{}", &inner)),
+            None => (inner != "()").then_some(inner),
+        }
+    }
+
+    fn output_value(&self) -> Option<String> {
+        match self.outputs.as_slice() {
+            [] => None,
+            [single] => Some(single.name.clone()),
+            many => Some(format!(
+                "({})",
+                many.iter()
+                    .map(|o| o.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+
+    fn render_function(
+        &self,
+        source: &SourceFile,
+        selection: &Selection,
+        name: &str,
+        indent: &str,
+    ) -> String {
+        let mut params: Vec<String> = self.receiver.iter().map(|r| r.to_string()).collect();
+        params.extend(self.params.iter().map(|p| match p.mode {
+            PassMode::Value => format!("{}: {}", p.name, p.ty),
+            PassMode::Ref => format!("{}: &{}", p.name, p.ty),
+            PassMode::RefMut => format!("{}: &mut {}", p.name, p.ty),
+        }));
+
+        let mut header = format!(
+            "{}{}fn {}",
+            indent,
+            if self.is_async { "async " } else { "" },
+            name
+        );
+        if !self.generics.is_empty() {
+            header.push_str(&format!("<{}>", self.generics.join(", ")));
+        }
+        header.push_str(&format!("({})", params.join(", ")));
+        if let Some(ret) = self.return_type() {
+            header.push_str(&format!(" -> {}", ret));
+        }
+
+        // Apply body edits back to front so earlier offsets stay valid
+        let mut body = source.text[selection.start..selection.end].to_string();
+        let mut edits = self.body_edits.clone();
+        edits.sort_by_key(|edit| std::cmp::Reverse(edit.0));
+        for (at, text, len) in edits {
+            let at = at - selection.start;
+            body.replace_range(at..at + len, &text);
+        }
+
+        let body_indent = format!("{}    ", indent);
+        let base_indent = source.line_indent(selection.start).len();
+        let mut lines: Vec<String> = body
+            .lines()
+            .enumerate()
+            .map(|(i, line)| {
+                let line = if i == 0 {
+                    line
+                } else {
+                    strip_indent(line, base_indent)
+                };
+                if line.trim().is_empty() {
+                    String::new()
+                } else {
+                    format!("{}{}", body_indent, line)
+                }
+            })
+            .collect();
+
+        if self.tail_type.is_none() {
+            let value = self.output_value();
+            match (&self.try_wrapper, value) {
+                (Some((_, ctor)), value) => lines.push(format!(
+                    "{}{}({})",
+                    body_indent,
+                    ctor,
+                    value.unwrap_or_else(|| "()".to_string())
+                )),
+                (None, Some(value)) => lines.push(format!("{}{}", body_indent, value)),
+                (None, None) => {}
+            }
+        }
+
+        format!("{} {{\n{}\n{}}}", header, lines.join("\n"), indent)
+    }
+
+    fn render_call(&self, name: &str) -> String {
+        let args: Vec<String> = self
+            .params
+            .iter()
+            .map(|p| match p.mode {
+                PassMode::Value => p.name.clone(),
+                PassMode::Ref => format!("&{}", p.name),
+                PassMode::RefMut => format!("&mut {}", p.name),
+            })
+            .collect();
+
+        let callee = if self.receiver.is_some() {
+            format!("self.{}", name)
+        } else if self.is_associated {
+            format!("Self::{}", name)
+        } else {
+            name.to_string()
+        };
+        let mut call = format!("{}({})", callee, args.join(", "));
+        if self.is_async {
+            call.push_str(".await");
+        }
+
+        if self.tail_type.is_some() {
+            return call;
+        }
+        if self.try_wrapper.is_some() {
+            call.push('?');
+        }
+
+        let binding = |o: &Output| format!("{}{}", if o.mutable { "mut " } else { "" }, o.name);
+        match self.outputs.as_slice() {
+            [] => format!("{};", call),
+            [single] => format!("let {} = {};", binding(single), call),
+            many => format!(
+                "let ({}) = {};",
+                many.iter().map(binding).collect::<Vec<_>>().join(", "),
+                call
+            ),
+        }
+    }
+
+    fn suggested_lifetimes(&self, name: &str) -> Vec<LifetimeSuggestion> {
+        let returns_reference = self.return_type().is_some_and(|ret| ret.contains('&'));
+        let borrows_params = self.receiver.is_some()
+            || self
+                .params
+                .iter()
+                .any(|p| p.mode != PassMode::Value || p.ty.starts_with('&'));
+
+        if returns_reference && borrows_params && self.params.len() + self.receiver.iter().len() > 1
+        {
+            vec![LifetimeSuggestion {
+                name: "'a".to_string(),
+                suggestion: format!(
+                    "`{}` returns a reference borrowed from its parameters; add a lifetime parameter 'a to tie them together",
+                    name
+                ),
+                confidence: 0.6,
+            }]
+        } else {
+            vec![]
+        }
+    }
+}
+
+/// Build the `?`-compatible return type from the host's `Result`/`Option` return type
+fn try_wrapper(source: &SourceFile, output: &ReturnType) -> Result<(String, &'static str)> {
+    let unsupported =
+        || anyhow!("The selection uses `?` but the function does not return `Result` or `Option`");
+    let ReturnType::Type(_, ty) = output else {
+        return Err(unsupported());
+    };
+    let Type::Path(path) = ty.as_ref() else {
+        return Err(unsupported());
+    };
+    let last = path.path.segments.last().ok_or_else(unsupported)?;
+    let syn::PathArguments::AngleBracketed(args) = &last.arguments else {
+        return Err(unsupported());
+    };
+    let first = args.args.first().ok_or_else(unsupported)?;
+
+    let full = source.byte_range(ty.span());
+    let arg = source.byte_range(first.span());
+    let wrapper = format!(
+        "{}{{}}{}",
+        &source.text[full.start..arg.start],
+        &source.text[arg.end..full.end]
+    );
+    let ctor = if last.ident == "Option" { "Some" } else { "Ok" };
+    Ok((wrapper, ctor))
+}
+
+/// Collect the bindings in scope at `before`: the host's parameters and earlier patterns
+fn bindings_before(source: &SourceFile, host: &HostFn, before: usize) -> HashMap<String, Binding> {
+    let mut bindings = HashMap::new();
+    for input in &host.sig.inputs {
+        if let FnArg::Typed(pat_type) = input {
+            if let Pat::Ident(ident) = pat_type.pat.as_ref() {
+                bindings.insert(
+                    ident.ident.to_string(),
+                    Binding {
+                        ty: Some(source.snippet(pat_type.ty.span()).to_string()),
+                        mutable: ident.mutability.is_some(),
+                    },
+                );
+            }
+        }
+    }
+
+    let mut collector = BindingCollector {
+        source,
+        before,
+        bindings: Vec::new(),
+    };
+    collector.visit_block(host.block);
+    bindings.extend(collector.bindings);
+    bindings
+}
+
+/// Bindings introduced by a `let` statement, with their declared or inferred types
+fn local_bindings(source: &SourceFile, local: &Local) -> Vec<(String, Binding)> {
+    let init = local.init.as_ref().map(|init| init.expr.as_ref());
+    let mut bindings = Vec::new();
+    pattern_bindings(source, &local.pat, None, init, &mut bindings);
+    bindings
+}
+
+fn pattern_bindings(
+    source: &SourceFile,
+    pat: &Pat,
+    ty: Option<&Type>,
+    init: Option<&Expr>,
+    bindings: &mut Vec<(String, Binding)>,
+) {
+    match pat {
+        Pat::Type(pat_type) => {
+            pattern_bindings(source, &pat_type.pat, Some(&pat_type.ty), init, bindings)
+        }
+        Pat::Ident(ident) => bindings.push((
+            ident.ident.to_string(),
+            Binding {
+                ty: ty
+                    .map(|ty| source.snippet(ty.span()).to_string())
+                    .or_else(|| init.and_then(|init| infer_type(source, init))),
+                mutable: ident.mutability.is_some(),
+            },
+        )),
+        Pat::Tuple(tuple) => {
+            let types = match ty {
+                Some(Type::Tuple(types)) if types.elems.len() == tuple.elems.len() => {
+                    types.elems.iter().map(Some).collect()
+                }
+                _ => vec![None; tuple.elems.len()],
+            };
+            for (elem, ty) in tuple.elems.iter().zip(types) {
+                pattern_bindings(source, elem, ty, None, bindings);
+            }
+        }
+        _ => {
+            let mut names = PatternNames::default();
+            names.visit_pat(pat);
+            for (name, mutable) in names.names {
+                bindings.push((name, Binding { ty: None, mutable }));
+            }
+        }
+    }
+}
+
+/// Best-effort type of an initializer expression, for bindings without annotations
+fn infer_type(source: &SourceFile, expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Lit(lit) => match &lit.lit {
+            // Unsuffixed numbers take whatever type later uses demand, so they are not guessed
+            Lit::Int(int) if !int.suffix().is_empty() => Some(int.suffix().to_string()),
+            Lit::Float(float) if !float.suffix().is_empty() => Some(float.suffix().to_string()),
+            Lit::Str(_) => Some("&str".to_string()),
+            Lit::ByteStr(_) => Some("&[u8]".to_string()),
+            Lit::Bool(_) => Some("bool".to_string()),
+            Lit::Char(_) => Some("char".to_string()),
+            _ => None,
+        },
+        Expr::Macro(mac) if mac.mac.path.is_ident("format") => Some("String".to_string()),
+        Expr::MethodCall(call) if call.method == "to_string" => Some("String".to_string()),
+        Expr::MethodCall(call) if call.method == "to_owned" => infer_type(source, &call.receiver)
+            .map(|ty| match ty.as_str() {
+                "&str" => "String".to_string(),
+                _ => ty.trim_start_matches('&').to_string(),
+            }),
+        Expr::Call(call) => match call.func.as_ref() {
+            // `Type::new(..)` and friends produce the type itself, when its generic arguments
+            // are written out (`Vec::<u8>::new()`) or it has none
+            Expr::Path(path) if path.path.segments.len() >= 2 => {
+                let segments = &path.path.segments;
+                let ty = &segments[segments.len() - 2];
+                let constructor = segments[segments.len() - 1].ident.to_string();
+                let is_type = ty.ident.to_string().starts_with(char::is_uppercase);
+                let is_constructor =
+                    ["new", "default", "from", "with_capacity"].contains(&constructor.as_str());
+                let complete = !ty.arguments.is_none() || takes_no_generics(source, &ty.ident);
+                (is_type && is_constructor && complete).then(|| {
+                    // Written as a type, so without the turbofish's `::`
+                    segments
+                        .iter()
+                        .take(segments.len() - 1)
+                        .map(|segment| match &segment.arguments {
+                            syn::PathArguments::AngleBracketed(args) => {
+                                let start = source.byte_range(args.lt_token.span()).start;
+                                let end = source.byte_range(args.gt_token.span()).end;
+                                format!("{}{}", segment.ident, &source.text[start..end])
+                            }
+                            _ => segment.ident.to_string(),
+                        })
+                        .collect::<Vec<_>>()
+                        .join("::")
+                })
+            }
+            _ => None,
+        },
+        Expr::Struct(lit) => {
+            let last = lit.path.segments.last()?;
+            (!last.arguments.is_none() || takes_no_generics(source, &last.ident))
+                .then(|| source.snippet(lit.path.span()).to_string())
+        }
+        Expr::Reference(reference) => infer_type(source, &reference.expr).map(|ty| {
+            format!(
+                "&{}{}",
+                if reference.mutability.is_some() {
+                    "mut "
+                } else {
+                    ""
+                },
+                ty
+            )
+        }),
+        Expr::Paren(paren) => infer_type(source, &paren.expr),
+        _ => None,
+    }
+}
+
+/// Whether the type named `ident` has no generic parameters: a plain std type, or a struct,
+/// enum, union or type alias the file declares without any
+fn takes_no_generics(source: &SourceFile, ident: &syn::Ident) -> bool {
+    const PLAIN_TYPES: &[&str] = &[
+        "String",
+        "PathBuf",
+        "OsString",
+        "CString",
+        "Duration",
+        "Instant",
+        "SystemTime",
+    ];
+    fn declared<'a>(items: &'a [Item], ident: &syn::Ident) -> Option<&'a syn::Generics> {
+        items.iter().find_map(|item| match item {
+            Item::Struct(item) if item.ident == *ident => Some(&item.generics),
+            Item::Enum(item) if item.ident == *ident => Some(&item.generics),
+            Item::Union(item) if item.ident == *ident => Some(&item.generics),
+            Item::Type(item) if item.ident == *ident => Some(&item.generics),
+            Item::Mod(item) => declared(&item.content.as_ref()?.1, ident),
+            _ => None,
+        })
+    }
+
+    PLAIN_TYPES.contains(&ident.to_string().as_str())
+        || declared(&source.ast.items, ident).is_some_and(|generics| generics.params.is_empty())
+}
+
+fn is_copy(ty: &str) -> bool {
+    const COPY_TYPES: &[&str] = &[
+        "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+        "f32", "f64", "bool", "char", "()",
+    ];
+    COPY_TYPES.contains(&ty) || (ty.starts_with('&') && !ty.starts_with("&mut "))
+}
+
+/// Whether `text` mentions the identifier or lifetime `name` as a whole token
+fn mentions(text: &str, name: &str) -> bool {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices(name).any(|(i, _)| {
+        let before = text[..i].chars().next_back();
+        let after = text[i + name.len()..].chars().next();
+        !before.is_some_and(|c| is_ident(c) || (c == '\'' && !name.starts_with('\'')))
+            && !after.is_some_and(is_ident)
+    })
+}
+
+fn strip_indent(line: &str, width: usize) -> &str {
+    let strip = line
+        .char_indices()
+        .take(width)
+        .take_while(|(_, c)| *c == ' ' || *c == '\t')
+        .count();
+    &line[strip..]
+}
+
+/// Paths from the file's top-level `use` declarations that the extracted function refers to
+fn required_imports(source: &SourceFile, function: &str) -> Vec<String> {
+    let mut idents = HashSet::new();
+    if let Ok(tokens) = function.parse::<proc_macro2::TokenStream>() {
+        collect_idents(tokens, &mut idents);
+    }
+
+    let mut imports = Vec::new();
+    for item in &source.ast.items {
+        if let Item::Use(item_use) = item {
+            for leaf in flatten_use(&item_use.tree) {
+                let Some(name) = leaf.visible_name() else { continue };
+                let path = leaf.to_string();
+                if idents.contains(name) && !imports.contains(&path) {
+                    imports.push(path);
+                }
+            }
+        }
+    }
+    imports
+}
+
+fn collect_idents(tokens: proc_macro2::TokenStream, idents: &mut HashSet<String>) {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) => {
+                idents.insert(ident.to_string());
+            }
+            TokenTree::Group(group) => collect_idents(group.stream(), idents),
+            _ => {}
+        }
+    }
+}
+
+/// Visitor collecting every function body in a file
+struct HostFinder<'ast> {
+    hosts: Vec<HostFn<'ast>>,
+}
+
+impl<'ast> Visit<'ast> for HostFinder<'ast> {
+    fn visit_item_fn(&mut self, node: &'ast ItemFn) {
+        self.hosts.push(HostFn {
+            sig: &node.sig,
+            block: &node.block,
+            item_span: node.span(),
+            is_associated: false,
+        });
+        visit::visit_item_fn(self, node);
+    }
+
+    fn visit_impl_item_fn(&mut self, node: &'ast ImplItemFn) {
+        self.hosts.push(HostFn {
+            sig: &node.sig,
+            block: &node.block,
+            item_span: node.span(),
+            is_associated: true,
+        });
+        visit::visit_impl_item_fn(self, node);
+    }
+
+    fn visit_trait_item_fn(&mut self, node: &'ast TraitItemFn) {
+        if let Some(block) = &node.default {
+            self.hosts.push(HostFn {
+                sig: &node.sig,
+                block,
+                item_span: node.span(),
+                is_associated: true,
+            });
+        }
+        visit::visit_trait_item_fn(self, node);
+    }
+}
+
+/// Visitor collecting every block below a function body
+struct BlockFinder<'ast> {
+    blocks: Vec<&'ast Block>,
+}
+
+impl<'ast> Visit<'ast> for BlockFinder<'ast> {
+    fn visit_block(&mut self, node: &'ast Block) {
+        self.blocks.push(node);
+        visit::visit_block(self, node);
+    }
+
+    fn visit_item(&mut self, _node: &'ast Item) {
+        // Nested items have bodies of their own
+    }
+}
+
+/// Visitor collecting the pattern bindings that end before a byte offset and are still in
+/// scope there, leaving out those of blocks, arms and `if let`s that closed before it
+struct BindingCollector<'a> {
+    source: &'a SourceFile,
+    before: usize,
+    bindings: Vec<(String, Binding)>,
+}
+
+impl<'ast> Visit<'ast> for BindingCollector<'_> {
+    fn visit_local(&mut self, node: &'ast Local) {
+        if self.source.byte_range(node.span()).end <= self.before {
+            self.bindings.extend(local_bindings(self.source, node));
+        }
+        if let Some(init) = &node.init {
+            self.visit_local_init(init);
+        }
+    }
+
+    fn visit_pat_ident(&mut self, node: &'ast syn::PatIdent) {
+        if self.source.byte_range(node.span()).end <= self.before {
+            self.bindings.push((
+                node.ident.to_string(),
+                Binding {
+                    ty: None,
+                    mutable: node.mutability.is_some(),
+                },
+            ));
+        }
+        visit::visit_pat_ident(self, node);
+    }
+
+    fn visit_expr(&mut self, node: &'ast Expr) {
+        if self.encloses_offset(node.span()) {
+            visit::visit_expr(self, node);
+        }
+    }
+
+    fn visit_arm(&mut self, node: &'ast syn::Arm) {
+        if self.encloses_offset(node.span()) {
+            visit::visit_arm(self, node);
+        }
+    }
+
+    fn visit_expr_if(&mut self, node: &'ast syn::ExprIf) {
+        // `if let` bindings are only in scope in the `then` branch
+        if self.encloses_offset(node.then_branch.span()) {
+            self.visit_expr(&node.cond);
+        }
+        self.visit_block(&node.then_branch);
+        if let Some((_, else_branch)) = &node.else_branch {
+            self.visit_expr(else_branch);
+        }
+    }
+
+    fn visit_item(&mut self, _node: &'ast Item) {}
+}
+
+impl BindingCollector<'_> {
+    /// Whether a scope spans the offset bindings are collected for
+    fn encloses_offset(&self, span: Span) -> bool {
+        let range = self.source.byte_range(span);
+        range.start <= self.before && self.before < range.end
+    }
+}
+
+/// Visitor collecting binding names from an arbitrary pattern
+#[derive(Default)]
+struct PatternNames {
+    names: Vec<(String, bool)>,
+}
+
+impl<'ast> Visit<'ast> for PatternNames {
+    fn visit_pat_ident(&mut self, node: &'ast syn::PatIdent) {
+        self.names
+            .push((node.ident.to_string(), node.mutability.is_some()));
+        visit::visit_pat_ident(self, node);
+    }
+}
+
+/// How a piece of code uses local variables
+#[derive(Default)]
+struct Usage {
+    /// Single-identifier paths in order of appearance
+    uses: Vec<(String, Span)>,
+    /// Names bound by patterns
+    declared: Vec<(String, Span)>,
+    /// Roots of assignment targets, e.g. `x` for `x.field += 1`
+    assigned: HashSet<String>,
+    /// Assignment targets that are a bare variable
+    direct_assigns: Vec<(String, Span)>,
+    /// Roots of `&mut` borrows
+    mut_borrowed: HashSet<String>,
+    /// Variables borrowed as a whole with `&mut x` (span of `x`)
+    direct_mut_borrows: Vec<(String, Span)>,
+    /// Variables borrowed as a whole with `&x` (span of `&x`)
+    direct_borrows: Vec<(String, Span)>,
+    /// Roots of method call receivers
+    receivers: HashSet<String>,
+    has_try: bool,
+    has_await: bool,
+    has_return: bool,
+    has_escape: bool,
+    closure_depth: usize,
+    loop_depth: usize,
+    /// Labels of the enclosing loops and labeled blocks
+    labels: Vec<String>,
+}
+
+impl Usage {
+    fn record_assignment(&mut self, target: &Expr) {
+        if let Some(name) = bare_variable(target) {
+            self.direct_assigns.push((name, target.span()));
+        }
+        if let Some(root) = root_variable(target) {
+            self.assigned.insert(root);
+        }
+    }
+
+    fn in_nested_body(&mut self, visit: impl FnOnce(&mut Self)) {
+        self.closure_depth += 1;
+        visit(self);
+        self.closure_depth -= 1;
+    }
+
+    fn in_loop(&mut self, label: Option<&syn::Label>, visit: impl FnOnce(&mut Self)) {
+        self.loop_depth += 1;
+        self.in_label(label, visit);
+        self.loop_depth -= 1;
+    }
+
+    fn in_label(&mut self, label: Option<&syn::Label>, visit: impl FnOnce(&mut Self)) {
+        let labels = self.labels.len();
+        if let Some(label) = label {
+            self.labels.push(label.name.ident.to_string());
+        }
+        visit(self);
+        self.labels.truncate(labels);
+    }
+
+    /// Whether a `break` or `continue` jumps out of the code being visited
+    fn escapes(&self, label: Option<&syn::Lifetime>) -> bool {
+        match label {
+            Some(label) => !self.labels.contains(&label.ident.to_string()),
+            None => self.loop_depth == 0,
+        }
+    }
+
+    /// Record inline format arguments such as `{name}` or `{name:?}`
+    fn visit_format_string(&mut self, lit: &syn::LitStr) {
+        let value = lit.value();
+        let mut chars = value.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '{' {
+                continue;
+            }
+            if chars.peek() == Some(&'{') {
+                chars.next();
+                continue;
+            }
+            let name: String =
+                std::iter::from_fn(|| chars.next_if(|c| c.is_alphanumeric() || *c == '_'))
+                    .collect();
+            if !name.is_empty()
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+                && matches!(chars.peek(), Some('}') | Some(':'))
+            {
+                self.uses.push((name, lit.span()));
+            }
+        }
+    }
+}
+
+fn bare_variable(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Path(path)
+            if path.qself.is_none()
+                && path.path.leading_colon.is_none()
+                && path.path.segments.len() == 1
+                && path.path.segments[0].arguments.is_none() =>
+        {
+            Some(path.path.segments[0].ident.to_string())
+        }
+        _ => None,
+    }
+}
+
+fn root_variable(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Field(field) => root_variable(&field.base),
+        Expr::Index(index) => root_variable(&index.expr),
+        Expr::Paren(paren) => root_variable(&paren.expr),
+        _ => bare_variable(expr),
+    }
+}
+
+impl<'ast> Visit<'ast> for Usage {
+    fn visit_expr_path(&mut self, node: &'ast syn::ExprPath) {
+        if let Some(name) = bare_variable(&Expr::Path(node.clone())) {
+            self.uses.push((name, node.span()));
+        }
+        visit::visit_expr_path(self, node);
+    }
+
+    fn visit_pat_ident(&mut self, node: &'ast syn::PatIdent) {
+        self.declared.push((node.ident.to_string(), node.span()));
+        visit::visit_pat_ident(self, node);
+    }
+
+    fn visit_expr_assign(&mut self, node: &'ast syn::ExprAssign) {
+        self.record_assignment(&node.left);
+        visit::visit_expr_assign(self, node);
+    }
+
+    fn visit_expr_binary(&mut self, node: &'ast syn::ExprBinary) {
+        if matches!(
+            node.op,
+            BinOp::AddAssign(_)
+                | BinOp::SubAssign(_)
+                | BinOp::MulAssign(_)
+                | BinOp::DivAssign(_)
+                | BinOp::RemAssign(_)
+                | BinOp::BitXorAssign(_)
+                | BinOp::BitAndAssign(_)
+                | BinOp::BitOrAssign(_)
+                | BinOp::ShlAssign(_)
+                | BinOp::ShrAssign(_)
+        ) {
+            self.record_assignment(&node.left);
+        }
+        visit::visit_expr_binary(self, node);
+    }
+
+    fn visit_expr_reference(&mut self, node: &'ast syn::ExprReference) {
+        if node.mutability.is_some() {
+            if let Some(root) = root_variable(&node.expr) {
+                self.mut_borrowed.insert(root);
+            }
+            if let Some(name) = bare_variable(&node.expr) {
+                self.direct_mut_borrows.push((name, node.expr.span()));
+            }
+        } else if let Some(name) = bare_variable(&node.expr) {
+            self.direct_borrows.push((name, node.span()));
+        }
+        visit::visit_expr_reference(self, node);
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast syn::ExprMethodCall) {
+        if let Some(root) = root_variable(&node.receiver) {
+            self.receivers.insert(root);
+        }
+        visit::visit_expr_method_call(self, node);
+    }
+
+    fn visit_expr_return(&mut self, node: &'ast syn::ExprReturn) {
+        if self.closure_depth == 0 {
+            self.has_return = true;
+        }
+        visit::visit_expr_return(self, node);
+    }
+
+    fn visit_expr_try(&mut self, node: &'ast syn::ExprTry) {
+        if self.closure_depth == 0 {
+            self.has_try = true;
+        }
+        visit::visit_expr_try(self, node);
+    }
+
+    fn visit_expr_await(&mut self, node: &'ast syn::ExprAwait) {
+        if self.closure_depth == 0 {
+            self.has_await = true;
+        }
+        visit::visit_expr_await(self, node);
+    }
+
+    fn visit_expr_break(&mut self, node: &'ast syn::ExprBreak) {
+        if self.closure_depth == 0 && self.escapes(node.label.as_ref()) {
+            self.has_escape = true;
+        }
+        visit::visit_expr_break(self, node);
+    }
+
+    fn visit_expr_continue(&mut self, node: &'ast syn::ExprContinue) {
+        if self.closure_depth == 0 && self.escapes(node.label.as_ref()) {
+            self.has_escape = true;
+        }
+        visit::visit_expr_continue(self, node);
+    }
+
+    fn visit_expr_closure(&mut self, node: &'ast syn::ExprClosure) {
+        self.in_nested_body(|this| visit::visit_expr_closure(this, node));
+    }
+
+    fn visit_expr_async(&mut self, node: &'ast syn::ExprAsync) {
+        self.in_nested_body(|this| visit::visit_expr_async(this, node));
+    }
+
+    fn visit_expr_loop(&mut self, node: &'ast syn::ExprLoop) {
+        self.in_loop(node.label.as_ref(), |this| visit::visit_expr_loop(this, node));
+    }
+
+    fn visit_expr_while(&mut self, node: &'ast syn::ExprWhile) {
+        self.in_loop(node.label.as_ref(), |this| visit::visit_expr_while(this, node));
+    }
+
+    fn visit_expr_for_loop(&mut self, node: &'ast syn::ExprForLoop) {
+        self.in_loop(node.label.as_ref(), |this| visit::visit_expr_for_loop(this, node));
+    }
+
+    fn visit_expr_block(&mut self, node: &'ast syn::ExprBlock) {
+        self.in_label(node.label.as_ref(), |this| visit::visit_expr_block(this, node));
+    }
+
+    fn visit_item(&mut self, _node: &'ast Item) {
+        // Nested items cannot capture locals
+    }
+
+    fn visit_macro(&mut self, node: &'ast syn::Macro) {
+        // Most expression-like macros (`println!`, `vec!`, `assert!`) take comma-separated expressions
+        match node.parse_body_with(Punctuated::<Expr, syn::Token![,]>::parse_terminated) {
+            Ok(args) => {
+                for arg in &args {
+                    match arg {
+                        // Named format arguments: `name = value`
+                        Expr::Assign(assign) if bare_variable(&assign.left).is_some() => {
+                            self.visit_expr(&assign.right)
+                        }
+                        Expr::Lit(syn::ExprLit {
+                            lit: Lit::Str(lit), ..
+                        }) => self.visit_format_string(lit),
+                        _ => self.visit_expr(arg),
+                    }
+                }
+            }
+            Err(_) => {
+                // Unknown macro syntax: treat every identifier as a potential use
+                let mut idents = Vec::new();
+                collect_ident_spans(node.tokens.clone(), &mut idents);
+                self.uses.extend(idents);
+            }
+        }
+    }
+}
+
+fn collect_ident_spans(tokens: proc_macro2::TokenStream, out: &mut Vec<(String, Span)>) {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) => out.push((ident.to_string(), ident.span())),
+            TokenTree::Group(group) => collect_ident_spans(group.stream(), out),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(line_start: u32, line_end: u32) -> SpanInfo {
+        SpanInfo {
+            line_start,
+            line_end: line_end + 1,
+            column_start: 1,
+            column_end: 1,
+        }
+    }
+
+    #[test]
+    fn test_extract_infers_params_and_return_value() -> Result<()> {
+        let source = SourceFile::parse(
+            r#"use std::collections::HashMap;
+
+fn report(names: Vec<String>, limit: usize) -> usize {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut total: usize = 0;
+    for name in &names {
+        *counts.entry(name.clone()).or_insert(0) += 1;
+        total += 1;
+    }
+    let summary = format!("{} of {limit}", total);
+    println!("{}", summary);
+    counts.len() + names.len()
+}
+"#,
+        )?;
+
+        let result = extract_function(&source, &range(6, 10), "tally")?;
+
+        assert_eq!(
+            result.extracted_code,
+            r#"fn tally(names: &Vec<String>, counts: &mut HashMap<String, usize>, total: &mut usize, limit: usize) -> String {
+    for name in names {
+        *counts.entry(name.clone()).or_insert(0) += 1;
+        *total += 1;
+    }
+    let summary = format!("{} of {limit}", total);
+    summary
+}"#
+        );
+        assert!(result.modified_file.contains(
+            "    let summary = tally(&names, &mut counts, &mut total, limit);\n    println!"
+        ));
+        assert!(result.modified_file.ends_with("    summary\n}\n"));
+
+        // Without a later use, `names` is moved instead of borrowed
+        let source = SourceFile::parse(source.text.replace(" + names.len()", ""))?;
+        let result = extract_function(&source, &range(6, 10), "tally")?;
+        assert!(result
+            .extracted_code
+            .starts_with("fn tally(names: Vec<String>, counts: &mut HashMap<String, usize>, total: &mut usize, limit: usize) -> String {\n    for name in &names {"));
+        assert_eq!(result.required_imports, vec!["std::collections::HashMap"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_method_and_try_operator() -> Result<()> {
+        let source = SourceFile::parse(
+            r#"impl Loader {
+    fn load(&mut self, path: &Path) -> io::Result<usize> {
+        let text = fs::read_to_string(path)?;
+        self.lines += text.lines().count();
+        Ok(self.lines)
+    }
+}
+"#,
+        )?;
+
+        let result = extract_function(&source, &range(3, 4), "read_lines")?;
+
+        assert_eq!(
+            result.extracted_code,
+            r#"fn read_lines(&mut self, path: &Path) -> io::Result<()> {
+    let text = fs::read_to_string(path)?;
+    self.lines += text.lines().count();
+    Ok(())
+}"#
+        );
+        assert!(result
+            .modified_file
+            .contains("        self.read_lines(path)?;\n        Ok(self.lines)"));
+        assert!(result
+            .modified_file
+            .contains("\n\n    fn read_lines(&mut self, path: &Path)"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_moves_unused_values_and_keeps_tail_expression() -> Result<()> {
+        let source = SourceFile::parse(
+            r#"fn build(prefix: String) -> String {
+    let suffix = String::from("!");
+    consume(suffix);
+    prefix + "?"
+}
+"#,
+        )?;
+
+        let moved = extract_function(&source, &range(3, 3), "finish")?;
+        assert_eq!(
+            moved.extracted_code,
+            "fn finish(suffix: String) {\n    consume(suffix);\n}"
+        );
+
+        let tail = extract_function(&source, &range(4, 4), "decorate")?;
+        assert_eq!(
+            tail.extracted_code,
+            "fn decorate(prefix: String) -> String {\n    prefix + \"?\"\n}"
+        );
+        assert!(tail.modified_file.contains("    decorate(prefix)\n}"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_rejects_partial_statements_and_return() -> Result<()> {
+        let source = SourceFile::parse(
+            r#"fn check(x: i32) -> i32 {
+    if x > 0 {
+        return x;
+    }
+    0
+}
+"#,
+        )?;
+
+        let partial = SpanInfo {
+            line_start: 2,
+            line_end: 2,
+            column_start: 5,
+            column_end: 10,
+        };
+        assert!(extract_function(&source, &partial, "f").is_err());
+        assert!(extract_function(&source, &range(2, 4), "f").is_err());
+        assert!(extract_function(&source, &range(5, 5), "not valid").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_rejects_unknown_types_and_outer_labels() -> Result<()> {
+        let source = SourceFile::parse(
+            r#"fn pick(items: Vec<u8>, flag: bool) -> u8 {
+    let first = items.first();
+    consume(first);
+    let value = if flag {
+        compute()
+    } else {
+        0
+    };
+    value
+}
+"#,
+        )?;
+
+        let untyped = extract_function(&source, &range(3, 3), "f").unwrap_err();
+        assert!(untyped.to_string().contains("`first`"));
+        let tail = extract_function(&source, &range(5, 5), "f").unwrap_err();
+        assert!(tail.to_string().contains("`compute()`"));
+
+        // Unsuffixed numbers and constructors of generic types leave the type open
+        let source = SourceFile::parse(
+            r#"fn sizes(names: &[String]) -> usize {
+    let mut total = 0;
+    let mut seen = Vec::new();
+    let typed = Vec::<u8>::new();
+    for name in names {
+        total += name.len();
+        seen.push(name.len());
+    }
+    consume(typed);
+    total + seen.len()
+}
+"#,
+        )?;
+
+        let untyped = extract_function(&source, &range(6, 6), "f").unwrap_err();
+        assert!(untyped.to_string().contains("`total`"));
+        let generic = extract_function(&source, &range(7, 7), "f").unwrap_err();
+        assert!(generic.to_string().contains("`seen`"));
+        let typed = extract_function(&source, &range(9, 9), "f")?;
+        assert_eq!(typed.extracted_code, "fn f(typed: Vec<u8>) {\n    consume(typed);\n}");
+
+        let source = SourceFile::parse(
+            r#"fn scan(rows: Vec<Vec<u8>>) {
+    'outer: for row in rows {
+        for cell in row {
+            if cell == 0 {
+                break 'outer;
+            }
+        }
+    }
+}
+"#,
+        )?;
+
+        assert!(extract_function(&source, &range(3, 7), "f").is_err());
+        let whole = extract_function(&source, &range(2, 8), "f")?;
+        assert!(whole.extracted_code.starts_with("fn f(rows: Vec<Vec<u8>>) {\n    'outer: for row in rows {"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_ignores_bindings_of_closed_scopes() -> Result<()> {
+        let source = SourceFile::parse(
+            r#"fn measure(x: String, fallback: Option<u8>) -> usize {
+    {
+        let x = 5u8;
+        consume(x);
+    }
+    if let Some(x) = fallback {
+        consume(x);
+    }
+    x.len()
+}
+"#,
+        )?;
+
+        let result = extract_function(&source, &range(9, 9), "length")?;
+        assert_eq!(
+            result.extracted_code,
+            "fn length(x: String) -> usize {\n    x.len()\n}"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,804 @@
+//! Import editing for Rusty Refactor
+//!
+//! This module adds `use` declarations to a file the way rustfmt would lay them out: merged
+//! into an existing declaration when `imports_granularity` allows it, otherwise as a new line
+//! in its std / external / crate group, without duplicating or shadowing existing imports.
+
+use anyhow::{anyhow, bail, Result};
+use std::cmp::Ordering;
+use std::ops::Range;
+use std::path::Path;
+use syn::spanned::Spanned;
+use syn::{Item, ItemUse, UseTree, Visibility};
+use toml::{Table, Value};
+
+use crate::module_ops::{apply_edits, header_insertion, line_range, top_level_names};
+use crate::preview::text_edits;
+use crate::syntax::{
+    flatten_use, flatten_use_spanned, span_encloses, span_info, SourceFile, UseLeaf,
+};
+use crate::{SpanInfo, TextEdit};
+
+/// A file's rewritten import block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportBlock {
+    /// Byte range of the original block
+    pub range: Range<usize>,
+    pub text: String,
+    /// Imports dropped as unused, e.g. `std::fmt::Write`
+    pub removed: Vec<String>,
+}
+
+/// How rustfmt merges or splits imports (`imports_granularity`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Granularity {
+    /// Leave declarations as written; new imports join a declaration of their module
+    #[default]
+    Preserve,
+    /// One declaration per crate
+    Crate,
+    /// One declaration per module
+    Module,
+    /// One declaration per imported item
+    Item,
+    /// A single declaration for everything
+    One,
+}
+
+/// How rustfmt groups imports (`group_imports`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GroupImports {
+    /// Keep the file's groups; new imports join the group of their kind
+    #[default]
+    Preserve,
+    /// std, core and alloc, then external crates, then `crate`, `self` and `super`
+    StdExternalCrate,
+    /// A single group
+    One,
+}
+
+/// rustfmt settings that affect imports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportStyle {
+    pub granularity: Granularity,
+    pub group: GroupImports,
+    pub max_width: usize,
+}
+
+impl Default for ImportStyle {
+    fn default() -> Self {
+        Self {
+            granularity: Granularity::default(),
+            group: GroupImports::default(),
+            max_width: 100,
+        }
+    }
+}
+
+impl ImportStyle {
+    /// Settings from the nearest `rustfmt.toml` or `.rustfmt.toml` in `dir` or above it
+    pub fn discover(dir: &Path) -> Result<Self> {
+        for ancestor in dir.ancestors() {
+            for name in ["rustfmt.toml", ".rustfmt.toml"] {
+                let path = ancestor.join(name);
+                if path.is_file() {
+                    return Self::parse(&std::fs::read_to_string(&path)?)
+                        .map_err(|e| anyhow!("{}: {}", path.display(), e));
+                }
+            }
+        }
+        Ok(Self::default())
+    }
+
+    /// Read the import settings from the text of a rustfmt config file
+    pub fn parse(text: &str) -> Result<Self> {
+        let table: Table = text.parse()?;
+        let setting = |key: &str| table.get(key).and_then(Value::as_str);
+
+        let granularity = match setting("imports_granularity") {
+            None | Some("Preserve") => Granularity::Preserve,
+            Some("Crate") => Granularity::Crate,
+            Some("Module") => Granularity::Module,
+            Some("Item") => Granularity::Item,
+            Some("One") => Granularity::One,
+            Some(other) => bail!("Unknown imports_granularity `{}`", other),
+        };
+        let group = match setting("group_imports") {
+            None | Some("Preserve") => GroupImports::Preserve,
+            Some("StdExternalCrate") => GroupImports::StdExternalCrate,
+            Some("One") => GroupImports::One,
+            Some(other) => bail!("Unknown group_imports `{}`", other),
+        };
+        let max_width = table
+            .get("max_width")
+            .and_then(Value::as_integer)
+            .map_or(100, |width| width.max(1) as usize);
+
+        Ok(Self {
+            granularity,
+            group,
+            max_width,
+        })
+    }
+}
+
+/// Read an import as given by a suggestion: a path or use tree, e.g. `std::io::{Read, Write}`
+///
+/// `alias` applies when the path names a single item; `is_glob` imports everything below it.
+pub fn import_leaves(path: &str, alias: Option<&str>, is_glob: bool) -> Result<Vec<UseLeaf>> {
+    let path = path.trim().trim_start_matches("use ").trim_end_matches(';');
+    let tree: UseTree =
+        syn::parse_str(path).map_err(|e| anyhow!("`{}` is not an import path: {}", path, e))?;
+    let mut leaves = flatten_use(&tree);
+    if let [leaf] = leaves.as_mut_slice() {
+        if is_glob {
+            leaf.is_glob = true;
+        } else if let Some(alias) = alias {
+            leaf.alias = Some(alias.to_string());
+        }
+    }
+    Ok(leaves)
+}
+
+/// The file's text with `import` added, unchanged when the file already imports it
+///
+/// Fails when the name the import brings in is already taken by another import or by an
+/// item of the file; importing under an alias (or `as _` for traits) avoids that.
+pub fn add_import(text: &str, import: &UseLeaf, style: &ImportStyle) -> Result<String> {
+    let file = SourceFile::parse(text)?;
+    let uses: Vec<&ItemUse> = file
+        .ast
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Use(item_use) => Some(item_use),
+            _ => None,
+        })
+        .collect();
+
+    // Already in scope, directly or through a glob of its module
+    let existing: Vec<UseLeaf> = uses
+        .iter()
+        .flat_map(|item_use| flatten_use(&item_use.tree))
+        .collect();
+    let parent = &import.path[..import.path.len().saturating_sub(1)];
+    if existing.iter().any(|leaf| {
+        leaf == import
+            || (leaf.is_glob && !import.is_glob && leaf.path == parent && import.alias.is_none())
+    }) {
+        return Ok(text.to_string());
+    }
+
+    if let Some(name) = import.visible_name() {
+        if let Some(other) = existing
+            .iter()
+            .find(|leaf| leaf.visible_name() == Some(name))
+        {
+            bail!(
+                "`{}` is already imported from `{}`; import `{}` under an alias instead",
+                name,
+                other.path.join("::"),
+                import
+            );
+        }
+        if top_level_names(&file).contains(name) {
+            bail!(
+                "The file already defines `{}`; import `{}` under an alias instead",
+                name,
+                import
+            );
+        }
+    }
+
+    let edit = match merge_target(&uses, import, style.granularity) {
+        Some(item_use) => {
+            let mut leaves = flatten_use(&item_use.tree);
+            leaves.push(import.clone());
+            let indent = file.line_indent(file.byte_range(item_use.span()).start);
+            (
+                file.byte_range(item_use.span()),
+                render_use(&leaves, indent, style.max_width),
+            )
+        }
+        None => insertion(&file, &uses, import, style),
+    };
+    Ok(apply_edits(text, vec![edit]))
+}
+
+/// Line-based edits adding imports to a file, styled by the nearest rustfmt config
+pub fn import_edits(file: &Path, imports: &[UseLeaf]) -> Result<Vec<TextEdit>> {
+    let before = std::fs::read_to_string(file)
+        .map_err(|e| anyhow!("Failed to read {}: {}", file.display(), e))?;
+    let style = ImportStyle::discover(file.parent().unwrap_or(Path::new(".")))?;
+    let mut after = before.clone();
+    for import in imports {
+        after = add_import(&after, import, &style)?;
+    }
+    Ok(text_edits(&file.to_string_lossy(), &before, &after))
+}
+
+/// Rewrite the run of `use` declarations that starts at a file's first import
+///
+/// Imports inside an `unused` span (rustc's `unused_imports` warnings) are dropped, duplicates
+/// and imports covered by the only glob are collapsed, and the rest is merged per
+/// `imports_granularity`, sorted and grouped std / external / crate. Declarations with
+/// attributes such as `#[cfg]`, `pub use` re-exports and declarations with comments are kept
+/// as written, apart from unused imports in commented ones. Returns `None` when the file has
+/// no imports.
+pub fn organize_imports(
+    text: &str,
+    unused: &[SpanInfo],
+    style: &ImportStyle,
+) -> Result<Option<ImportBlock>> {
+    let file = SourceFile::parse(text)?;
+    let Some(first) = file
+        .ast
+        .items
+        .iter()
+        .position(|item| matches!(item, Item::Use(_)))
+    else {
+        return Ok(None);
+    };
+    let run: Vec<&ItemUse> = file.ast.items[first..]
+        .iter()
+        .map_while(|item| match item {
+            Item::Use(item_use) => Some(item_use),
+            _ => None,
+        })
+        .collect();
+    let is_unused = |leaf: &(UseLeaf, proc_macro2::Span)| {
+        let span = span_info(leaf.1);
+        unused.iter().any(|outer| span_encloses(outer, &span))
+    };
+
+    let start = line_range(&file, run[0].span()).start;
+    let mut end = start;
+    let mut removed = Vec::new();
+    let mut plain: Vec<Vec<UseLeaf>> = Vec::new();
+    // Declarations kept as written, with their group
+    let mut kept: Vec<(u8, String)> = Vec::new();
+
+    for item_use in &run {
+        let mut range = line_range(&file, item_use.span());
+        // A comment between declarations belongs to the one below it
+        let gap = &text[end..range.start];
+        let leading_blank: usize = gap
+            .split_inclusive('\n')
+            .take_while(|line| line.trim().is_empty())
+            .map(str::len)
+            .sum();
+        let detached = leading_blank < gap.len();
+        range.start = end + leading_blank;
+        end = range.end;
+
+        let item_range = file.byte_range(item_use.span());
+        let snippet = &text[item_range.clone()];
+        let commented = detached
+            || !text[range.start..item_range.start].trim().is_empty()
+            || !text[item_range.end..range.end].trim().is_empty()
+            || snippet.contains("//")
+            || snippet.contains("/*");
+        let mut unit = text[range.clone()].to_string();
+        if !unit.ends_with('\n') {
+            unit.push('\n');
+        }
+
+        let leaves = flatten_use_spanned(&item_use.tree);
+        let group = leaves.first().map_or(1, |(leaf, _)| group_of(leaf));
+        if !item_use.attrs.is_empty()
+            || item_use.leading_colon.is_some()
+            || !matches!(item_use.vis, Visibility::Inherited)
+        {
+            kept.push((group, unit));
+            continue;
+        }
+
+        let (dead, live): (Vec<_>, Vec<_>) = leaves.into_iter().partition(is_unused);
+        removed.extend(dead.iter().map(|(leaf, _)| leaf.to_string()));
+        let live: Vec<UseLeaf> = live.into_iter().map(|(leaf, _)| leaf).collect();
+        if live.is_empty() {
+            continue;
+        }
+        if !commented {
+            plain.push(live);
+        } else if dead.is_empty() {
+            kept.push((group, unit));
+        } else {
+            let at = item_range.start - range.start..item_range.end - range.start;
+            unit.replace_range(at, &render_use(&live, "", style.max_width));
+            kept.push((group, unit));
+        }
+    }
+
+    let declarations = merge_declarations(plain, style.granularity);
+    let mut groups: [Vec<String>; 3] = Default::default();
+    let mut sorted: Vec<(u8, Vec<String>, String)> = declarations
+        .iter()
+        .filter_map(|leaves| {
+            let first = leaves
+                .iter()
+                .min_by(|a, b| compare_paths(&a.path, &b.path))?;
+            let group = match style.group {
+                GroupImports::One => 0,
+                _ => group_of(first),
+            };
+            let rendered = format!("{}\n", render_use(leaves, "", style.max_width));
+            Some((group, first.path.clone(), rendered))
+        })
+        .collect();
+    sorted.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| compare_paths(&a.1, &b.1)));
+    for (group, _, rendered) in sorted {
+        groups[group as usize].push(rendered);
+    }
+    for (group, unit) in kept {
+        let group = match style.group {
+            GroupImports::One => 0,
+            _ => group,
+        };
+        groups[group as usize].push(unit);
+    }
+
+    let blocks: Vec<String> = groups
+        .iter()
+        .filter(|group| !group.is_empty())
+        .map(|group| group.concat())
+        .collect();
+    let block = blocks.join("\n");
+    if block.is_empty() {
+        // Nothing left: take the blank lines after the block too
+        end += text[end..].len() - text[end..].trim_start_matches('\n').len();
+    }
+
+    Ok(Some(ImportBlock {
+        range: start..end,
+        text: block,
+        removed,
+    }))
+}
+
+// Helper functions
+
+/// Deduplicate imports and merge them into declarations per `granularity`
+///
+/// Under `Preserve` each declaration keeps the imports it had. An import its module's glob
+/// already brings in is dropped when that is the only glob, since with several globs the
+/// explicit import may be what settles an ambiguity.
+fn merge_declarations(
+    declarations: Vec<Vec<UseLeaf>>,
+    granularity: Granularity,
+) -> Vec<Vec<UseLeaf>> {
+    let globs: Vec<&UseLeaf> = declarations
+        .iter()
+        .flatten()
+        .filter(|leaf| leaf.is_glob)
+        .collect();
+    let redundant = |leaf: &UseLeaf| {
+        matches!(globs.as_slice(), [glob] if !leaf.is_glob
+            && leaf.alias.is_none()
+            && leaf_module(leaf) == glob.path.as_slice())
+    };
+
+    let mut seen: Vec<UseLeaf> = Vec::new();
+    let mut deduped: Vec<Vec<UseLeaf>> = Vec::new();
+    for declaration in &declarations {
+        let mut leaves = Vec::new();
+        for leaf in declaration {
+            if !redundant(leaf) && !seen.contains(leaf) {
+                seen.push(leaf.clone());
+                leaves.push(leaf.clone());
+            }
+        }
+        if !leaves.is_empty() {
+            deduped.push(leaves);
+        }
+    }
+
+    let key: fn(&UseLeaf) -> Vec<String> = match granularity {
+        Granularity::Preserve => return deduped,
+        Granularity::Item => {
+            return deduped
+                .into_iter()
+                .flatten()
+                .map(|leaf| vec![leaf])
+                .collect();
+        }
+        Granularity::Module => |leaf| leaf_module(leaf).to_vec(),
+        Granularity::Crate => |leaf| leaf.path.iter().take(1).cloned().collect(),
+        Granularity::One => |_| Vec::new(),
+    };
+    let mut merged: Vec<(Vec<String>, Vec<UseLeaf>)> = Vec::new();
+    for leaf in deduped.into_iter().flatten() {
+        let key = key(&leaf);
+        match merged.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, leaves)) => leaves.push(leaf),
+            None => merged.push((key, vec![leaf])),
+        }
+    }
+    merged.into_iter().map(|(_, leaves)| leaves).collect()
+}
+
+/// Existing declaration the import can join under `granularity`, preferring the one
+/// sharing the longest path with it
+///
+/// Only plain private declarations qualify: `pub use` and `#[cfg]`-gated ones are left alone.
+fn merge_target<'a>(
+    uses: &[&'a ItemUse],
+    import: &UseLeaf,
+    granularity: Granularity,
+) -> Option<&'a ItemUse> {
+    let module = leaf_module(import);
+    let shared = |item_use: &ItemUse| {
+        flatten_use(&item_use.tree)
+            .iter()
+            .map(|leaf| {
+                leaf.path
+                    .iter()
+                    .zip(&import.path)
+                    .take_while(|(a, b)| a == b)
+                    .count()
+            })
+            .max()
+            .unwrap_or(0)
+    };
+    let candidates = uses.iter().copied().filter(|item_use| {
+        if !item_use.attrs.is_empty()
+            || item_use.leading_colon.is_some()
+            || !matches!(item_use.vis, Visibility::Inherited)
+        {
+            return false;
+        }
+        let leaves = flatten_use(&item_use.tree);
+        match granularity {
+            Granularity::Item => false,
+            Granularity::One => true,
+            Granularity::Crate => leaves
+                .iter()
+                .all(|leaf| leaf.path.first() == import.path.first()),
+            Granularity::Module | Granularity::Preserve => {
+                !leaves.is_empty() && leaves.iter().all(|leaf| leaf_module(leaf) == module)
+            }
+        }
+    });
+    // The first of equally good declarations
+    candidates.rev().max_by_key(|item_use| shared(item_use))
+}
+
+/// Where a new `use` line goes: sorted into the group of its kind, or a new group placed in
+/// std / external / crate order
+fn insertion(
+    file: &SourceFile,
+    uses: &[&ItemUse],
+    import: &UseLeaf,
+    style: &ImportStyle,
+) -> (Range<usize>, String) {
+    let line = format!(
+        "{}\n",
+        render_use(std::slice::from_ref(import), "", style.max_width)
+    );
+    let group = group_of(import);
+    let same_group = |item_use: &&&ItemUse| {
+        style.group == GroupImports::One
+            || flatten_use(&item_use.tree)
+                .first()
+                .is_some_and(|leaf| group_of(leaf) == group)
+    };
+    let sort_key = |item_use: &ItemUse| flatten_use(&item_use.tree).into_iter().next();
+
+    let members: Vec<&&ItemUse> = uses.iter().filter(same_group).collect();
+    if let Some(last) = members.last() {
+        let after = members.iter().find(|item_use| {
+            sort_key(item_use)
+                .is_some_and(|leaf| compare_paths(&leaf.path, &import.path) == Ordering::Greater)
+        });
+        return match after {
+            Some(item_use) => {
+                let at = line_range(file, item_use.span()).start;
+                (at..at, line)
+            }
+            None => {
+                let at = line_range(file, last.span()).end;
+                (at..at, line)
+            }
+        };
+    }
+
+    let first_group = |item_use: &ItemUse| flatten_use(&item_use.tree).first().map_or(1, group_of);
+    if let Some(before) = uses
+        .iter()
+        .rev()
+        .find(|item_use| first_group(item_use) < group)
+    {
+        let at = line_range(file, before.span()).end;
+        return (at..at, format!("\n{}", line));
+    }
+    if let Some(after) = uses.iter().find(|item_use| first_group(item_use) > group) {
+        let at = line_range(file, after.span()).start;
+        return (at..at, format!("{}\n", line));
+    }
+    header_insertion(file, line)
+}
+
+/// 0 for std, core and alloc, 1 for external crates, 2 for this crate
+fn group_of(leaf: &UseLeaf) -> u8 {
+    match leaf.path.first().map(String::as_str) {
+        Some("std" | "core" | "alloc") => 0,
+        Some("crate" | "self" | "super") => 2,
+        _ => 1,
+    }
+}
+
+/// Module a leaf imports from; a glob imports from the path itself
+fn leaf_module(leaf: &UseLeaf) -> &[String] {
+    if leaf.is_glob {
+        &leaf.path
+    } else {
+        &leaf.path[..leaf.path.len().saturating_sub(1)]
+    }
+}
+
+/// rustfmt's order for names in a use list: `self` first, then lower-case names
+/// (modules, functions), then the rest
+fn compare_segments(a: &str, b: &str) -> Ordering {
+    let rank = |s: &str| match s {
+        "self" => 0,
+        _ if s.starts_with(|c: char| c.is_lowercase() || c == '_') => 1,
+        "*" => 3,
+        _ => 2,
+    };
+    rank(a).cmp(&rank(b)).then_with(|| a.cmp(b))
+}
+
+fn compare_paths(a: &[String], b: &[String]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| compare_segments(a, b))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+/// A use tree under construction: what is imported at a path segment and below it
+#[derive(Default)]
+struct UseNode {
+    children: Vec<(String, UseNode)>,
+    /// The path itself is imported, once per alias (`None` for no alias)
+    imported: Vec<Option<String>>,
+    glob: bool,
+}
+
+impl UseNode {
+    fn insert(&mut self, leaf: &UseLeaf) {
+        let mut node = self;
+        for segment in &leaf.path {
+            let index = match node.children.iter().position(|(name, _)| name == segment) {
+                Some(index) => index,
+                None => {
+                    node.children.push((segment.clone(), UseNode::default()));
+                    node.children.len() - 1
+                }
+            };
+            node = &mut node.children[index].1;
+        }
+        if leaf.is_glob {
+            node.glob = true;
+        } else if !node.imported.contains(&leaf.alias) {
+            node.imported.push(leaf.alias.clone());
+        }
+    }
+
+    /// Entries of this node's use list, sorted
+    fn entries(&self) -> Vec<String> {
+        let mut entries = Vec::new();
+        if self.glob {
+            entries.push("*".to_string());
+        }
+        for (name, child) in &self.children {
+            let mut inner = child.entries();
+            let renamed = |name: &str, alias: &Option<String>| match alias {
+                Some(alias) => format!("{} as {}", name, alias),
+                None => name.to_string(),
+            };
+            if inner.is_empty() {
+                entries.extend(child.imported.iter().map(|alias| renamed(name, alias)));
+                continue;
+            }
+            for alias in &child.imported {
+                inner.insert(0, renamed("self", alias));
+            }
+            entries.push(match inner.as_slice() {
+                [only] => format!("{}::{}", name, only),
+                _ => format!("{}::{{{}}}", name, inner.join(", ")),
+            });
+        }
+        entries.sort_by(|a, b| compare_segments(a, b));
+        entries
+    }
+}
+
+/// Render leaves as one `use` declaration, breaking its outer list over several lines when
+/// the single-line form is wider than `max_width`
+fn render_use(leaves: &[UseLeaf], indent: &str, max_width: usize) -> String {
+    let mut root = UseNode::default();
+    for leaf in leaves {
+        root.insert(leaf);
+    }
+
+    // Segments shared by every leaf, before the outermost list
+    let mut prefix = Vec::new();
+    let mut node = &root;
+    while let [(name, child)] = node.children.as_slice() {
+        if child.glob || !child.imported.is_empty() || child.children.is_empty() {
+            break;
+        }
+        prefix.push(name.as_str());
+        node = child;
+    }
+    let entries = node.entries();
+    let head = if prefix.is_empty() {
+        String::new()
+    } else {
+        format!("{}::", prefix.join("::"))
+    };
+
+    if let [only] = entries.as_slice() {
+        return format!("use {}{};", head, only);
+    }
+    let line = format!("use {}{{{}}};", head, entries.join(", "));
+    if indent.len() + line.len() <= max_width {
+        return line;
+    }
+    let mut text = format!("use {}{{\n", head);
+    for entry in &entries {
+        text.push_str(&format!("{}    {},\n", indent, entry));
+    }
+    text.push_str(&format!("{}}};", indent));
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add(text: &str, path: &str, style: &ImportStyle) -> Result<String> {
+        let leaves = import_leaves(path, None, false)?;
+        add_import(text, &leaves[0], style)
+    }
+
+    #[test]
+    fn test_imports_merge_and_group() -> Result<()> {
+        let style = ImportStyle::default();
+        let text =
+            "//! Docs\n\nuse std::collections::{HashMap, HashSet};\n\nuse serde::Serialize;\n\n\
+                    use crate::models::User;\n\nfn main() {}\n";
+
+        // Joins the declaration of its module
+        let merged = add(text, "std::collections::BTreeMap", &style)?;
+        assert!(merged.contains("use std::collections::{BTreeMap, HashMap, HashSet};\n"));
+
+        // Sorted into its group, or starting a new one in std / external / crate order
+        let external = add(text, "anyhow::Result", &style)?;
+        assert!(external.contains("\nuse anyhow::Result;\nuse serde::Serialize;\n"));
+        let std = add(
+            "use serde::Serialize;\n\nfn main() {}\n",
+            "std::fmt",
+            &style,
+        )?;
+        assert_eq!(
+            std,
+            "use std::fmt;\n\nuse serde::Serialize;\n\nfn main() {}\n"
+        );
+        let local = add(
+            "use std::fmt;\n\nfn main() {}\n",
+            "crate::models::User",
+            &style,
+        )?;
+        assert_eq!(
+            local,
+            "use std::fmt;\n\nuse crate::models::User;\n\nfn main() {}\n"
+        );
+        let first = add("//! Docs\n\nfn main() {}\n", "std::fmt", &style)?;
+        assert_eq!(first, "//! Docs\n\nuse std::fmt;\n\nfn main() {}\n");
+
+        // Duplicates and conflicts
+        assert_eq!(add(text, "std::collections::HashMap", &style)?, text);
+        assert_eq!(
+            add("use std::io::*;\n", "std::io::Write", &style)?,
+            "use std::io::*;\n"
+        );
+        assert!(add(text, "hashbrown::HashMap", &style).is_err());
+        let leaves = import_leaves("hashbrown::HashMap", Some("FastMap"), false)?;
+        assert!(
+            add_import(text, &leaves[0], &style)?.contains("use hashbrown::HashMap as FastMap;\n")
+        );
+        assert!(add("struct Write;\n", "std::io::Write", &style).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_imports_follow_rustfmt_settings() -> Result<()> {
+        let text = "use std::fs;\nuse std::io::{self, Read};\n\nfn main() {}\n";
+
+        let crate_style = ImportStyle::parse("imports_granularity = \"Crate\"\n")?;
+        assert_eq!(
+            add(text, "std::io::Write", &crate_style)?,
+            "use std::fs;\nuse std::io::{self, Read, Write};\n\nfn main() {}\n"
+        );
+        assert_eq!(
+            add("use std::fs;\n", "std::io::Write", &crate_style)?,
+            "use std::{fs, io::Write};\n"
+        );
+
+        let item_style = ImportStyle::parse("imports_granularity = \"Item\"\n")?;
+        assert_eq!(
+            add(text, "std::io::Write", &item_style)?,
+            "use std::fs;\nuse std::io::{self, Read};\nuse std::io::Write;\n\nfn main() {}\n"
+        );
+
+        let one_group = ImportStyle::parse("group_imports = \"One\"\n")?;
+        assert_eq!(
+            add(text, "anyhow::Result", &one_group)?,
+            "use anyhow::Result;\nuse std::fs;\nuse std::io::{self, Read};\n\nfn main() {}\n"
+        );
+
+        let narrow = ImportStyle::parse("max_width = 30\n")?;
+        assert_eq!(
+            add("use std::io::{Read, Write};\n", "std::io::BufRead", &narrow)?,
+            "use std::io::{\n    BufRead,\n    Read,\n    Write,\n};\n"
+        );
+
+        assert!(ImportStyle::parse("imports_granularity = \"Everything\"\n").is_err());
+        Ok(())
+    }
+
+    /// Span of the only occurrence of `needle`, as rustc would report it
+    fn span_of(text: &str, needle: &str) -> SpanInfo {
+        let at = text.find(needle).unwrap();
+        let line_start = text[..at].rfind('\n').map_or(0, |i| i + 1);
+        let line = text[..at].matches('\n').count() as u32 + 1;
+        let column = (at - line_start) as u32 + 1;
+        SpanInfo {
+            line_start: line,
+            line_end: line,
+            column_start: column,
+            column_end: column + needle.len() as u32,
+        }
+    }
+
+    #[test]
+    fn test_organize_imports() -> Result<()> {
+        let text = "//! Docs\n\nuse crate::models::User;\nuse std::io::{self, Read, Write};\n\
+                    use serde::Serialize;\nuse std::collections::HashMap;\n\
+                    // Needed on Windows only\n#[cfg(windows)]\nuse std::os::windows::ffi::OsStrExt;\n\
+                    use std::fmt;\nuse std::collections::HashMap;\npub use crate::models::Role;\n\n\
+                    fn main() {}\n";
+        let unused = [span_of(text, "Read"), span_of(text, "std::fmt")];
+        let block = organize_imports(text, &unused, &ImportStyle::default())?.unwrap();
+        assert_eq!(
+            block.text,
+            "use std::collections::HashMap;\nuse std::io::{self, Write};\n\
+             // Needed on Windows only\n#[cfg(windows)]\nuse std::os::windows::ffi::OsStrExt;\n\n\
+             use serde::Serialize;\n\nuse crate::models::User;\npub use crate::models::Role;\n"
+        );
+        assert_eq!(block.removed, vec!["std::io::Read", "std::fmt"]);
+        assert_eq!(&text[..block.range.start], "//! Docs\n\n");
+        assert_eq!(&text[block.range.end..], "\nfn main() {}\n");
+
+        // Merged per granularity; an import its module's glob covers is redundant
+        let text = "use std::fs;\nuse std::io::*;\nuse std::io::Write;\n";
+        let crate_style = ImportStyle::parse("imports_granularity = \"Crate\"\n")?;
+        let block = organize_imports(text, &[], &crate_style)?.unwrap();
+        assert_eq!(block.text, "use std::{fs, io::*};\n");
+
+        // Nothing left
+        let text = "use std::fmt;\n\nfn main() {}\n";
+        let block =
+            organize_imports(text, &[span_of(text, "std::fmt")], &ImportStyle::default())?.unwrap();
+        assert_eq!(
+            apply_edits(text, vec![(block.range, block.text)]),
+            "fn main() {}\n"
+        );
+        assert!(organize_imports("fn main() {}\n", &[], &ImportStyle::default())?.is_none());
+
+        Ok(())
+    }
+}
@@ -8,19 +8,30 @@ use std::fs;
 use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use toml;
 pub mod models;
+pub mod builtins;
 pub mod cache;
+pub mod cfg;
+pub mod crate_index;
+pub mod dependency_index;
+pub mod extract;
+pub mod imports;
+pub mod lockfile;
+pub mod manifest;
+pub mod module_ops;
 pub mod name_resolution;
+pub mod preview;
+pub mod reexports;
+pub mod rustdoc_json;
+pub mod std_catalog;
+pub mod syntax;
+pub mod transaction;
+#[cfg(test)]
+mod test_support;
 
 pub use models::*;
 pub use cache::*;
 pub use name_resolution::*;
-#[derive(Deserialize, Debug)]
-struct CargoToml {
-    dependencies: Option<HashMap<String, toml::Value>>,
-}
-
 // Enhanced data structures for better IDE integration
 #[derive(Serialize, Debug)]
 #[napi(object)]
@@ -28,12 +39,25 @@ pub struct EnhancedOutput {
     pub file: String,
     pub suggested_imports: Vec<ImportInfo>,
     pub external_crates: Vec<ExternalCrate>,
+    /// Every crate in Cargo.lock outside the workspace, including transitive ones
+    pub resolved_crates: Vec<ResolvedCrate>,
     pub diagnostics: Vec<Diagnostic>,
     pub unresolved_types: Vec<String>,
+    /// Method calls rustc could not resolve (E0599), usually for want of a trait import
+    pub unresolved_methods: Vec<UnresolvedMethod>,
     pub functions: Vec<FunctionInfo>,
     pub types: Vec<TypeInfo>,
 }
 
+#[derive(Serialize, Debug, Clone)]
+#[napi(object)]
+pub struct UnresolvedMethod {
+    pub method: String,
+    /// Receiver type as rustc prints it, e.g. `&mut File`
+    pub receiver: String,
+    pub span: Option<SpanInfo>,
+}
+
 #[derive(Serialize, Debug, Clone)]
 #[napi(object)]
 pub struct ImportInfo {
@@ -42,6 +66,8 @@ pub struct ImportInfo {
     pub span: Option<SpanInfo>,
     pub is_glob: bool,
     pub confidence: f64,
+    /// Visibility of a re-export as written, e.g. `pub(crate)`
+    pub visibility: Option<String>,
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -54,6 +80,24 @@ pub struct FunctionInfo {
     pub return_type: String,
     pub is_unsafe: bool,
     pub is_async: bool,
+    /// The `impl` or `trait` block this function is declared in
+    pub impl_context: Option<ImplContext>,
+    /// How the function takes `self`: `self`, `&self`, `&mut self` or `none`
+    pub self_kind: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[napi(object)]
+pub struct ImplContext {
+    /// Either `impl` or `trait`
+    pub kind: String,
+    /// Self type of an impl block, or the name of a trait definition
+    pub target_type: String,
+    /// Implemented trait for `impl Trait for Type` blocks
+    pub trait_name: Option<String>,
+    /// Block header as written, e.g. `impl<T: Clone> Display for Wrapper<T>`
+    pub header: String,
+    pub span: Option<SpanInfo>,
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -70,9 +114,14 @@ pub struct TypeInfo {
     pub name: String,
     pub span: Option<SpanInfo>,
     pub definition: String,
+    /// One of `struct`, `enum`, `trait`, `union` or `type_alias`
+    pub kind: String,
+    pub generics: Vec<String>,
+    /// Inline module path within the file (empty at the file root)
+    pub module_path: String,
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[napi(object)]
 pub struct SpanInfo {
     pub line_start: u32,
@@ -81,11 +130,46 @@ pub struct SpanInfo {
     pub column_end: u32,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone)]
 #[napi(object)]
 pub struct ExternalCrate {
+    /// Crate name as code refers to it (the dependency key with `-` replaced by `_`)
     pub name: String,
+    /// Version requirement from the manifest, or `*`
     pub version: String,
+    /// "normal", "dev" or "build"
+    pub kind: String,
+    /// Package name in the registry/git/path source
+    pub package: String,
+    /// Dependency key when it renames the package with `package = "..."`
+    pub rename: Option<String>,
+    pub features: Vec<String>,
+    /// `false` when the dependency sets `default-features = false`
+    pub default_features: bool,
+    pub optional: bool,
+    /// "registry", "registry+<name>", "git+<url>[?branch=..]" or "path+<dir>"
+    pub source: String,
+    /// `cfg(...)` or target triple for target-specific dependencies
+    pub target: Option<String>,
+    /// Workspace member declaring the dependency
+    pub member: String,
+    /// The package this dependency binds to in Cargo.lock
+    pub resolved: Option<ResolvedCrate>,
+}
+
+/// A package as locked in Cargo.lock
+#[derive(Serialize, Debug, Clone)]
+#[napi(object)]
+pub struct ResolvedCrate {
+    pub name: String,
+    /// Exact version being compiled
+    pub version: String,
+    pub source: Option<String>,
+    pub checksum: Option<String>,
+    /// Shortest chain of `name version` ids from a workspace member to this crate
+    pub dependency_path: Vec<String>,
+    /// Other versions of the same crate in the lockfile
+    pub duplicate_versions: Vec<String>,
 }
 
 #[derive(Serialize, Debug)]
@@ -152,6 +236,42 @@ impl AnalysisState {
     pub fn get_imports(&self) -> Vec<ImportInfo> {
         self.imports.values().cloned().collect()
     }
+
+    /// Analyze a file's functions, types and imports
+    ///
+    /// Functions are keyed by name, qualified by their `impl` or `trait` target; types by
+    /// inline module path and name; imports by the import as written without its
+    /// visibility, e.g. `std::io::Write as W`. Of identical imports the first is kept.
+    pub fn from_file(file_path: String) -> anyhow::Result<Self> {
+        let source = syntax::SourceFile::read(&file_path)?;
+        let mut state = Self::new(file_path);
+        for function in source.functions() {
+            let key = match &function.impl_context {
+                Some(context) => format!("{}::{}", context.target_type, function.name),
+                None => function.name.clone(),
+            };
+            state.functions.entry(key).or_insert(function);
+        }
+        for info in source.types() {
+            let key = if info.module_path.is_empty() {
+                info.name.clone()
+            } else {
+                format!("{}::{}", info.module_path, info.name)
+            };
+            state.types.entry(key).or_insert(info);
+        }
+        for import in source.imports() {
+            let mut key = import.path.clone();
+            if import.is_glob {
+                key.push_str("::*");
+            }
+            if let Some(alias) = &import.alias {
+                key.push_str(&format!(" as {}", alias));
+            }
+            state.imports.entry(key).or_insert(import);
+        }
+        Ok(state)
+    }
 }
 
 /// Enhanced version of cargo check that provides more accurate import suggestions
@@ -165,12 +285,14 @@ pub fn enhanced_cargo_check(workspace_root: String, target_file: String) -> Resu
                 file: target_file,
                 suggested_imports: vec![],
                 external_crates: vec![],
+                resolved_crates: vec![],
                 diagnostics: vec![Diagnostic {
                     level: "error".to_string(),
                     message: e.to_string(),
                     span: None,
                 }],
                 unresolved_types: vec![],
+                unresolved_methods: vec![],
                 functions: vec![],
                 types: vec![],
             })
@@ -199,6 +321,7 @@ pub fn suggest_imports_for_types_legacy(
             span: None,
             is_glob: false,
             confidence: 0.9,
+            visibility: None,
         });
     }
 
@@ -251,15 +374,23 @@ pub fn resolve_trait_bounds(_code: String, generic_params: Vec<String>) -> Resul
     Ok(trait_bounds)
 }
 
-/// Get function information for a given file
+/// Get the innermost function enclosing a 1-based line/column position
 #[napi]
-pub fn get_function_info(
-    _file_path: String,
-    _workspace_root: String,
-) -> Result<Option<FunctionInfo>> {
-    // Placeholder implementation
-    // In a real implementation, this would parse the file and extract function info
-    Ok(None)
+pub fn get_function_info(file_path: String, line: u32, column: u32) -> Result<Option<FunctionInfo>> {
+    let source = syntax::SourceFile::read(&file_path)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+    Ok(source.function_at(line, column))
+}
+
+/// Extract the statements covered by a 1-based, end-exclusive range into a new function
+#[napi]
+pub fn extract_function(file_path: String, range: SpanInfo, new_name: String) -> Result<ExtractionResult> {
+    let source = syntax::SourceFile::read(&file_path)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+    extract::extract_function(&source, &range, &new_name)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))
 }
 
 /// Information about module conversion from file to folder
@@ -309,17 +440,209 @@ pub fn check_module_conversion(
     })
 }
 
+/// A file created, renamed or deleted by a previewed refactoring
+#[derive(Serialize, Debug)]
+#[napi(object)]
+pub struct FileOperation {
+    /// "create", "rename" or "delete"
+    pub kind: String,
+    pub path: String,
+    /// Destination of a rename
+    pub new_path: Option<String>,
+}
+
+/// A replacement of the text in `range` (against the file's current contents)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[napi(object)]
+pub struct TextEdit {
+    pub file: String,
+    pub range: SpanInfo,
+    pub new_text: String,
+}
+
+/// Everything a refactoring would change, computed without touching the disk
+#[derive(Serialize, Debug)]
+#[napi(object)]
+pub struct RefactorPreview {
+    pub label: String,
+    pub file_operations: Vec<FileOperation>,
+    /// Edits to existing files and the full text of created files
+    pub edits: Vec<TextEdit>,
+}
+
 /// Convert a module file to a folder structure
-/// Moves models.rs -> models/mod.rs
+/// Moves models.rs -> models/mod.rs as one transaction that `revert_last_refactor` can undo
 #[napi]
 pub fn convert_module_to_folder(
+    workspace_root: String,
     existing_file_path: String,
-    target_folder_path: String,
     target_mod_file_path: String,
 ) -> Result<bool> {
-    let source = Path::new(&existing_file_path);
-    let folder = Path::new(&target_folder_path);
-    let mod_file = Path::new(&target_mod_file_path);
+    let transaction = module_to_folder_transaction(&existing_file_path, &target_mod_file_path)?;
+    transaction.commit(Path::new(&workspace_root)).map_err(|e| {
+        napi::Error::new(
+            napi::Status::GenericFailure,
+            format!("Failed to convert module: {:#}", e),
+        )
+    })?;
+    
+    Ok(true)
+}
+
+/// Preview `convert_module_to_folder` without changing any file
+#[napi]
+pub fn preview_convert_module_to_folder(
+    existing_file_path: String,
+    target_mod_file_path: String,
+) -> Result<RefactorPreview> {
+    let transaction = module_to_folder_transaction(&existing_file_path, &target_mod_file_path)?;
+    preview::preview(&transaction).map_err(|e| napi::Error::from_reason(e.to_string()))
+}
+
+/// Result of moving items into another module
+#[derive(Serialize, Debug)]
+#[napi(object)]
+pub struct ModuleExtractionResult {
+    /// Path of the target module, e.g. `crate::models::get_functions`
+    pub module_path: String,
+    pub target_file: String,
+    pub moved_items: Vec<String>,
+    /// Existing files that were rewritten
+    pub changed_files: Vec<String>,
+    /// Files created for the module and any missing parent modules
+    pub created_files: Vec<String>,
+}
+
+/// Move items from a source file into a new or existing module file
+/// Inserts the `mod` declaration, widens visibility where the items are still used and
+/// rewrites `use` paths across the crate. Methods are named `Type::method`.
+#[napi]
+pub fn extract_to_module(
+    workspace_root: String,
+    source_file: String,
+    item_names: Vec<String>,
+    target_file: String,
+) -> Result<ModuleExtractionResult> {
+    let (plan, transaction) =
+        extract_to_module_transaction(&workspace_root, &source_file, &item_names, &target_file)?;
+    transaction
+        .commit(Path::new(&workspace_root))
+        .map_err(|e| napi::Error::from_reason(format!("{:#}", e)))?;
+
+    let (created, changed): (Vec<_>, Vec<_>) = plan.changes.iter().partition(|c| c.created);
+    let display = |changes: Vec<&transaction::FileChange>| {
+        changes
+            .iter()
+            .map(|c| c.path.to_string_lossy().to_string())
+            .collect()
+    };
+
+    Ok(ModuleExtractionResult {
+        module_path: plan.module_path,
+        target_file: Path::new(&workspace_root)
+            .join(&target_file)
+            .to_string_lossy()
+            .to_string(),
+        moved_items: plan.moved_items,
+        changed_files: display(changed),
+        created_files: display(created),
+    })
+}
+
+/// Preview `extract_to_module` without changing any file
+#[napi]
+pub fn preview_extract_to_module(
+    workspace_root: String,
+    source_file: String,
+    item_names: Vec<String>,
+    target_file: String,
+) -> Result<RefactorPreview> {
+    let (_, transaction) =
+        extract_to_module_transaction(&workspace_root, &source_file, &item_names, &target_file)?;
+    preview::preview(&transaction).map_err(|e| napi::Error::from_reason(e.to_string()))
+}
+
+/// Result of converting a folder module back into a single file
+#[derive(Serialize, Debug)]
+#[napi(object)]
+pub struct ModuleCollapseResult {
+    pub module_file_path: String,
+    /// Child module files merged into the module file as inline `mod` blocks
+    pub merged_files: Vec<String>,
+    pub removed_files: Vec<String>,
+}
+
+/// Convert a folder module back to a file
+/// Moves models/mod.rs -> models.rs. With `merge_children`, child module files are merged in
+/// as inline `mod` blocks; otherwise they stay in models/ (the 2018 layout).
+#[napi]
+pub fn convert_folder_to_module_file(
+    workspace_root: String,
+    mod_file_path: String,
+    merge_children: bool,
+) -> Result<ModuleCollapseResult> {
+    let (plan, transaction) = folder_to_module_file_transaction(&mod_file_path, merge_children)?;
+    transaction
+        .commit(Path::new(&workspace_root))
+        .map_err(|e| napi::Error::from_reason(format!("{:#}", e)))?;
+
+    let display = |path: &PathBuf| path.to_string_lossy().to_string();
+    Ok(ModuleCollapseResult {
+        module_file_path: display(&plan.module_file),
+        merged_files: plan.merged_files.iter().map(display).collect(),
+        removed_files: plan
+            .changes
+            .iter()
+            .filter(|c| c.contents.is_none())
+            .map(|c| display(&c.path))
+            .collect(),
+    })
+}
+
+/// Preview `convert_folder_to_module_file` without changing any file
+#[napi]
+pub fn preview_convert_folder_to_module_file(
+    mod_file_path: String,
+    merge_children: bool,
+) -> Result<RefactorPreview> {
+    let (_, transaction) = folder_to_module_file_transaction(&mod_file_path, merge_children)?;
+    preview::preview(&transaction).map_err(|e| napi::Error::from_reason(e.to_string()))
+}
+
+/// A refactoring undone by `revert_last_refactor`
+#[derive(Serialize, Debug)]
+#[napi(object)]
+pub struct RevertResult {
+    pub label: String,
+    pub restored_files: Vec<String>,
+}
+
+/// Undo the most recent refactoring recorded in the workspace's undo journal
+/// Returns null when there is nothing to revert; fails if the touched files changed since.
+#[napi]
+pub fn revert_last_refactor(workspace_root: String) -> Result<Option<RevertResult>> {
+    let entry = transaction::revert_last(Path::new(&workspace_root))
+        .map_err(|e| napi::Error::from_reason(format!("{:#}", e)))?;
+
+    Ok(entry.map(|entry| RevertResult {
+        label: entry.label,
+        restored_files: entry
+            .files
+            .iter()
+            .map(|file| file.path.to_string_lossy().to_string())
+            .collect(),
+    }))
+}
+
+// Private implementation functions
+
+/// Stage models.rs -> models/mod.rs: write mod.rs and remove the original file together
+fn module_to_folder_transaction(
+    existing_file_path: &str,
+    target_mod_file_path: &str,
+) -> napi::Result<transaction::Transaction> {
+    let source = Path::new(existing_file_path);
+    let mod_file = Path::new(target_mod_file_path);
     
     // Verify source exists
     if !source.exists() {
@@ -329,14 +652,6 @@ pub fn convert_module_to_folder(
         ));
     }
     
-    // Create the folder
-    fs::create_dir_all(folder).map_err(|e| {
-        napi::Error::new(
-            napi::Status::GenericFailure,
-            format!("Failed to create folder: {}", e),
-        )
-    })?;
-    
     // Read the content from the original file
     let content = fs::read_to_string(source).map_err(|e| {
         napi::Error::new(
@@ -345,26 +660,61 @@ pub fn convert_module_to_folder(
         )
     })?;
     
-    // Write to mod.rs
-    fs::write(mod_file, content).map_err(|e| {
-        napi::Error::new(
-            napi::Status::GenericFailure,
-            format!("Failed to write mod.rs: {}", e),
-        )
-    })?;
-    
-    // Remove the original file
-    fs::remove_file(source).map_err(|e| {
-        napi::Error::new(
-            napi::Status::GenericFailure,
-            format!("Failed to remove original file: {}", e),
-        )
-    })?;
-    
-    Ok(true)
+    let mut transaction = transaction::Transaction::new(format!(
+        "Convert {} to {}",
+        source.display(),
+        mod_file.display()
+    ));
+    transaction
+        .stage(transaction::FileChange {
+            path: mod_file.to_path_buf(),
+            contents: Some(content),
+            created: true,
+        })
+        .stage(transaction::FileChange {
+            path: source.to_path_buf(),
+            contents: None,
+            created: false,
+        });
+    Ok(transaction)
 }
 
-// Private implementation functions
+fn extract_to_module_transaction(
+    workspace_root: &str,
+    source_file: &str,
+    item_names: &[String],
+    target_file: &str,
+) -> napi::Result<(module_ops::ModuleMove, transaction::Transaction)> {
+    let workspace = Path::new(workspace_root);
+    let plan = module_ops::plan_extract_to_module(
+        &workspace.join(source_file),
+        item_names,
+        &workspace.join(target_file),
+    )
+    .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+    let mut transaction = transaction::Transaction::new(format!("Extract to {}", plan.module_path));
+    transaction.stage_all(plan.changes.clone());
+    Ok((plan, transaction))
+}
+
+fn folder_to_module_file_transaction(
+    mod_file_path: &str,
+    merge_children: bool,
+) -> napi::Result<(module_ops::ModuleCollapse, transaction::Transaction)> {
+    let layout = if merge_children {
+        module_ops::CollapseLayout::Inline
+    } else {
+        module_ops::CollapseLayout::Flat
+    };
+    let plan = module_ops::plan_collapse_module(Path::new(mod_file_path), layout)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+    let mut transaction =
+        transaction::Transaction::new(format!("Convert {} to a file", mod_file_path));
+    transaction.stage_all(plan.changes.clone());
+    Ok((plan, transaction))
+}
 
 fn enhanced_check_impl(workspace_root: &str, target_file: &str) -> napi::Result<EnhancedOutput> {
     // Ensure target file exists
@@ -373,69 +723,71 @@ fn enhanced_check_impl(workspace_root: &str, target_file: &str) -> napi::Result<
             file: target_file.to_string(),
             suggested_imports: vec![],
             external_crates: vec![],
+            resolved_crates: vec![],
             diagnostics: vec![],
             unresolved_types: vec![],
+            unresolved_methods: vec![],
             functions: vec![],
             types: vec![],
         });
     }
 
-    // Read external crates from Cargo.toml
-    let external_crates = read_cargo_dependencies(workspace_root);
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
 
-    // Run cargo check
-    let mut cmd = Command::new("cargo");
-    cmd.args(&["check", "--message-format=json", "--all-targets"]);
-    cmd.current_dir(workspace_root);
-    cmd.stdout(Stdio::piped());
-    cmd.stderr(Stdio::piped());
+    // Read external crates from every Cargo.toml in the workspace, resolved via Cargo.lock
+    let (external_crates, resolved_crates) =
+        match lockfile::resolve_workspace(Path::new(workspace_root)) {
+            Ok(resolution) => resolution,
+            Err(e) => {
+                diagnostics.push(Diagnostic {
+                    level: "warning".to_string(),
+                    message: format!("Failed to read Cargo manifests: {:#}", e),
+                    span: None,
+                });
+                (vec![], vec![])
+            }
+        };
 
-    let mut child = cmd.spawn().map_err(|e| {
+    // Parse the target file for function and type information
+    let (functions, types) = match syntax::SourceFile::read(target_file) {
+        Ok(source) => (source.functions(), source.types()),
+        Err(e) => {
+            diagnostics.push(Diagnostic {
+                level: "warning".to_string(),
+                message: format!("Failed to parse {}: {}", target_file, e),
+                span: None,
+            });
+            (vec![], vec![])
+        }
+    };
+
+    let backtick_re = Regex::new(r#"`([^`]+)`"#).map_err(|e| {
         napi::Error::new(
             napi::Status::GenericFailure,
-            format!("Failed to run cargo check: {}", e),
+            format!("Failed to compile regex: {}", e),
         )
     })?;
-    let stdout = child.stdout.take().expect("Failed to capture stdout");
-    let reader = BufReader::new(stdout);
-
-    let backtick_re = Regex::new(r#"`([^`]+)`"#).map_err(|e| {
+    let type_re = Regex::new(r"(?:type|struct|enum|trait)\s+`([^`]+)`").map_err(|e| {
         napi::Error::new(
             napi::Status::GenericFailure,
             format!("Failed to compile regex: {}", e),
         )
     })?;
-    let type_re = Regex::new(r"(?:type|struct|enum|trait)\s+`([^`]+)`").map_err(|e| {
+    let method_re = Regex::new(r"no method named `([^`]+)` found for [^`]*`([^`]+)`").map_err(|e| {
         napi::Error::new(
             napi::Status::GenericFailure,
             format!("Failed to compile regex: {}", e),
         )
     })?;
     let mut suggestions: HashSet<String> = HashSet::new();
-    let mut diagnostics: Vec<Diagnostic> = Vec::new();
     let mut unresolved_types: HashSet<String> = HashSet::new();
+    let mut unresolved_methods: Vec<UnresolvedMethod> = Vec::new();
 
     // Canonicalize target file for reliable comparison
     let canonical_target =
         fs::canonicalize(target_file).unwrap_or_else(|_| PathBuf::from(target_file));
 
-    for line in reader.lines() {
-        let line = line.unwrap_or_default();
-        if line.trim().is_empty() {
-            continue;
-        }
-
-        // Parse JSON
-        let v: Value = match serde_json::from_str(&line) {
-            Ok(v) => v,
-            Err(_) => continue,
-        };
-
-        if v.get("reason").and_then(|r| r.as_str()) != Some("compiler-message") {
-            continue;
-        }
-
-        let message = &v["message"];
+    for message in &cargo_check_messages(workspace_root)? {
         let level = message["level"].as_str().unwrap_or("").to_string();
         let msg_text = message["message"].as_str().unwrap_or("").to_string();
 
@@ -474,7 +826,7 @@ fn enhanced_check_impl(workspace_root: &str, target_file: &str) -> napi::Result<
         diagnostics.push(Diagnostic {
             level: level.clone(),
             message: msg_text.clone(),
-            span: span_info,
+            span: span_info.clone(),
         });
 
         // Extract unresolved types
@@ -486,6 +838,17 @@ fn enhanced_check_impl(workspace_root: &str, target_file: &str) -> napi::Result<
             }
         }
 
+        // Methods whose trait is not in scope
+        if message["code"]["code"].as_str() == Some("E0599") {
+            if let Some(cap) = method_re.captures(&msg_text) {
+                unresolved_methods.push(UnresolvedMethod {
+                    method: cap[1].to_string(),
+                    receiver: cap[2].to_string(),
+                    span: span_info,
+                });
+            }
+        }
+
         // Process rendered message for import suggestions
         if let Some(rendered) = message.get("rendered").and_then(|r| r.as_str()) {
             extract_imports_from_rendered(rendered, &backtick_re, &mut suggestions);
@@ -504,13 +867,20 @@ fn enhanced_check_impl(workspace_root: &str, target_file: &str) -> napi::Result<
                         extract_imports_from_rendered(rendered, &backtick_re, &mut suggestions);
                     }
                 }
+
+                // Suggested `use` lines, e.g. for a trait that provides a method
+                if let Some(spans) = child.get("spans").and_then(|s| s.as_array()) {
+                    for span in spans {
+                        let replacement = span["suggested_replacement"].as_str().unwrap_or("");
+                        if let Some(path) = replacement.trim().strip_prefix("use ") {
+                            suggestions.insert(path.trim_end_matches(';').trim().to_string());
+                        }
+                    }
+                }
             }
         }
     }
 
-    // Wait for child process
-    let _ = child.wait();
-
     // Convert structured suggestions
     let mut import_infos = Vec::new();
     for suggestion in suggestions {
@@ -520,6 +890,7 @@ fn enhanced_check_impl(workspace_root: &str, target_file: &str) -> napi::Result<
             span: None,
             is_glob: false,
             confidence: 0.8,
+            visibility: None,
         });
     }
 
@@ -529,13 +900,55 @@ fn enhanced_check_impl(workspace_root: &str, target_file: &str) -> napi::Result<
         file: canonical_target.to_string_lossy().to_string(),
         suggested_imports: import_infos,
         external_crates,
+        resolved_crates,
         diagnostics,
         unresolved_types: unresolved_vec,
-        functions: vec![], // TODO: Implement function extraction
-        types: vec![],     // TODO: Implement type extraction
+        unresolved_methods,
+        functions,
+        types,
     })
 }
 
+/// Run `cargo check` in a workspace and collect the compiler messages it reports
+fn cargo_check_messages(workspace_root: &str) -> napi::Result<Vec<Value>> {
+    let mut cmd = Command::new("cargo");
+    cmd.args(["check", "--message-format=json", "--all-targets"]);
+    cmd.current_dir(workspace_root);
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+
+    let mut child = cmd.spawn().map_err(|e| {
+        napi::Error::new(
+            napi::Status::GenericFailure,
+            format!("Failed to run cargo check: {}", e),
+        )
+    })?;
+    let stdout = child.stdout.take().expect("Failed to capture stdout");
+    let reader = BufReader::new(stdout);
+
+    let mut messages = Vec::new();
+    for line in reader.lines() {
+        let line = line.unwrap_or_default();
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        // Parse JSON
+        let mut v: Value = match serde_json::from_str(&line) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+
+        if v.get("reason").and_then(|r| r.as_str()) == Some("compiler-message") {
+            messages.push(v["message"].take());
+        }
+    }
+
+    // Wait for child process
+    let _ = child.wait();
+    Ok(messages)
+}
+
 /// Extract import suggestions from rendered compiler output
 fn extract_imports_from_rendered(
     rendered: &str,
@@ -564,33 +977,6 @@ fn extract_imports_from_rendered(
     }
 }
 
-/// Read dependencies from Cargo.toml
-fn read_cargo_dependencies(workspace_root: &str) -> Vec<ExternalCrate> {
-    let cargo_path = Path::new(workspace_root).join("Cargo.toml");
-    let mut crates = Vec::new();
-
-    if let Ok(contents) = fs::read_to_string(&cargo_path) {
-        if let Ok(cargo_toml) = toml::from_str::<CargoToml>(&contents) {
-            if let Some(deps) = cargo_toml.dependencies {
-                for (name, value) in deps {
-                    let version = match value {
-                        toml::Value::String(v) => v,
-                        toml::Value::Table(t) => t
-                            .get("version")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("*")
-                            .to_string(),
-                        _ => "*".to_string(),
-                    };
-                    crates.push(ExternalCrate { name, version });
-                }
-            }
-        }
-    }
-
-    crates
-}
-
 // ============================================================================
 // NAPI Cache Bindings
 // ============================================================================
@@ -697,12 +1083,19 @@ pub fn clear_cache(workspace_root: String) -> Result<bool> {
 // ============================================================================
 
 /// Suggest imports for unresolved types using the name resolution engine
+///
+/// Returns one group of ranked matches per type, best match first. `path_preference` is
+/// `"shortest"` (the default) or `"canonical"`, see `PathPreference`.
 #[napi]
 pub fn suggest_imports_for_types(
     workspace_root: String,
     unresolved_types: Vec<String>,
+    rustdoc_json: Option<Vec<String>>,
+    path_preference: Option<String>,
 ) -> Result<String> {
-    let resolver = NameResolver::new();
+    let resolver = NameResolver::new()
+        .with_rustdoc_json(rustdoc_files(rustdoc_json))
+        .with_path_preference(parse_path_preference(path_preference)?);
     
     let matches = resolver.find_matches_for_types(&unresolved_types, Path::new(&workspace_root))
         .map_err(|e| napi::Error::from_reason(e.to_string()))?;
@@ -714,6 +1107,157 @@ pub fn suggest_imports_for_types(
     Ok(json)
 }
 
+/// Suggest imports for the name at a 1-based line/column of a file
+///
+/// Only kinds of item that fit how the name is used there (type, trait, call, macro or
+/// pattern) are matched. Returns `null` when there is no name at the position.
+#[napi]
+pub fn suggest_imports_at(
+    workspace_root: String,
+    file_path: String,
+    line: u32,
+    column: u32,
+    rustdoc_json: Option<Vec<String>>,
+    path_preference: Option<String>,
+) -> Result<String> {
+    let resolver = NameResolver::new()
+        .with_rustdoc_json(rustdoc_files(rustdoc_json))
+        .with_path_preference(parse_path_preference(path_preference)?);
+
+    let matches = resolver.find_matches_at(Path::new(&file_path), line, column, Path::new(&workspace_root))
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+    serde_json::to_string(&matches)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))
+}
+
+/// Every name visible at a 1-based line/column of a file, innermost first
+///
+/// Locals, generic parameters, imports (globs expanded), items of the enclosing module and
+/// the prelude, each under the name it can be written by there; items of ancestor modules
+/// follow under `super::` paths.
+#[napi]
+pub fn items_in_scope_at(
+    workspace_root: String,
+    file_path: String,
+    line: u32,
+    column: u32,
+    rustdoc_json: Option<Vec<String>>,
+) -> Result<String> {
+    let resolver = NameResolver::new().with_rustdoc_json(rustdoc_files(rustdoc_json));
+
+    let items = resolver
+        .in_scope_at(Path::new(&file_path), line, column, Path::new(&workspace_root))
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+    serde_json::to_string(&items).map_err(|e| napi::Error::from_reason(e.to_string()))
+}
+
+/// Every import in a file in source order: `use` declarations flattened to one entry per
+/// path (groups, `self`, renames and globs resolved), `pub use` re-exports and `extern crate`
+#[napi]
+pub fn get_file_imports(file_path: String) -> Result<Vec<ImportInfo>> {
+    let source = syntax::SourceFile::read(&file_path)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    Ok(source.imports())
+}
+
+/// A file's rewritten import block
+#[derive(Serialize, Debug)]
+#[napi(object)]
+pub struct OrganizedImports {
+    pub file: String,
+    /// Range of the original import block, `None` when the file has no imports
+    pub range: Option<SpanInfo>,
+    /// The rewritten block, to replace `range` with
+    pub new_text: String,
+    /// Imports removed as unused
+    pub removed: Vec<String>,
+}
+
+/// Organize a file's imports without changing the file
+///
+/// Drops the imports `cargo check` reports as unused, collapses duplicates, and sorts and
+/// groups the rest std / external / crate following rustfmt.toml. `#[cfg]`-gated imports,
+/// re-exports and comments are kept.
+#[napi]
+pub fn organize_imports(workspace_root: String, file_path: String) -> Result<OrganizedImports> {
+    let text = fs::read_to_string(&file_path)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to read {}: {}", file_path, e)))?;
+    let canonical_target =
+        fs::canonicalize(&file_path).unwrap_or_else(|_| PathBuf::from(&file_path));
+
+    let mut unused = Vec::new();
+    for message in cargo_check_messages(&workspace_root)? {
+        if message["code"]["code"].as_str() != Some("unused_imports") {
+            continue;
+        }
+        for span in message["spans"].as_array().into_iter().flatten() {
+            let file_name = Path::new(&workspace_root).join(span["file_name"].as_str().unwrap_or(""));
+            if fs::canonicalize(&file_name).unwrap_or(file_name) == canonical_target {
+                unused.push(SpanInfo {
+                    line_start: span["line_start"].as_u64().unwrap_or(0) as u32,
+                    line_end: span["line_end"].as_u64().unwrap_or(0) as u32,
+                    column_start: span["column_start"].as_u64().unwrap_or(0) as u32,
+                    column_end: span["column_end"].as_u64().unwrap_or(0) as u32,
+                });
+            }
+        }
+    }
+
+    let style = imports::ImportStyle::discover(canonical_target.parent().unwrap_or(Path::new(".")))
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    let block = imports::organize_imports(&text, &unused, &style)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    Ok(match block {
+        Some(block) => OrganizedImports {
+            file: file_path,
+            range: Some(preview::span_between(&text, block.range.start, block.range.end)),
+            new_text: block.text,
+            removed: block.removed,
+        },
+        None => OrganizedImports {
+            file: file_path,
+            range: None,
+            new_text: String::new(),
+            removed: vec![],
+        },
+    })
+}
+
+/// Suggest traits to import for a method rustc could not find on a receiver (E0599)
+///
+/// Returns the ranked matches as JSON, best first.
+#[napi]
+pub fn suggest_trait_imports(
+    workspace_root: String,
+    receiver: String,
+    method: String,
+    rustdoc_json: Option<Vec<String>>,
+) -> Result<String> {
+    let resolver = NameResolver::new().with_rustdoc_json(rustdoc_files(rustdoc_json));
+
+    let matches = resolver.find_traits_for_method(&receiver, &method, Path::new(&workspace_root))
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+    serde_json::to_string(&matches)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))
+}
+
+/// Text edits adding a chosen import to a file
+///
+/// The import joins an existing `use` declaration or goes on a new line in its std / external /
+/// crate group, following `imports_granularity`, `group_imports` and `max_width` from the
+/// nearest rustfmt.toml. Returns no edits when the file already has the import, and fails when
+/// its name is taken by another import or item.
+#[napi]
+pub fn apply_import(file_path: String, import: ImportInfo) -> Result<Vec<TextEdit>> {
+    let leaves = imports::import_leaves(&import.path, import.alias.as_deref(), import.is_glob)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    imports::import_edits(Path::new(&file_path), &leaves)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))
+}
+
 /// Get all importable items from std library
 #[napi]
 pub fn get_std_library_items() -> Result<String> {
@@ -733,14 +1277,15 @@ pub fn get_std_library_items() -> Result<String> {
 pub fn find_best_import(
     workspace_root: String,
     type_name: String,
+    rustdoc_json: Option<Vec<String>>,
 ) -> Result<String> {
-    let resolver = NameResolver::new();
+    let resolver = NameResolver::new().with_rustdoc_json(rustdoc_files(rustdoc_json));
     
-    let matches = resolver.find_matches_for_types(&[type_name], Path::new(&workspace_root))
+    let groups = resolver.find_matches_for_types(&[type_name], Path::new(&workspace_root))
         .map_err(|e| napi::Error::from_reason(e.to_string()))?;
     
     // Get the best match (highest confidence)
-    if let Some(best_match) = matches.first() {
+    if let Some(best_match) = groups.first().and_then(|group| group.matches.first()) {
         let json = serde_json::to_string(&best_match)
             .map_err(|e| napi::Error::from_reason(e.to_string()))?;
         Ok(json)
@@ -751,12 +1296,15 @@ pub fn find_best_import(
 
 /// Resolve all names in a project (expensive operation, use cache!)
 #[napi]
-pub fn resolve_project_names(workspace_root: String) -> Result<String> {
+pub fn resolve_project_names(
+    workspace_root: String,
+    rustdoc_json: Option<Vec<String>>,
+) -> Result<String> {
     // Create resolver with cache
     let cache = IncrementalCache::new(&workspace_root)
         .map_err(|e| napi::Error::from_reason(e.to_string()))?;
     
-    let resolver = NameResolver::with_cache(cache);
+    let resolver = NameResolver::with_cache(cache).with_rustdoc_json(rustdoc_files(rustdoc_json));
     
     let result = resolver.resolve_project(Path::new(&workspace_root))
         .map_err(|e| napi::Error::from_reason(e.to_string()))?;
@@ -766,3 +1314,24 @@ pub fn resolve_project_names(workspace_root: String) -> Result<String> {
     
     Ok(json)
 }
+
+/// Paths of the `rustdoc --output-format json` files passed to a name resolution binding
+fn rustdoc_files(rustdoc_json: Option<Vec<String>>) -> Vec<PathBuf> {
+    rustdoc_json
+        .unwrap_or_default()
+        .into_iter()
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// The `PathPreference` named by a name resolution binding's `path_preference` argument
+fn parse_path_preference(path_preference: Option<String>) -> Result<PathPreference> {
+    match path_preference.as_deref() {
+        None | Some("shortest") => Ok(PathPreference::Shortest),
+        Some("canonical") => Ok(PathPreference::Canonical),
+        Some(other) => Err(napi::Error::from_reason(format!(
+            "Unknown path preference `{}` (expected `shortest` or `canonical`)",
+            other
+        ))),
+    }
+}
@@ -0,0 +1,374 @@
+//! Cargo.lock reading for Rusty Refactor
+//!
+//! This module resolves manifest dependencies to the exact packages in `Cargo.lock`, and
+//! reports every locked crate (including transitive ones) with its checksum, the dependency
+//! chain that pulls it in and any other versions of the same crate in the graph.
+
+use anyhow::{Context, Result};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use toml::{Table, Value};
+
+use crate::manifest::Workspace;
+use crate::{ExternalCrate, ResolvedCrate};
+
+/// One `[[package]]` entry of the lockfile
+#[derive(Debug, Clone)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+    /// `None` for workspace and path packages
+    pub source: Option<String>,
+    pub checksum: Option<String>,
+    /// Dependency references as written: `name`, `name version` or `name version (source)`
+    pub dependencies: Vec<String>,
+}
+
+impl LockedPackage {
+    /// `name version`, the form dependency paths are reported in
+    pub fn id(&self) -> String {
+        format!("{} {}", self.name, self.version)
+    }
+}
+
+/// A parsed `Cargo.lock`
+#[derive(Debug, Clone, Default)]
+pub struct Lockfile {
+    pub packages: Vec<LockedPackage>,
+}
+
+impl Lockfile {
+    pub fn parse(text: &str) -> Result<Self> {
+        let table: Table = text.parse().context("Failed to parse Cargo.lock")?;
+        let text_of = |package: &Table, key: &str| {
+            package.get(key).and_then(Value::as_str).map(str::to_string)
+        };
+
+        let packages = table
+            .get("package")
+            .and_then(Value::as_array)
+            .map(|packages| {
+                packages
+                    .iter()
+                    .filter_map(Value::as_table)
+                    .map(|package| LockedPackage {
+                        name: text_of(package, "name").unwrap_or_default(),
+                        version: text_of(package, "version").unwrap_or_default(),
+                        source: text_of(package, "source"),
+                        checksum: text_of(package, "checksum"),
+                        dependencies: package
+                            .get("dependencies")
+                            .and_then(Value::as_array)
+                            .map(|deps| {
+                                deps.iter()
+                                    .filter_map(Value::as_str)
+                                    .map(str::to_string)
+                                    .collect()
+                            })
+                            .unwrap_or_default(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(Self { packages })
+    }
+
+    /// Read the workspace's lockfile, if there is one
+    pub fn read(workspace: &Workspace) -> Result<Option<Self>> {
+        let path = workspace.root.join("Cargo.lock");
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    /// Index of the package a dependency reference points at
+    fn lookup(&self, reference: &str) -> Option<usize> {
+        let mut parts = reference.splitn(3, ' ');
+        let name = parts.next()?;
+        let version = parts.next();
+        let source = parts
+            .next()
+            .map(|s| s.trim_start_matches('(').trim_end_matches(')'));
+
+        self.packages.iter().position(|package| {
+            package.name == name
+                && version.is_none_or(|v| package.version == v)
+                && source.is_none_or(|s| package.source.as_deref() == Some(s))
+        })
+    }
+
+    /// The package `parent` at `version` depends on under the package name `package`
+    pub fn dependency_of(
+        &self,
+        parent: &str,
+        version: &str,
+        package: &str,
+    ) -> Option<&LockedPackage> {
+        let parent = self
+            .packages
+            .iter()
+            .find(|p| p.name == parent && p.version == version)?;
+        parent
+            .dependencies
+            .iter()
+            .filter_map(|reference| self.lookup(reference))
+            .map(|i| &self.packages[i])
+            .find(|p| p.name == package)
+    }
+
+    /// Workspace members: unsourced packages named like a member of `workspace`
+    fn member_indices(&self, workspace: &Workspace) -> Vec<usize> {
+        self.packages
+            .iter()
+            .enumerate()
+            .filter(|(_, package)| {
+                package.source.is_none()
+                    && workspace
+                        .packages
+                        .iter()
+                        .any(|member| member.name == package.name)
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Shortest chain of package ids from a workspace member to each package
+    fn dependency_paths(&self, roots: &[usize]) -> Vec<Option<Vec<usize>>> {
+        let mut paths: Vec<Option<Vec<usize>>> = vec![None; self.packages.len()];
+        let mut queue = VecDeque::new();
+        for &root in roots {
+            paths[root] = Some(vec![root]);
+            queue.push_back(root);
+        }
+
+        while let Some(current) = queue.pop_front() {
+            for reference in &self.packages[current].dependencies {
+                let Some(next) = self.lookup(reference) else {
+                    continue;
+                };
+                if paths[next].is_none() {
+                    let mut path = paths[current].clone().unwrap_or_default();
+                    path.push(next);
+                    paths[next] = Some(path);
+                    queue.push_back(next);
+                }
+            }
+        }
+        paths
+    }
+
+    /// Every locked package outside the workspace, with how it is reached
+    pub fn resolved_crates(&self, workspace: &Workspace) -> Vec<ResolvedCrate> {
+        let members = self.member_indices(workspace);
+        let paths = self.dependency_paths(&members);
+
+        let mut versions: HashMap<&str, Vec<&str>> = HashMap::new();
+        for package in &self.packages {
+            versions
+                .entry(&package.name)
+                .or_default()
+                .push(&package.version);
+        }
+
+        (0..self.packages.len())
+            .filter(|i| !members.contains(i))
+            .map(|i| {
+                let package = &self.packages[i];
+                ResolvedCrate {
+                    name: package.name.clone(),
+                    version: package.version.clone(),
+                    source: package.source.clone(),
+                    checksum: package.checksum.clone(),
+                    dependency_path: paths[i]
+                        .iter()
+                        .flatten()
+                        .map(|&step| self.packages[step].id())
+                        .collect(),
+                    duplicate_versions: versions[package.name.as_str()]
+                        .iter()
+                        .filter(|v| **v != package.version)
+                        .map(|v| v.to_string())
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// Attach the locked package each manifest dependency binds to
+    pub fn resolve(&self, workspace: &Workspace, crates: &mut [ExternalCrate]) {
+        let resolved = self.resolved_crates(workspace);
+        let members = self.member_indices(workspace);
+
+        for dep in crates.iter_mut() {
+            // Prefer the exact references in the declaring member's dependency list; a member
+            // can depend on several versions of a package through renames
+            let from_member: Vec<&str> = members
+                .iter()
+                .map(|&i| &self.packages[i])
+                .find(|member| member.name == dep.member)
+                .map(|member| {
+                    member
+                        .dependencies
+                        .iter()
+                        .filter_map(|reference| self.lookup(reference))
+                        .map(|i| &self.packages[i])
+                        .filter(|package| package.name == dep.package)
+                        .map(|package| package.version.as_str())
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            let candidates = resolved.iter().filter(|c| {
+                c.name == dep.package
+                    && (from_member.is_empty() || from_member.contains(&c.version.as_str()))
+            });
+            dep.resolved = best_match(candidates, &dep.version).cloned();
+        }
+    }
+}
+
+/// The highest locked version meeting a manifest requirement, or the only candidate when
+/// none does (the requirement may be one semver cannot read, e.g. a bare git dependency)
+fn best_match<'a>(
+    candidates: impl Iterator<Item = &'a ResolvedCrate>,
+    requirement: &str,
+) -> Option<&'a ResolvedCrate> {
+    let candidates: Vec<&ResolvedCrate> = candidates.collect();
+    let requirement = semver::VersionReq::parse(requirement).ok();
+    let matching = candidates
+        .iter()
+        .filter_map(|c| Some((semver::Version::parse(&c.version).ok()?, *c)))
+        .filter(|(version, _)| requirement.as_ref().is_none_or(|req| req.matches(version)))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, c)| c);
+    match (matching, candidates.as_slice()) {
+        (Some(c), _) => Some(c),
+        (None, [only]) => Some(*only),
+        (None, _) => None,
+    }
+}
+
+/// Manifest dependencies resolved against the workspace's lockfile, plus every locked crate
+pub fn resolve_workspace(
+    workspace_root: &Path,
+) -> Result<(Vec<ExternalCrate>, Vec<ResolvedCrate>)> {
+    let workspace = Workspace::load(workspace_root)?;
+    let mut crates = workspace.dependencies()?;
+    let resolved = match Lockfile::read(&workspace)? {
+        Some(lockfile) => {
+            lockfile.resolve(&workspace, &mut crates);
+            lockfile.resolved_crates(&workspace)
+        }
+        None => Vec::new(),
+    };
+    Ok((crates, resolved))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const LOCK: &str = r#"
+version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "serde 1.0.200",
+ "toml",
+]
+
+[[package]]
+name = "serde"
+version = "1.0.100"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "aaaa"
+
+[[package]]
+name = "serde"
+version = "1.0.200"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "bbbb"
+
+[[package]]
+name = "toml"
+version = "0.8.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "cccc"
+dependencies = [
+ "serde 1.0.100",
+]
+"#;
+
+    #[test]
+    fn test_resolve_against_lockfile() -> Result<()> {
+        let dir = TempDir::new()?;
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"app\"\n\n[dependencies]\nserde = \"1\"\ntoml = \"0.8\"\n",
+        )?;
+        std::fs::write(dir.path().join("Cargo.lock"), LOCK)?;
+
+        let (crates, resolved) = resolve_workspace(dir.path())?;
+
+        let serde = crates.iter().find(|c| c.name == "serde").unwrap();
+        let locked = serde.resolved.as_ref().expect("serde is locked");
+        assert_eq!(locked.version, "1.0.200");
+        assert_eq!(locked.checksum.as_deref(), Some("bbbb"));
+        assert_eq!(locked.duplicate_versions, vec!["1.0.100"]);
+        assert_eq!(locked.dependency_path, vec!["app 0.1.0", "serde 1.0.200"]);
+
+        // The older serde only arrives through toml
+        let transitive = resolved.iter().find(|c| c.version == "1.0.100").unwrap();
+        assert_eq!(
+            transitive.dependency_path,
+            vec!["app 0.1.0", "toml 0.8.0", "serde 1.0.100"]
+        );
+        assert_eq!(resolved.len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_renamed_dependencies_resolve_by_version() -> Result<()> {
+        let dir = TempDir::new()?;
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"app\"\n\n[dependencies]\nrand07 = { package = \"rand\", version = \"0.7\" }\nrand = \"0.8\"\n",
+        )?;
+        std::fs::write(
+            dir.path().join("Cargo.lock"),
+            "version = 3\n\n[[package]]\nname = \"app\"\nversion = \"0.1.0\"\ndependencies = [\n \"rand 0.7.3\",\n \"rand 0.8.5\",\n]\n\n\
+             [[package]]\nname = \"rand\"\nversion = \"0.7.3\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n\n\
+             [[package]]\nname = \"rand\"\nversion = \"0.8.5\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+        )?;
+
+        let (crates, _) = resolve_workspace(dir.path())?;
+        let locked = |name: &str| {
+            let dep = crates.iter().find(|c| c.name == name).unwrap();
+            dep.resolved.as_ref().map(|r| r.version.clone())
+        };
+        assert_eq!(locked("rand07").as_deref(), Some("0.7.3"));
+        assert_eq!(locked("rand").as_deref(), Some("0.8.5"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_lockfile_leaves_crates_unresolved() -> Result<()> {
+        let dir = TempDir::new()?;
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"app\"\n\n[dependencies]\nserde = \"1\"\n",
+        )?;
+
+        let (crates, resolved) = resolve_workspace(dir.path())?;
+        assert!(crates[0].resolved.is_none());
+        assert!(resolved.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,462 @@
+//! Cargo manifest reading for Rusty Refactor
+//!
+//! This module collects the dependencies of every package in a Cargo workspace, following
+//! `workspace = true` inheritance, `package = "..."` renames and target-specific tables.
+
+use anyhow::{anyhow, Context, Result};
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+use crate::ExternalCrate;
+
+/// Dependency tables and the kind reported for each
+const DEPENDENCY_TABLES: [(&str, &str); 5] = [
+    ("dependencies", "normal"),
+    ("dev-dependencies", "dev"),
+    ("dev_dependencies", "dev"),
+    ("build-dependencies", "build"),
+    ("build_dependencies", "build"),
+];
+
+/// A package manifest inside the workspace
+#[derive(Debug, Clone)]
+pub struct PackageManifest {
+    pub name: String,
+    pub manifest_path: PathBuf,
+    pub table: Table,
+}
+
+/// The workspace a directory belongs to
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    pub root: PathBuf,
+    pub packages: Vec<PackageManifest>,
+    /// `[workspace.dependencies]`, inherited by `workspace = true` entries
+    inherited: Table,
+}
+
+impl Workspace {
+    /// Load the workspace containing `dir`
+    ///
+    /// A package inside a larger workspace resolves to that workspace, so every member's
+    /// dependencies are reported.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let manifest_path = dir.join("Cargo.toml");
+        let manifest = read_manifest(&manifest_path)?;
+
+        let (root, root_manifest) = if manifest.contains_key("workspace") {
+            (dir.to_path_buf(), manifest)
+        } else {
+            match find_workspace_root(dir, &manifest)? {
+                Some(found) => found,
+                None => (dir.to_path_buf(), manifest),
+            }
+        };
+
+        let workspace = root_manifest.get("workspace").and_then(Value::as_table);
+        let inherited = workspace
+            .and_then(|w| w.get("dependencies"))
+            .and_then(Value::as_table)
+            .cloned()
+            .unwrap_or_default();
+
+        let mut manifest_paths = Vec::new();
+        if root_manifest.contains_key("package") {
+            manifest_paths.push(root.join("Cargo.toml"));
+        }
+        if let Some(workspace) = workspace {
+            let excluded: Vec<PathBuf> = string_array(workspace.get("exclude"))
+                .iter()
+                .map(|path| root.join(path))
+                .collect();
+            for pattern in string_array(workspace.get("members")) {
+                for member in expand_member(&root, &pattern) {
+                    let path = member.join("Cargo.toml");
+                    if path.is_file()
+                        && !excluded.contains(&member)
+                        && !manifest_paths.contains(&path)
+                    {
+                        manifest_paths.push(path);
+                    }
+                }
+            }
+        }
+
+        let mut packages = Vec::new();
+        for manifest_path in manifest_paths {
+            let table = read_manifest(&manifest_path)?;
+            let name = table
+                .get("package")
+                .and_then(|p| p.get("name"))
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            packages.push(PackageManifest {
+                name,
+                manifest_path,
+                table,
+            });
+        }
+
+        Ok(Self {
+            root,
+            packages,
+            inherited,
+        })
+    }
+
+    /// Every dependency declared by every package, in declaration order per package
+    pub fn dependencies(&self) -> Result<Vec<ExternalCrate>> {
+        let mut crates = Vec::new();
+        for package in &self.packages {
+            let mut tables: Vec<(Option<String>, &Table)> = vec![(None, &package.table)];
+            if let Some(targets) = package.table.get("target").and_then(Value::as_table) {
+                for (target, table) in targets {
+                    if let Some(table) = table.as_table() {
+                        tables.push((Some(target.clone()), table));
+                    }
+                }
+            }
+
+            for (target, table) in tables {
+                for (section, kind) in DEPENDENCY_TABLES {
+                    let Some(deps) = table.get(section).and_then(Value::as_table) else {
+                        continue;
+                    };
+                    for (key, spec) in deps {
+                        let dep = self
+                            .dependency(package, key, spec, kind, target.clone())
+                            .with_context(|| {
+                                format!("In {} [{}]", package.manifest_path.display(), section)
+                            })?;
+                        crates.push(dep);
+                    }
+                }
+            }
+        }
+        Ok(crates)
+    }
+
+    fn dependency(
+        &self,
+        package: &PackageManifest,
+        key: &str,
+        spec: &Value,
+        kind: &str,
+        target: Option<String>,
+    ) -> Result<ExternalCrate> {
+        let mut features = Vec::new();
+        let mut optional = false;
+
+        // `workspace = true` takes everything but `features` and `optional` from the workspace
+        let inherits = spec.get("workspace").and_then(Value::as_bool) == Some(true);
+        let spec = if inherits {
+            features.extend(string_array(spec.get("features")));
+            optional = spec
+                .get("optional")
+                .and_then(Value::as_bool)
+                .unwrap_or(false);
+            self.inherited.get(key).ok_or_else(|| {
+                anyhow!(
+                    "`{}` inherits a dependency missing from [workspace.dependencies]",
+                    key
+                )
+            })?
+        } else {
+            spec
+        };
+
+        let (version, source_base, package_name) = match spec {
+            Value::String(version) => (version.clone(), self.root.clone(), key.to_string()),
+            Value::Table(table) => {
+                features.extend(string_array(table.get("features")));
+                optional |= table
+                    .get("optional")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false);
+                let version = table
+                    .get("version")
+                    .and_then(Value::as_str)
+                    .unwrap_or("*")
+                    .to_string();
+                let package_name = table
+                    .get("package")
+                    .and_then(Value::as_str)
+                    .unwrap_or(key)
+                    .to_string();
+                // Inherited paths are relative to the workspace root, others to the member
+                let base = if inherits {
+                    self.root.clone()
+                } else {
+                    package
+                        .manifest_path
+                        .parent()
+                        .unwrap_or(&self.root)
+                        .to_path_buf()
+                };
+                (version, base, package_name)
+            }
+            _ => {
+                return Err(anyhow!(
+                    "`{}` has an unsupported dependency specification",
+                    key
+                ))
+            }
+        };
+        let mut seen = std::collections::HashSet::new();
+        features.retain(|feature| seen.insert(feature.clone()));
+        let default_features = ["default-features", "default_features"]
+            .iter()
+            .find_map(|key| spec.get(key).and_then(Value::as_bool))
+            .unwrap_or(true);
+
+        Ok(ExternalCrate {
+            name: key.replace('-', "_"),
+            version,
+            kind: kind.to_string(),
+            rename: (package_name != key).then(|| key.to_string()),
+            package: package_name,
+            features,
+            default_features,
+            optional,
+            source: dependency_source(spec, &source_base),
+            target,
+            member: package.name.clone(),
+            resolved: None,
+        })
+    }
+}
+
+/// Dependencies of every package in the workspace containing `workspace_root`
+pub fn workspace_dependencies(workspace_root: &Path) -> Result<Vec<ExternalCrate>> {
+    Workspace::load(workspace_root)?.dependencies()
+}
+
+// Helper functions
+
+fn read_manifest(path: &Path) -> Result<Table> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    contents
+        .parse::<Table>()
+        .with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Find the enclosing workspace of a package, via `package.workspace` or its ancestors
+fn find_workspace_root(dir: &Path, manifest: &Table) -> Result<Option<(PathBuf, Table)>> {
+    let explicit = manifest
+        .get("package")
+        .and_then(|p| p.get("workspace"))
+        .and_then(Value::as_str);
+    if let Some(relative) = explicit {
+        let root = dir.join(relative);
+        let table = read_manifest(&root.join("Cargo.toml"))?;
+        return Ok(Some((root, table)));
+    }
+
+    for ancestor in dir.ancestors().skip(1) {
+        let path = ancestor.join("Cargo.toml");
+        if !path.is_file() {
+            continue;
+        }
+        let table = read_manifest(&path)?;
+        let Some(workspace) = table.get("workspace") else {
+            continue;
+        };
+        let is_member = string_array(workspace.get("members"))
+            .iter()
+            .flat_map(|pattern| expand_member(ancestor, pattern))
+            .any(|member| member == dir);
+        if is_member {
+            return Ok(Some((ancestor.to_path_buf(), table)));
+        }
+    }
+    Ok(None)
+}
+
+/// Expand a `members` entry, where any path component may contain `*` wildcards
+fn expand_member(root: &Path, pattern: &str) -> Vec<PathBuf> {
+    let mut matches = vec![root.to_path_buf()];
+    for component in pattern.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        let mut next = Vec::new();
+        for dir in &matches {
+            if !component.contains('*') {
+                next.push(dir.join(component));
+                continue;
+            }
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                continue;
+            };
+            let mut found: Vec<PathBuf> = entries
+                .flatten()
+                .filter(|entry| entry.path().is_dir())
+                .filter(|entry| wildcard_match(component, &entry.file_name().to_string_lossy()))
+                .map(|entry| entry.path())
+                .collect();
+            found.sort();
+            next.extend(found);
+        }
+        matches = next;
+    }
+    matches
+}
+
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => {
+            name.starts_with(prefix)
+                && (0..=name.len() - prefix.len()).any(|i| {
+                    name.is_char_boundary(prefix.len() + i)
+                        && wildcard_match(rest, &name[prefix.len() + i..])
+                })
+        }
+    }
+}
+
+/// Where a dependency comes from, in Cargo's `kind+location` style
+fn dependency_source(spec: &Value, base: &Path) -> String {
+    let Some(table) = spec.as_table() else {
+        return "registry".to_string();
+    };
+    let text = |key: &str| table.get(key).and_then(Value::as_str);
+
+    if let Some(path) = text("path") {
+        return format!("path+{}", base.join(path).display());
+    }
+    if let Some(url) = text("git") {
+        let reference = ["branch", "tag", "rev"]
+            .iter()
+            .find_map(|key| text(key).map(|value| format!("?{}={}", key, value)))
+            .unwrap_or_default();
+        return format!("git+{}{}", url, reference);
+    }
+    match text("registry") {
+        Some(registry) => format!("registry+{}", registry),
+        None => "registry".to_string(),
+    }
+}
+
+fn string_array(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_array)
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::write;
+    use tempfile::TempDir;
+
+    fn sample_workspace() -> Result<TempDir> {
+        let dir = TempDir::new()?;
+        write(
+            dir.path(),
+            "Cargo.toml",
+            r#"
+[workspace]
+members = ["crates/*"]
+exclude = ["crates/ignored"]
+
+[workspace.dependencies]
+serde = { version = "1.0", features = ["derive"] }
+shared = { path = "crates/shared" }
+"#,
+        )?;
+        write(
+            dir.path(),
+            "crates/app/Cargo.toml",
+            r#"
+[package]
+name = "app"
+
+[dependencies]
+serde = { workspace = true, features = ["rc"], optional = true }
+json = { package = "serde_json", version = "1.0", default-features = false }
+shared.workspace = true
+
+[dev-dependencies]
+tempfile = "3"
+
+[build-dependencies]
+cc = { git = "https://github.com/rust-lang/cc-rs", branch = "main" }
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+"#,
+        )?;
+        write(
+            dir.path(),
+            "crates/shared/Cargo.toml",
+            "[package]\nname = \"shared\"\n\n[dependencies]\nregex-lite = \"0.1\"\n",
+        )?;
+        write(
+            dir.path(),
+            "crates/ignored/Cargo.toml",
+            "[package]\nname = \"ignored\"\n\n[dependencies]\nrand = \"0.8\"\n",
+        )?;
+        Ok(dir)
+    }
+
+    #[test]
+    fn test_workspace_dependencies() -> Result<()> {
+        let dir = sample_workspace()?;
+        let deps = workspace_dependencies(dir.path())?;
+        let find = |name: &str| deps.iter().find(|d| d.name == name).unwrap();
+
+        assert_eq!(deps.len(), 7);
+        assert!(deps.iter().all(|d| d.name != "rand"));
+
+        let serde = find("serde");
+        assert_eq!(serde.version, "1.0");
+        assert_eq!(serde.features, vec!["rc", "derive"]);
+        assert!(serde.optional);
+        assert!(serde.default_features);
+        assert_eq!(serde.source, "registry");
+
+        let json = find("json");
+        assert_eq!(json.package, "serde_json");
+        assert_eq!(json.rename.as_deref(), Some("json"));
+        assert!(!json.default_features);
+        assert!(find("regex_lite").rename.is_none());
+
+        let shared = find("shared");
+        assert!(shared.source.starts_with("path+") && shared.source.ends_with("crates/shared"));
+
+        assert_eq!(find("tempfile").kind, "dev");
+        assert_eq!(find("cc").kind, "build");
+        assert_eq!(
+            find("cc").source,
+            "git+https://github.com/rust-lang/cc-rs?branch=main"
+        );
+        assert_eq!(find("libc").target.as_deref(), Some("cfg(unix)"));
+        assert_eq!(find("regex_lite").package, "regex-lite");
+        assert_eq!(find("regex_lite").member, "shared");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_member_directory_resolves_whole_workspace() -> Result<()> {
+        let dir = sample_workspace()?;
+        let workspace = Workspace::load(&dir.path().join("crates/shared"))?;
+        assert_eq!(workspace.root, dir.path());
+        assert_eq!(workspace.packages.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*", "app"));
+        assert!(wildcard_match("rusty-*", "rusty-core"));
+        assert!(!wildcard_match("rusty-*", "core"));
+    }
+}
@@ -0,0 +1,1918 @@
+//! Module refactorings for Rusty Refactor
+//!
+//! This module moves items between module files and keeps `mod` declarations, visibility and
+//! `use` paths across the crate in sync with the items' new location.
+
+use anyhow::{anyhow, bail, Result};
+use proc_macro2::{Span, TokenStream, TokenTree};
+use std::collections::{BTreeMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use syn::{ImplItem, ImplItemFn, Item, ItemImpl, ItemUse, Type, Visibility};
+
+use crate::crate_index::{target_roots, CrateIndex, IndexedModule};
+use crate::manifest::Workspace;
+use crate::syntax::{flatten_use, item_name, SourceFile, UseLeaf};
+use crate::transaction::FileChange;
+
+/// Files and directories of a single crate: one library or binary target of a package
+#[derive(Debug, Clone)]
+pub struct CrateLayout {
+    /// Directory the crate root's `mod` declarations are relative to, usually `src`
+    pub src_dir: PathBuf,
+    /// `lib.rs`, `main.rs`, `src/bin/tool.rs` or the `[lib]`/`[[bin]]` path
+    pub root_file: PathBuf,
+    /// Modules of the target's tree and the files they are in
+    modules: Vec<IndexedModule>,
+}
+
+impl CrateLayout {
+    /// Find the target whose module tree contains a source file
+    ///
+    /// The package's library is tried first, then its binaries, following the same target
+    /// discovery as name resolution.
+    pub fn for_file(file: &Path) -> Result<Self> {
+        for dir in file.ancestors().skip(1) {
+            if !dir.join("Cargo.toml").is_file() {
+                continue;
+            }
+            let workspace = Workspace::load(dir)?;
+            let Some(package) = workspace
+                .packages
+                .iter()
+                .find(|package| package.manifest_path.parent() == Some(dir))
+            else {
+                continue;
+            };
+            for root_file in target_roots(package) {
+                let index = CrateIndex::build_all(&root_file)?;
+                let mut modules = index.modules;
+                for module in &mut modules {
+                    module.file = normalize_path(&module.file);
+                }
+                if modules.iter().any(|module| module.file == file) {
+                    return Ok(Self {
+                        src_dir: root_file.parent().unwrap_or(dir).to_path_buf(),
+                        root_file: normalize_path(&root_file),
+                        modules,
+                    });
+                }
+            }
+            bail!(
+                "{} is not part of the module tree of any target of {}",
+                file.display(),
+                package.name
+            );
+        }
+        bail!("{} is not inside a Cargo package", file.display())
+    }
+
+    /// Module path of a file, e.g. `["models", "user"]` for `src/models/user.rs`
+    ///
+    /// Files of the tree get the path they are declared at (following `#[path]`); others,
+    /// such as a module about to be created, the one their location implies.
+    pub fn module_path(&self, file: &Path) -> Result<Vec<String>> {
+        if let Some(module) = self
+            .modules
+            .iter()
+            .filter(|module| module.file == file)
+            .min_by_key(|module| module.path.len())
+        {
+            return Ok(module.path.clone());
+        }
+
+        let relative = file
+            .strip_prefix(&self.src_dir)
+            .map_err(|_| anyhow!("{} is outside {}", file.display(), self.src_dir.display()))?;
+        let mut segments: Vec<String> = relative
+            .iter()
+            .map(|part| part.to_string_lossy().to_string())
+            .collect();
+        let file_name = segments.pop().unwrap_or_default();
+        let stem = file_name
+            .strip_suffix(".rs")
+            .ok_or_else(|| anyhow!("{} is not a Rust source file", file.display()))?;
+        if stem != "mod" {
+            segments.push(stem.to_string());
+        }
+        Ok(segments)
+    }
+
+    /// Candidate files for a module: `a/b.rs` and `a/b/mod.rs`
+    pub fn module_file_candidates(&self, path: &[String]) -> [PathBuf; 2] {
+        let dir = path
+            .iter()
+            .fold(self.src_dir.clone(), |dir, part| dir.join(part));
+        [dir.with_extension("rs"), dir.join("mod.rs")]
+    }
+
+    /// Every file of the target's module tree
+    pub fn source_files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self
+            .modules
+            .iter()
+            .map(|module| module.file.clone())
+            .collect();
+        files.sort();
+        files.dedup();
+        files
+    }
+}
+
+/// Planned result of moving items into a module
+#[derive(Debug, Clone)]
+pub struct ModuleMove {
+    /// Path of the target module, e.g. `crate::models::get_functions`
+    pub module_path: String,
+    /// Names of the moved items, including impl blocks that followed their type
+    pub moved_items: Vec<String>,
+    pub changes: Vec<FileChange>,
+}
+
+/// Plan moving `item_names` out of `source_file` into the module file `target_file`
+///
+/// Items are named as they are declared (`helper`, `Counter`); a single method is named
+/// `Type::method` and is moved into a new `impl Type` block. Moving a type also moves the
+/// impl blocks for it that live in the same file.
+pub fn plan_extract_to_module(
+    source_file: &Path,
+    item_names: &[String],
+    target_file: &Path,
+) -> Result<ModuleMove> {
+    let source_file = normalize_path(source_file);
+    let target_file = normalize_path(target_file);
+    if source_file == target_file {
+        bail!("The target module must differ from the source file");
+    }
+
+    let layout = CrateLayout::for_file(&source_file)?;
+    let mut planner = Planner {
+        layout: layout.clone(),
+        pending: BTreeMap::new(),
+    };
+
+    let source = SourceFile::read(&source_file)?;
+    let source_path = layout.module_path(&source_file)?;
+    let target_path = layout.module_path(&target_file)?;
+    if target_path.is_empty() {
+        bail!("Cannot move items into the crate root");
+    }
+
+    let selection = select_items(&source, item_names)?;
+    let moved_names = selection.moved_names();
+
+    // Names that decide which imports and visibility changes are needed
+    let mut moved_idents = HashSet::new();
+    for piece in &selection.pieces {
+        moved_idents.extend(referenced_names(&piece.text(&source)));
+    }
+    let remaining_text = remove_ranges(&source.text, &selection.removed_ranges(&source));
+    let remaining_idents = referenced_names(&remaining_text);
+
+    let other_files: Vec<(PathBuf, SourceFile)> = layout
+        .source_files()
+        .into_iter()
+        .filter(|path| *path != source_file && *path != target_file)
+        .filter_map(|path| SourceFile::read(&path).ok().map(|file| (path, file)))
+        .collect();
+    let mut other_idents = HashSet::new();
+    for (_, file) in &other_files {
+        other_idents.extend(file_references(&file.ast));
+    }
+    let referenced_outside =
+        |name: &str| remaining_idents.contains(name) || other_idents.contains(name);
+
+    // Field accesses are not paths, so fields are matched against every identifier
+    let mut field_idents = HashSet::new();
+    collect_idents(&remaining_text, &mut field_idents);
+    for (_, file) in &other_files {
+        collect_idents(&file.text, &mut field_idents);
+    }
+
+    let target_is_descendant = target_path.starts_with(&source_path);
+    let mut source_edits: Vec<(Range<usize>, String)> = Vec::new();
+    let mut moved_edits: Vec<(usize, String)> = Vec::new();
+
+    // Moved items that are still used elsewhere must be visible from there
+    for piece in &selection.pieces {
+        for (name, vis, at) in piece.visibility_points(&source) {
+            if matches!(vis, Visibility::Inherited) && referenced_outside(&name) {
+                moved_edits.push((at, "pub(crate) ".to_string()));
+            }
+        }
+        if let Piece::Item(Item::Struct(item)) = piece {
+            if referenced_outside(&item.ident.to_string()) {
+                for field in &item.fields {
+                    let Some(ident) = &field.ident else { continue };
+                    if matches!(field.vis, Visibility::Inherited)
+                        && field_idents.contains(&ident.to_string())
+                    {
+                        moved_edits.push((
+                            source.byte_range(ident.span()).start,
+                            "pub(crate) ".to_string(),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    // Items left behind that the moved code uses must be imported by (and visible to) the target
+    let source_prefix = absolute(&source_path);
+    let mut target_imports: Vec<UseLeaf> = Vec::new();
+    let local_names = top_level_names(&source);
+    for item in &source.ast.items {
+        let Some(name) = item_name(item) else {
+            continue;
+        };
+        if moved_names.contains(&name)
+            || !moved_idents.contains(&name)
+            || matches!(item, Item::Use(_))
+        {
+            continue;
+        }
+        if matches!(item, Item::Macro(_)) {
+            continue;
+        }
+        target_imports.push(UseLeaf {
+            path: source_prefix
+                .iter()
+                .cloned()
+                .chain([name.clone()])
+                .collect(),
+            alias: None,
+            is_glob: false,
+        });
+        if !target_is_descendant {
+            if let Some((vis, at)) = visibility_point(&source, item) {
+                if matches!(vis, Visibility::Inherited) {
+                    source_edits.push((at..at, "pub(crate) ".to_string()));
+                }
+            }
+        }
+    }
+
+    // Imports of the source file that the moved code relies on
+    let mut pruned_uses: Vec<(&ItemUse, Vec<UseLeaf>)> = Vec::new();
+    for item in &source.ast.items {
+        let Item::Use(item_use) = item else { continue };
+        let leaves = flatten_use(&item_use.tree);
+        let mut kept = Vec::new();
+        for leaf in &leaves {
+            let name = leaf.visible_name().map(str::to_string);
+            let used_by_moved = name.as_ref().is_some_and(|n| moved_idents.contains(n));
+            if used_by_moved {
+                let mut import = leaf.clone();
+                if let Some(path) = absolute_use_path(&leaf.path, &source_path, &local_names) {
+                    import.path = path;
+                }
+                if !target_imports.contains(&import) {
+                    target_imports.push(import);
+                }
+            }
+            // Private imports only the moved code used would now be unused
+            let still_used = name.as_ref().is_none_or(|n| remaining_idents.contains(n));
+            if still_used || !used_by_moved || !matches!(item_use.vis, Visibility::Inherited) {
+                kept.push(leaf.clone());
+            }
+        }
+        if kept.len() != leaves.len() {
+            pruned_uses.push((item_use, kept));
+        }
+    }
+
+    // Rewrite the source: drop moved items and stale imports, import what stayed in use
+    for range in selection.removed_ranges(&source) {
+        source_edits.push((range, String::new()));
+    }
+    for (item_use, kept) in &pruned_uses {
+        if kept.is_empty() {
+            source_edits.push((line_range(&source, item_use.span()), String::new()));
+        } else {
+            source_edits.push((
+                source.byte_range(item_use.tree.span()),
+                render_use_tree(kept),
+            ));
+        }
+    }
+
+    // Public items stay reachable at their old path; the rest is imported where still used
+    let target_module: Vec<String> = absolute(&target_path);
+    let leaf = |name: String| UseLeaf {
+        path: target_module.iter().cloned().chain([name]).collect(),
+        alias: None,
+        is_glob: false,
+    };
+    let (public, private): (Vec<String>, Vec<String>) = selection
+        .exported_names()
+        .into_iter()
+        .partition(|name| selection.is_public(name));
+    let reexports: Vec<UseLeaf> = public.into_iter().map(leaf).collect();
+    let back_imports: Vec<UseLeaf> = private
+        .into_iter()
+        .filter(|name| remaining_idents.contains(name))
+        .map(leaf)
+        .collect();
+    let mut lines = String::new();
+    if !reexports.is_empty() {
+        lines.push_str(&format!("pub use {};\n", render_use_tree(&reexports)));
+    }
+    if !back_imports.is_empty() {
+        lines.push_str(&format!("use {};\n", render_use_tree(&back_imports)));
+    }
+    if !lines.is_empty() {
+        source_edits.push(import_insertion(&source, lines));
+    }
+
+    planner.write(&source_file, apply_edits(&source.text, source_edits));
+
+    // Rewrite the paths other files use to reach the moved items
+    let old_prefix = source_prefix.clone();
+    let mut referencing_modules = Vec::new();
+    if !back_imports.is_empty() || !reexports.is_empty() {
+        referencing_modules.push(source_path.clone());
+    }
+    for (path, file) in &other_files {
+        let module = layout.module_path(path)?;
+        let rewritten = rewrite_paths(file, &module, &old_prefix, &target_module, &moved_names);
+        if let Some(contents) = rewritten {
+            planner.write(path, contents);
+            referencing_modules.push(module);
+        } else if !file_references(&file.ast).is_disjoint(&moved_names) {
+            referencing_modules.push(module);
+        }
+    }
+
+    // Create or extend the target module
+    target_imports.retain(|leaf| !is_moved_path(&leaf.path, &old_prefix, &moved_names));
+    let items_text: Vec<String> = selection
+        .pieces
+        .iter()
+        .map(|piece| piece.render(&source, &moved_edits))
+        .collect();
+    let target_contents = match planner.read_optional(&target_file)? {
+        Some(existing) => extend_module(
+            &existing,
+            &target_path,
+            &target_imports,
+            &items_text,
+            &moved_names,
+            &old_prefix,
+        )?,
+        None => new_module(&target_imports, &items_text),
+    };
+    planner.write(&target_file, target_contents);
+
+    // Declare the module (and any missing parents) so the compiler can find it
+    let parent = &target_path[..target_path.len() - 1];
+    let any_public = selection.pieces.iter().any(|piece| piece.is_public());
+    let outside_parent = referencing_modules
+        .iter()
+        .any(|module| !module.starts_with(parent));
+    let vis = if any_public {
+        "pub "
+    } else if outside_parent {
+        "pub(crate) "
+    } else {
+        ""
+    };
+    planner.declare_module(&target_path, vis)?;
+
+    Ok(ModuleMove {
+        module_path: target_module.join("::"),
+        moved_items: selection.descriptions,
+        changes: planner.into_changes(),
+    })
+}
+
+/// How `plan_collapse_module` lays out the child modules of `foo/mod.rs`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollapseLayout {
+    /// Merge every child file into `foo.rs` as inline `mod` blocks and remove `foo/`
+    Inline,
+    /// Keep the children in `foo/` next to `foo.rs` (the 2018 non-`mod.rs` layout)
+    Flat,
+}
+
+/// Planned result of turning `foo/mod.rs` back into `foo.rs`
+#[derive(Debug, Clone)]
+pub struct ModuleCollapse {
+    pub module_file: PathBuf,
+    /// Child module files merged into `module_file`
+    pub merged_files: Vec<PathBuf>,
+    pub changes: Vec<FileChange>,
+}
+
+/// Plan converting `foo/mod.rs` into `foo.rs`, the inverse of converting a module to a folder
+///
+/// Refuses whenever the result would not be the same module tree: both `foo.rs` and
+/// `foo/mod.rs` exist, a child exists as both `child.rs` and `child/mod.rs`, a `#[path]`
+/// attribute or `include!`-style macro depends on the file's location, or (when inlining)
+/// `foo/` contains Rust files that are not part of the module tree.
+pub fn plan_collapse_module(mod_file: &Path, layout: CollapseLayout) -> Result<ModuleCollapse> {
+    let mod_file = normalize_path(mod_file);
+    if mod_file.file_name().is_none_or(|name| name != "mod.rs") {
+        bail!("{} is not a mod.rs file", mod_file.display());
+    }
+    let module_dir = mod_file
+        .parent()
+        .ok_or_else(|| anyhow!("{} has no parent directory", mod_file.display()))?
+        .to_path_buf();
+    let module_file = module_dir.with_extension("rs");
+    if module_file.exists() {
+        bail!(
+            "Both {} and {} exist; remove one before converting",
+            module_file.display(),
+            mod_file.display()
+        );
+    }
+
+    let crate_layout = CrateLayout::for_file(&mod_file)?;
+    if module_dir == crate_layout.src_dir {
+        bail!("{} is not a module of the crate", mod_file.display());
+    }
+    let module_path = crate_layout.module_path(&mod_file)?;
+    check_parent_declaration(&crate_layout, &module_path)?;
+
+    let source = SourceFile::read(&mod_file)?;
+    check_relocatable(&source, &mod_file)?;
+
+    let (contents, merged_files) = match layout {
+        CollapseLayout::Flat => (source.text.clone(), Vec::new()),
+        CollapseLayout::Inline => {
+            let mut merged = Vec::new();
+            let contents = inline_child_modules(&source, &module_dir, &mut merged)?;
+
+            let leftovers: Vec<String> = rust_files_below(&module_dir)
+                .into_iter()
+                .filter(|path| *path != mod_file && !merged.contains(path))
+                .map(|path| path.display().to_string())
+                .collect();
+            if !leftovers.is_empty() {
+                bail!(
+                    "{} contains files that are not declared as modules: {}",
+                    module_dir.display(),
+                    leftovers.join(", ")
+                );
+            }
+            (contents, merged)
+        }
+    };
+
+    let mut changes = vec![FileChange {
+        path: module_file.clone(),
+        contents: Some(contents),
+        created: true,
+    }];
+    // Deepest files first so their directories empty out before their parents
+    let mut removed: Vec<&PathBuf> = merged_files.iter().chain([&mod_file]).collect();
+    removed.sort_by_key(|path| std::cmp::Reverse(path.components().count()));
+    changes.extend(removed.into_iter().map(|path| FileChange {
+        path: path.clone(),
+        contents: None,
+        created: false,
+    }));
+
+    Ok(ModuleCollapse {
+        module_file,
+        merged_files,
+        changes,
+    })
+}
+
+/// Refuse when the parent declares the module with an explicit `#[path]`
+fn check_parent_declaration(layout: &CrateLayout, module_path: &[String]) -> Result<()> {
+    let Some((name, parent)) = module_path.split_last() else {
+        return Ok(());
+    };
+    let parent_file = if parent.is_empty() {
+        Some(layout.root_file.clone())
+    } else {
+        layout
+            .module_file_candidates(parent)
+            .into_iter()
+            .find(|path| path.is_file())
+    };
+    let Some(parent_file) = parent_file else {
+        return Ok(());
+    };
+
+    let parent = SourceFile::read(&parent_file)?;
+    let declared_with_path = parent.ast.items.iter().any(|item| {
+        matches!(item, Item::Mod(item_mod) if item_mod.ident == name && has_path_attr(&item_mod.attrs))
+    });
+    if declared_with_path {
+        bail!(
+            "`mod {}` in {} uses a #[path] attribute; update it by hand",
+            name,
+            parent_file.display()
+        );
+    }
+    Ok(())
+}
+
+/// Refuse files whose meaning depends on their location on disk
+fn check_relocatable(file: &SourceFile, path: &Path) -> Result<()> {
+    #[derive(Default)]
+    struct LocationCheck {
+        depth: usize,
+        problem: Option<String>,
+    }
+
+    impl<'ast> Visit<'ast> for LocationCheck {
+        fn visit_item_mod(&mut self, item_mod: &'ast syn::ItemMod) {
+            if has_path_attr(&item_mod.attrs) {
+                self.problem = Some(format!("`mod {}` uses a #[path] attribute", item_mod.ident));
+            } else if item_mod.content.is_none() && self.depth > 0 {
+                self.problem = Some(format!(
+                    "`mod {}` is declared inside an inline module",
+                    item_mod.ident
+                ));
+            }
+            self.depth += 1;
+            visit::visit_item_mod(self, item_mod);
+            self.depth -= 1;
+        }
+
+        fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+            let name = mac.path.segments.last().map(|s| s.ident.to_string());
+            if let Some(name @ ("include" | "include_str" | "include_bytes")) = name.as_deref() {
+                self.problem = Some(format!("`{}!` reads a path relative to the file", name));
+            }
+            visit::visit_macro(self, mac);
+        }
+    }
+
+    let mut check = LocationCheck::default();
+    check.visit_file(&file.ast);
+    match check.problem {
+        Some(problem) => bail!("Cannot move {}: {}", path.display(), problem),
+        None => Ok(()),
+    }
+}
+
+fn has_path_attr(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| attr.path().is_ident("path"))
+}
+
+/// Replace every `mod child;` in `file` with an inline block holding the child's contents
+fn inline_child_modules(
+    file: &SourceFile,
+    module_dir: &Path,
+    merged: &mut Vec<PathBuf>,
+) -> Result<String> {
+    let mut edits = Vec::new();
+    for item in &file.ast.items {
+        let Item::Mod(item_mod) = item else { continue };
+        let Some(semi) = &item_mod.semi else { continue };
+
+        let name = item_mod.ident.to_string();
+        let flat = module_dir.join(format!("{}.rs", name));
+        let nested = module_dir.join(&name).join("mod.rs");
+        let (child_path, child_dir) = match (flat.is_file(), nested.is_file()) {
+            (true, true) => bail!(
+                "Module `{}` exists as both {} and {}",
+                name,
+                flat.display(),
+                nested.display()
+            ),
+            (true, false) => (flat, module_dir.join(&name)),
+            (false, true) => (nested, module_dir.join(&name)),
+            (false, false) => bail!(
+                "No file found for `mod {}` in {}",
+                name,
+                module_dir.display()
+            ),
+        };
+
+        let child = SourceFile::read(&child_path)?;
+        check_relocatable(&child, &child_path)?;
+        let body = inline_child_modules(&child, &child_dir, merged)?;
+        merged.push(child_path);
+
+        let block = if body.trim().is_empty() {
+            " {}".to_string()
+        } else {
+            format!(" {{\n{}}}", indent_block(&body))
+        };
+        edits.push((file.byte_range(semi.span), block));
+    }
+    Ok(apply_edits(&file.text, edits))
+}
+
+/// Indent each line by one level, unless a multi-line literal would change meaning
+fn indent_block(text: &str) -> String {
+    fn has_multiline_literal(tokens: TokenStream) -> bool {
+        tokens.into_iter().any(|token| match token {
+            TokenTree::Literal(literal) => literal.to_string().contains('\n'),
+            TokenTree::Group(group) => has_multiline_literal(group.stream()),
+            _ => false,
+        })
+    }
+
+    let text = text.trim_end();
+    let multiline = text
+        .parse::<TokenStream>()
+        .map_or(true, has_multiline_literal);
+    let mut out = String::new();
+    for line in text.lines() {
+        if !multiline && !line.trim().is_empty() {
+            out.push_str("    ");
+        }
+        out.push_str(if multiline { line } else { line.trim_end() });
+        out.push('\n');
+    }
+    out
+}
+
+/// Pending file contents, layered over the files on disk
+struct Planner {
+    layout: CrateLayout,
+    pending: BTreeMap<PathBuf, String>,
+}
+
+impl Planner {
+    fn read_optional(&self, path: &Path) -> Result<Option<String>> {
+        if let Some(contents) = self.pending.get(path) {
+            return Ok(Some(contents.clone()));
+        }
+        if path.is_file() {
+            return Ok(Some(std::fs::read_to_string(path)?));
+        }
+        Ok(None)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.pending.contains_key(path) || path.is_file()
+    }
+
+    fn write(&mut self, path: &Path, contents: String) {
+        self.pending.insert(path.to_path_buf(), contents);
+    }
+
+    fn module_file(&self, path: &[String]) -> Option<PathBuf> {
+        if path.is_empty() {
+            return Some(self.layout.root_file.clone());
+        }
+        self.layout
+            .module_file_candidates(path)
+            .into_iter()
+            .find(|candidate| self.exists(candidate))
+    }
+
+    /// Make sure `mod name;` for `path` exists in its parent, creating parents as needed
+    fn declare_module(&mut self, path: &[String], vis: &str) -> Result<()> {
+        let Some((name, parent)) = path.split_last() else {
+            return Ok(());
+        };
+
+        let parent_file = match self.module_file(parent) {
+            Some(file) => file,
+            None => {
+                let [_, nested] = self.layout.module_file_candidates(parent);
+                self.write(&nested, String::new());
+                self.declare_module(parent, vis)?;
+                nested
+            }
+        };
+
+        let contents = self.read_optional(&parent_file)?.unwrap_or_default();
+        let file = SourceFile::parse(contents.clone())?;
+        let existing = file.ast.items.iter().find_map(|item| match item {
+            Item::Mod(item_mod) if item_mod.ident == name => Some(item_mod),
+            _ => None,
+        });
+
+        let edit = match existing {
+            Some(item_mod) if matches!(item_mod.vis, Visibility::Inherited) && !vis.is_empty() => {
+                let at = file.byte_range(item_mod.mod_token.span).start;
+                (at..at, vis.to_string())
+            }
+            Some(_) => return Ok(()),
+            None => module_insertion(&file, format!("{}mod {};\n", vis, name)),
+        };
+        self.write(&parent_file, apply_edits(&contents, vec![edit]));
+        Ok(())
+    }
+
+    fn into_changes(self) -> Vec<FileChange> {
+        self.pending
+            .into_iter()
+            .map(|(path, contents)| FileChange {
+                created: !path.is_file(),
+                path,
+                contents: Some(contents),
+            })
+            .collect()
+    }
+}
+
+/// One contiguous piece of source moved to the target
+enum Piece<'a> {
+    Item(&'a Item),
+    /// Methods taken out of an impl block; `whole` when no items remain behind
+    Methods {
+        item_impl: &'a ItemImpl,
+        methods: Vec<&'a ImplItemFn>,
+        whole: bool,
+    },
+}
+
+impl Piece<'_> {
+    fn text(&self, source: &SourceFile) -> String {
+        self.render(source, &[])
+    }
+
+    /// Source text of the piece with visibility insertions applied
+    fn render(&self, source: &SourceFile, edits: &[(usize, String)]) -> String {
+        let slice = |range: Range<usize>| {
+            let local: Vec<(Range<usize>, String)> = edits
+                .iter()
+                .filter(|(at, _)| range.contains(at))
+                .map(|(at, text)| (*at - range.start..*at - range.start, text.clone()))
+                .collect();
+            apply_edits(&source.text[range], local)
+                .trim_end()
+                .to_string()
+        };
+
+        match self {
+            Piece::Item(item) => slice(line_range(source, item.span())),
+            Piece::Methods {
+                item_impl, methods, ..
+            } => {
+                let header = source.impl_context(item_impl).header;
+                let body: Vec<String> = methods
+                    .iter()
+                    .map(|method| slice(line_range(source, method.span())))
+                    .collect();
+                format!("{} {{\n{}\n}}", header, body.join("\n\n"))
+            }
+        }
+    }
+
+    /// Every visibility that may need widening: (name, visibility, insertion offset)
+    fn visibility_points(&self, source: &SourceFile) -> Vec<(String, Visibility, usize)> {
+        match self {
+            Piece::Item(Item::Impl(item_impl)) => impl_methods(item_impl)
+                .map(|method| method_visibility_point(source, method))
+                .collect(),
+            Piece::Item(item) => match (item_name(item), visibility_point(source, item)) {
+                (Some(name), Some((vis, at))) => vec![(name, vis, at)],
+                _ => vec![],
+            },
+            Piece::Methods { methods, .. } => methods
+                .iter()
+                .map(|method| method_visibility_point(source, method))
+                .collect(),
+        }
+    }
+
+    fn is_public(&self) -> bool {
+        match self {
+            Piece::Item(item) => matches!(item_visibility(item), Some(Visibility::Public(_))),
+            Piece::Methods { methods, .. } => methods
+                .iter()
+                .any(|m| matches!(m.vis, Visibility::Public(_))),
+        }
+    }
+}
+
+fn impl_methods(item_impl: &ItemImpl) -> impl Iterator<Item = &ImplItemFn> {
+    item_impl.items.iter().filter_map(|item| match item {
+        ImplItem::Fn(method) => Some(method),
+        _ => None,
+    })
+}
+
+fn method_visibility_point(
+    source: &SourceFile,
+    method: &ImplItemFn,
+) -> (String, Visibility, usize) {
+    (
+        method.sig.ident.to_string(),
+        method.vis.clone(),
+        source.byte_range(method.sig.span()).start,
+    )
+}
+
+/// The items selected for a move
+struct ItemSelection<'a> {
+    pieces: Vec<Piece<'a>>,
+    descriptions: Vec<String>,
+}
+
+impl ItemSelection<'_> {
+    /// Names of moved top-level items
+    fn moved_names(&self) -> HashSet<String> {
+        self.pieces
+            .iter()
+            .filter_map(|piece| match piece {
+                Piece::Item(item) => item_name(item),
+                Piece::Methods { .. } => None,
+            })
+            .collect()
+    }
+
+    /// Whether the moved top-level item `name` is declared `pub`
+    fn is_public(&self, name: &str) -> bool {
+        self.pieces.iter().any(|piece| match piece {
+            Piece::Item(item) => {
+                item_name(item).as_deref() == Some(name) && piece.is_public()
+            }
+            Piece::Methods { .. } => false,
+        })
+    }
+
+    /// Moved items that other code may import by name
+    fn exported_names(&self) -> Vec<String> {
+        self.pieces
+            .iter()
+            .filter_map(|piece| match piece {
+                Piece::Item(Item::Macro(_)) => None,
+                Piece::Item(item) => item_name(item),
+                Piece::Methods { .. } => None,
+            })
+            .collect()
+    }
+
+    fn removed_ranges(&self, source: &SourceFile) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Item(item) => ranges.push(removal_range(source, item.span())),
+                Piece::Methods {
+                    item_impl,
+                    whole: true,
+                    ..
+                } => ranges.push(removal_range(source, item_impl.span())),
+                Piece::Methods { methods, .. } => {
+                    ranges.extend(methods.iter().map(|m| removal_range(source, m.span())))
+                }
+            }
+        }
+        ranges
+    }
+}
+
+fn select_items<'a>(source: &'a SourceFile, names: &[String]) -> Result<ItemSelection<'a>> {
+    let mut item_indices: Vec<usize> = Vec::new();
+    let mut methods: Vec<(usize, &'a ImplItemFn)> = Vec::new();
+
+    for name in names {
+        if let Some((type_name, method_name)) = name.split_once("::") {
+            let found = source
+                .ast
+                .items
+                .iter()
+                .enumerate()
+                .find_map(|(i, item)| match item {
+                    Item::Impl(item_impl)
+                        if item_impl.trait_.is_none()
+                            && impl_type_name(item_impl).as_deref() == Some(type_name) =>
+                    {
+                        impl_methods(item_impl)
+                            .find(|method| method.sig.ident == method_name)
+                            .map(|method| (i, method))
+                    }
+                    _ => None,
+                });
+            methods.push(found.ok_or_else(|| {
+                anyhow!(
+                    "No method `{}` found in an `impl {}` block",
+                    method_name,
+                    type_name
+                )
+            })?);
+            continue;
+        }
+
+        let index = source
+            .ast
+            .items
+            .iter()
+            .position(|item| {
+                item_name(item).as_deref() == Some(name.as_str()) && !matches!(item, Item::Use(_))
+            })
+            .ok_or_else(|| anyhow!("No item named `{}` found", name))?;
+        if !item_indices.contains(&index) {
+            item_indices.push(index);
+        }
+
+        // Impl blocks travel with their type
+        if matches!(
+            source.ast.items[index],
+            Item::Struct(_) | Item::Enum(_) | Item::Union(_)
+        ) {
+            for (i, item) in source.ast.items.iter().enumerate() {
+                if let Item::Impl(item_impl) = item {
+                    if impl_type_name(item_impl).as_deref() == Some(name.as_str())
+                        && !item_indices.contains(&i)
+                    {
+                        item_indices.push(i);
+                    }
+                }
+            }
+        }
+    }
+
+    item_indices.sort();
+    let mut pieces: Vec<Piece> = item_indices
+        .iter()
+        .map(|&i| Piece::Item(&source.ast.items[i]))
+        .collect();
+    let mut descriptions: Vec<String> = item_indices
+        .iter()
+        .map(|&i| match &source.ast.items[i] {
+            Item::Impl(item_impl) => source.impl_context(item_impl).header,
+            item => item_name(item).unwrap_or_default(),
+        })
+        .collect();
+
+    // Methods whose impl block already moves with its type need no separate handling
+    methods.retain(|(i, _)| !item_indices.contains(i));
+    methods.sort_by_key(|(i, method)| (*i, source.byte_range(method.span()).start));
+    methods.dedup_by_key(|(_, method)| source.byte_range(method.span()).start);
+
+    let mut start = 0;
+    while start < methods.len() {
+        let index = methods[start].0;
+        let group: Vec<&ImplItemFn> = methods[start..]
+            .iter()
+            .take_while(|(i, _)| *i == index)
+            .map(|(_, method)| *method)
+            .collect();
+        start += group.len();
+
+        let Item::Impl(item_impl) = &source.ast.items[index] else {
+            continue;
+        };
+        descriptions.extend(group.iter().map(|m| {
+            format!(
+                "{}::{}",
+                impl_type_name(item_impl).unwrap_or_default(),
+                m.sig.ident
+            )
+        }));
+        pieces.push(Piece::Methods {
+            item_impl,
+            whole: group.len() == item_impl.items.len(),
+            methods: group,
+        });
+    }
+
+    Ok(ItemSelection {
+        pieces,
+        descriptions,
+    })
+}
+
+/// Name of the type an impl block is for, e.g. `Wrapper` for `impl<T> Wrapper<T>`
+fn impl_type_name(item_impl: &ItemImpl) -> Option<String> {
+    match item_impl.self_ty.as_ref() {
+        Type::Path(path) => path.path.segments.last().map(|s| s.ident.to_string()),
+        _ => None,
+    }
+}
+
+fn item_visibility(item: &Item) -> Option<&Visibility> {
+    match item {
+        Item::Fn(item) => Some(&item.vis),
+        Item::Struct(item) => Some(&item.vis),
+        Item::Enum(item) => Some(&item.vis),
+        Item::Union(item) => Some(&item.vis),
+        Item::Trait(item) => Some(&item.vis),
+        Item::Type(item) => Some(&item.vis),
+        Item::Const(item) => Some(&item.vis),
+        Item::Static(item) => Some(&item.vis),
+        Item::Mod(item) => Some(&item.vis),
+        _ => None,
+    }
+}
+
+/// An item's visibility and the offset where a visibility keyword would be inserted
+fn visibility_point(source: &SourceFile, item: &Item) -> Option<(Visibility, usize)> {
+    let first: Span = match item {
+        Item::Fn(item) => item.sig.span(),
+        Item::Struct(item) => item.struct_token.span,
+        Item::Enum(item) => item.enum_token.span,
+        Item::Union(item) => item.union_token.span,
+        Item::Trait(item) => item
+            .unsafety
+            .map(|u| u.span)
+            .or(item.auto_token.map(|a| a.span))
+            .unwrap_or(item.trait_token.span),
+        Item::Type(item) => item.type_token.span,
+        Item::Const(item) => item.const_token.span,
+        Item::Static(item) => item.static_token.span,
+        Item::Mod(item) => item.unsafety.map(|u| u.span).unwrap_or(item.mod_token.span),
+        _ => return None,
+    };
+    Some((
+        item_visibility(item)?.clone(),
+        source.byte_range(first).start,
+    ))
+}
+
+/// Edit setting the visibility of the item `name`, declared in the inline modules
+/// `inline_path` of a file, to `visibility` (e.g. `pub(crate)`)
+pub(crate) fn visibility_edit(
+    file: &SourceFile,
+    inline_path: &[String],
+    name: &str,
+    visibility: &str,
+) -> Result<(Range<usize>, String)> {
+    let mut items = &file.ast.items;
+    for segment in inline_path {
+        items = items
+            .iter()
+            .find_map(|item| match item {
+                Item::Mod(item_mod) if item_mod.ident == segment => {
+                    item_mod.content.as_ref().map(|(_, items)| items)
+                }
+                _ => None,
+            })
+            .ok_or_else(|| anyhow!("No inline module `{}`", segment))?;
+    }
+    let (vis, at) = items
+        .iter()
+        .filter(|item| item_name(item).as_deref() == Some(name))
+        .find_map(|item| visibility_point(file, item))
+        .ok_or_else(|| anyhow!("No item `{}` with a visibility to change", name))?;
+    Ok(match vis {
+        Visibility::Inherited => (at..at, format!("{} ", visibility)),
+        vis => (file.byte_range(vis.span()), visibility.to_string()),
+    })
+}
+
+/// Names of every top-level item in a file, including modules
+pub(crate) fn top_level_names(file: &SourceFile) -> HashSet<String> {
+    file.ast.items.iter().filter_map(item_name).collect()
+}
+
+/// `crate::a::b` segments for a module path
+fn absolute(module: &[String]) -> Vec<String> {
+    std::iter::once("crate".to_string())
+        .chain(module.iter().cloned())
+        .collect()
+}
+
+/// Resolve a `use` path written in `module` to an absolute `crate::` path
+///
+/// Returns `None` for paths into other crates, which are written the same everywhere.
+pub fn absolute_use_path(
+    path: &[String],
+    module: &[String],
+    local_names: &HashSet<String>,
+) -> Option<Vec<String>> {
+    let first = path.first()?;
+    match first.as_str() {
+        "crate" => Some(path.to_vec()),
+        "self" => Some(
+            absolute(module)
+                .into_iter()
+                .chain(path[1..].iter().cloned())
+                .collect(),
+        ),
+        "super" => {
+            let supers = path.iter().take_while(|s| *s == "super").count();
+            let base = module.get(..module.len().checked_sub(supers)?)?;
+            Some(
+                absolute(base)
+                    .into_iter()
+                    .chain(path[supers..].iter().cloned())
+                    .collect(),
+            )
+        }
+        name if local_names.contains(name) => Some(
+            absolute(module)
+                .into_iter()
+                .chain(path.iter().cloned())
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Rewrite `use` declarations and qualified paths that point at moved items
+fn rewrite_paths(
+    file: &SourceFile,
+    module: &[String],
+    old_prefix: &[String],
+    new_prefix: &[String],
+    moved: &HashSet<String>,
+) -> Option<String> {
+    let local_names = top_level_names(file);
+    let mut edits = Vec::new();
+
+    for item in &file.ast.items {
+        let Item::Use(item_use) = item else { continue };
+        let mut changed = false;
+        let leaves: Vec<UseLeaf> = flatten_use(&item_use.tree)
+            .into_iter()
+            .map(|mut leaf| {
+                let Some(path) = absolute_use_path(&leaf.path, module, &local_names) else {
+                    return leaf;
+                };
+                if !leaf.is_glob && is_moved_path(&path, old_prefix, moved) {
+                    leaf.path = new_prefix
+                        .iter()
+                        .cloned()
+                        .chain([path[old_prefix.len()].clone()])
+                        .collect();
+                    changed = true;
+                }
+                leaf
+            })
+            .collect();
+        if changed {
+            edits.push((
+                file.byte_range(item_use.tree.span()),
+                render_use_tree(&leaves),
+            ));
+        }
+    }
+
+    // Qualified paths in code and macro bodies; literals and comments are left alone
+    let mut rewriter = PathRewriter {
+        file,
+        old_prefix,
+        new_prefix: format!("{}::", new_prefix.join("::")),
+        moved,
+        edits,
+    };
+    rewriter.visit_file(&file.ast);
+
+    let contents = apply_edits(&file.text, rewriter.edits);
+    (contents != file.text).then_some(contents)
+}
+
+/// Whether an absolute path names one of the moved items at its old location
+fn is_moved_path(path: &[String], old_prefix: &[String], moved: &HashSet<String>) -> bool {
+    path.len() == old_prefix.len() + 1
+        && path.starts_with(old_prefix)
+        && moved.contains(&path[old_prefix.len()])
+}
+
+/// Visitor moving paths that start with `old_prefix::<moved item>` under `new_prefix`
+struct PathRewriter<'a> {
+    file: &'a SourceFile,
+    old_prefix: &'a [String],
+    /// New prefix with its trailing `::`
+    new_prefix: String,
+    moved: &'a HashSet<String>,
+    edits: Vec<(Range<usize>, String)>,
+}
+
+impl PathRewriter<'_> {
+    /// Replace the old prefix of a path whose leading identifiers are `segments`
+    fn rewrite(&mut self, segments: &[(String, Span)]) {
+        let names: Vec<String> = segments
+            .iter()
+            .take(self.old_prefix.len() + 1)
+            .map(|(name, _)| name.clone())
+            .collect();
+        if is_moved_path(&names, self.old_prefix, self.moved) {
+            let start = self.file.byte_range(segments[0].1).start;
+            let end = self.file.byte_range(segments[self.old_prefix.len()].1).start;
+            self.edits.push((start..end, self.new_prefix.clone()));
+        }
+    }
+
+    /// Paths in a macro body, which are token sequences like `crate :: a :: b`
+    fn rewrite_tokens(&mut self, tokens: TokenStream) {
+        let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+        for (i, token) in tokens.iter().enumerate() {
+            match token {
+                TokenTree::Group(group) => self.rewrite_tokens(group.stream()),
+                TokenTree::Ident(_) if i < 2 || !is_path_separator(&tokens[i - 2..i]) => {
+                    let mut segments = Vec::new();
+                    let mut at = i;
+                    while let Some(TokenTree::Ident(ident)) = tokens.get(at) {
+                        segments.push((ident.to_string(), ident.span()));
+                        if !tokens.get(at + 1..at + 3).is_some_and(is_path_separator) {
+                            break;
+                        }
+                        at += 3;
+                    }
+                    self.rewrite(&segments);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Whether two tokens are the `::` path separator
+fn is_path_separator(tokens: &[TokenTree]) -> bool {
+    matches!(
+        tokens,
+        [TokenTree::Punct(first), TokenTree::Punct(second)]
+            if first.as_char() == ':' && second.as_char() == ':'
+    )
+}
+
+impl<'ast> Visit<'ast> for PathRewriter<'_> {
+    fn visit_path(&mut self, path: &'ast syn::Path) {
+        if path.leading_colon.is_none() {
+            let segments: Vec<(String, Span)> = path
+                .segments
+                .iter()
+                .map(|segment| (segment.ident.to_string(), segment.ident.span()))
+                .collect();
+            self.rewrite(&segments);
+        }
+        visit::visit_path(self, path);
+    }
+
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        self.rewrite_tokens(mac.tokens.clone());
+        visit::visit_macro(self, mac);
+    }
+
+    fn visit_item_use(&mut self, _: &'ast ItemUse) {
+        // `use` trees are rewritten leaf by leaf
+    }
+}
+
+/// Render leaves as a single use tree, grouping siblings: `a::{b, c}`
+pub fn render_use_tree(leaves: &[UseLeaf]) -> String {
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    for leaf in leaves {
+        let (parent, last) = match (leaf.is_glob, leaf.path.split_last()) {
+            (true, _) => (leaf.path.join("::"), "*".to_string()),
+            (false, Some((last, parent))) => (
+                parent.join("::"),
+                match &leaf.alias {
+                    Some(alias) => format!("{} as {}", last, alias),
+                    None => last.clone(),
+                },
+            ),
+            (false, None) => continue,
+        };
+        match groups.iter_mut().find(|(p, _)| *p == parent) {
+            Some((_, names)) if !names.contains(&last) => names.push(last),
+            Some(_) => {}
+            None => groups.push((parent, vec![last])),
+        }
+    }
+
+    let rendered: Vec<String> = groups
+        .into_iter()
+        .map(|(parent, mut names)| {
+            names.sort_by(|a, b| {
+                let self_first = |s: &str| s != "self";
+                (self_first(a), a.to_lowercase()).cmp(&(self_first(b), b.to_lowercase()))
+            });
+            let tail = if names.len() == 1 {
+                names.remove(0)
+            } else {
+                format!("{{{}}}", names.join(", "))
+            };
+            if parent.is_empty() {
+                tail
+            } else {
+                format!("{}::{}", parent, tail)
+            }
+        })
+        .collect();
+
+    if rendered.len() == 1 {
+        rendered.into_iter().next().unwrap_or_default()
+    } else {
+        format!("{{{}}}", rendered.join(", "))
+    }
+}
+
+/// Render one `use` line per top-level root (`crate`, `std`, ...)
+fn render_imports(imports: &[UseLeaf]) -> String {
+    let mut roots: Vec<(String, Vec<UseLeaf>)> = Vec::new();
+    for import in imports {
+        let root = import.path.first().cloned().unwrap_or_default();
+        match roots.iter_mut().find(|(r, _)| *r == root) {
+            Some((_, leaves)) => leaves.push(import.clone()),
+            None => roots.push((root, vec![import.clone()])),
+        }
+    }
+
+    // std, then external crates, then this crate
+    roots.sort_by_key(|(root, _)| match root.as_str() {
+        "std" | "core" | "alloc" => (0, root.clone()),
+        "crate" | "self" | "super" => (2, root.clone()),
+        _ => (1, root.clone()),
+    });
+    roots
+        .iter()
+        .map(|(_, leaves)| format!("use {};\n", render_use_tree(leaves)))
+        .collect()
+}
+
+fn new_module(imports: &[UseLeaf], items: &[String]) -> String {
+    let mut contents = String::new();
+    if !imports.is_empty() {
+        contents.push_str(&render_imports(imports));
+        contents.push('\n');
+    }
+    contents.push_str(&items.join("\n\n"));
+    contents.push('\n');
+    contents
+}
+
+fn extend_module(
+    existing: &str,
+    module: &[String],
+    imports: &[UseLeaf],
+    items: &[String],
+    moved: &HashSet<String>,
+    old_prefix: &[String],
+) -> Result<String> {
+    let file = SourceFile::parse(existing.to_string())?;
+    let defined = top_level_names(&file);
+    if let Some(conflict) = moved.iter().find(|name| defined.contains(*name)) {
+        bail!("The target module already defines `{}`", conflict);
+    }
+
+    // Drop imports of the moved items themselves and imports the module already has
+    let mut existing_leaves = Vec::new();
+    let mut edits = Vec::new();
+    for item in &file.ast.items {
+        let Item::Use(item_use) = item else { continue };
+        let leaves = flatten_use(&item_use.tree);
+        let resolved: Vec<UseLeaf> = leaves
+            .iter()
+            .map(|leaf| UseLeaf {
+                path: absolute_use_path(&leaf.path, module, &defined)
+                    .unwrap_or_else(|| leaf.path.clone()),
+                ..leaf.clone()
+            })
+            .collect();
+        let kept: Vec<UseLeaf> = leaves
+            .iter()
+            .zip(&resolved)
+            .filter(|(_, leaf)| leaf.is_glob || !is_moved_path(&leaf.path, old_prefix, moved))
+            .map(|(leaf, _)| leaf.clone())
+            .collect();
+        existing_leaves.extend(resolved);
+        if kept.is_empty() {
+            edits.push((line_range(&file, item_use.span()), String::new()));
+        } else if kept.len() != leaves.len() {
+            edits.push((
+                file.byte_range(item_use.tree.span()),
+                render_use_tree(&kept),
+            ));
+        }
+    }
+
+    let missing: Vec<UseLeaf> = imports
+        .iter()
+        .filter(|import| !existing_leaves.contains(import))
+        .cloned()
+        .collect();
+    if !missing.is_empty() {
+        edits.push(import_insertion(&file, render_imports(&missing)));
+    }
+
+    let mut contents = apply_edits(existing, edits);
+    let trimmed = contents.trim_end().len();
+    contents.truncate(trimmed);
+    if !contents.is_empty() {
+        contents.push_str("\n\n");
+    }
+    contents.push_str(&items.join("\n\n"));
+    contents.push('\n');
+    Ok(contents)
+}
+
+/// Where a new `use` line goes: after the last top-level `use`, or after the file's header
+fn import_insertion(file: &SourceFile, line: String) -> (Range<usize>, String) {
+    let last_use = file
+        .ast
+        .items
+        .iter()
+        .rev()
+        .find(|item| matches!(item, Item::Use(_)));
+    match last_use {
+        Some(item) => {
+            let at = line_range(file, item.span()).end;
+            (at..at, line)
+        }
+        None => header_insertion(file, line),
+    }
+}
+
+/// Where a new `mod` declaration goes: after the last declaration, else after the imports
+fn module_insertion(file: &SourceFile, line: String) -> (Range<usize>, String) {
+    let last_decl = file
+        .ast
+        .items
+        .iter()
+        .rev()
+        .find(|item| matches!(item, Item::Mod(item_mod) if item_mod.content.is_none()));
+    match last_decl {
+        Some(item) => {
+            let at = line_range(file, item.span()).end;
+            (at..at, line)
+        }
+        None => {
+            let (range, line) = import_insertion(file, line);
+            let separated = if file
+                .ast
+                .items
+                .iter()
+                .any(|item| matches!(item, Item::Use(_)))
+            {
+                format!("\n{}", line)
+            } else {
+                line
+            };
+            (range, separated)
+        }
+    }
+}
+
+/// Insert after inner attributes and `//!` docs, separated from them by a blank line
+pub(crate) fn header_insertion(file: &SourceFile, line: String) -> (Range<usize>, String) {
+    let mut at = file
+        .ast
+        .attrs
+        .last()
+        .map_or(0, |attr| line_range(file, attr.span()).end);
+    for line_text in file.text[at..].split_inclusive('\n') {
+        if !line_text.trim_start().starts_with("//!") {
+            break;
+        }
+        at += line_text.len();
+    }
+
+    if at > 0 {
+        (at..at, format!("\n{}", line))
+    } else if file.text.trim().is_empty() {
+        (0..file.text.len(), line)
+    } else {
+        (0..0, format!("{}\n", line))
+    }
+}
+
+/// Byte range of the full lines a span covers, including preceding `//` comment lines
+pub(crate) fn line_range(file: &SourceFile, span: Span) -> Range<usize> {
+    let range = file.byte_range(span);
+    let text = &file.text;
+    let mut start = text[..range.start].rfind('\n').map_or(0, |i| i + 1);
+    while start > 0 {
+        let previous = text[..start - 1].rfind('\n').map_or(0, |i| i + 1);
+        let line = text[previous..start].trim();
+        if line.starts_with("//") && !line.starts_with("//!") {
+            start = previous;
+        } else {
+            break;
+        }
+    }
+    let end = text[range.end..]
+        .find('\n')
+        .map_or(text.len(), |i| range.end + i + 1);
+    start..end
+}
+
+/// Like `line_range`, also swallowing one blank line so no double gap is left behind
+fn removal_range(file: &SourceFile, span: Span) -> Range<usize> {
+    let range = line_range(file, span);
+    let text = &file.text;
+    let blank_before = range.start == 0 || text[..range.start].ends_with("\n\n");
+    let next_line_end = text[range.end..].find('\n').map(|i| range.end + i + 1);
+    let next_line = next_line_end.map_or("", |end| &text[range.end..end]);
+    match next_line_end {
+        Some(end) if blank_before && next_line.trim().is_empty() => range.start..end,
+        // The last item of a block: drop the gap above it rather than before the brace
+        _ if blank_before
+            && range.start > 0
+            && (next_line.trim_start().starts_with('}') || range.end == text.len()) =>
+        {
+            range.start - 1..range.end
+        }
+        _ => range,
+    }
+}
+
+fn remove_ranges(text: &str, ranges: &[Range<usize>]) -> String {
+    apply_edits(
+        text,
+        ranges.iter().map(|r| (r.clone(), String::new())).collect(),
+    )
+}
+
+/// Apply non-overlapping replacements, back to front
+pub fn apply_edits(text: &str, mut edits: Vec<(Range<usize>, String)>) -> String {
+    edits.sort_by_key(|(range, _)| std::cmp::Reverse((range.start, range.end)));
+    let mut out = text.to_string();
+    let mut limit = usize::MAX;
+    for (range, replacement) in edits {
+        if range.end > limit {
+            continue;
+        }
+        out.replace_range(range.clone(), &replacement);
+        limit = range.start;
+    }
+    out
+}
+
+/// Names used as paths (or inside macros), ignoring `use` declarations
+fn file_references(file: &syn::File) -> HashSet<String> {
+    struct PathCollector(HashSet<String>);
+
+    impl<'ast> Visit<'ast> for PathCollector {
+        fn visit_path(&mut self, path: &'ast syn::Path) {
+            self.0
+                .extend(path.segments.iter().map(|s| s.ident.to_string()));
+            visit::visit_path(self, path);
+        }
+
+        fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+            collect_idents(&mac.tokens.to_string(), &mut self.0);
+            visit::visit_macro(self, mac);
+        }
+
+        fn visit_item_use(&mut self, _: &'ast ItemUse) {}
+    }
+
+    let mut collector = PathCollector(HashSet::new());
+    collector.visit_file(file);
+    collector.0
+}
+
+fn referenced_names(text: &str) -> HashSet<String> {
+    match syn::parse_file(text) {
+        Ok(file) => file_references(&file),
+        Err(_) => {
+            let mut idents = HashSet::new();
+            collect_idents(text, &mut idents);
+            idents
+        }
+    }
+}
+
+fn collect_idents(text: &str, idents: &mut HashSet<String>) {
+    fn walk(tokens: TokenStream, idents: &mut HashSet<String>) {
+        for token in tokens {
+            match token {
+                TokenTree::Ident(ident) => {
+                    idents.insert(ident.to_string());
+                }
+                TokenTree::Group(group) => walk(group.stream(), idents),
+                _ => {}
+            }
+        }
+    }
+
+    if let Ok(tokens) = text.parse::<TokenStream>() {
+        walk(tokens, idents);
+    }
+}
+
+/// Every `.rs` file below `dir`, whether or not a module tree includes it
+fn rust_files_below(dir: &Path) -> Vec<PathBuf> {
+    fn walk(dir: &Path, out: &mut Vec<PathBuf>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                walk(&path, out);
+            } else if path.extension().is_some_and(|ext| ext == "rs") {
+                out.push(path);
+            }
+        }
+    }
+
+    let mut files = Vec::new();
+    walk(dir, &mut files);
+    files.sort();
+    files
+}
+
+/// Make a path absolute and resolve `.`/`..` and symlinks as far as the path exists
+fn normalize_path(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap_or_default().join(path)
+    };
+    let mut existing = absolute.as_path();
+    let mut rest = Vec::new();
+    while !existing.exists() {
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return absolute,
+        }
+    }
+    let mut normalized = std::fs::canonicalize(existing).unwrap_or_else(|_| existing.to_path_buf());
+    for part in rest.into_iter().rev() {
+        normalized.push(part);
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::Transaction;
+    use tempfile::TempDir;
+
+    const LIB: &str = r#"use std::collections::HashMap;
+
+pub mod models;
+mod report;
+
+pub struct FunctionInfo {
+    pub name: String,
+}
+
+pub struct AnalysisState {
+    pub functions: HashMap<String, FunctionInfo>,
+}
+
+impl AnalysisState {
+    pub fn new() -> Self {
+        Self { functions: HashMap::new() }
+    }
+
+    pub fn get_functions(&self) -> Vec<&FunctionInfo> {
+        self.functions.values().collect()
+    }
+}
+
+// Counts functions
+fn helper(state: &AnalysisState) -> usize {
+    state.functions.len()
+}
+
+struct Counter {
+    count: usize,
+}
+
+pub fn count(state: &AnalysisState) -> usize {
+    let counter = Counter { count: helper(state) };
+    counter.count
+}
+"#;
+
+    const REPORT: &str = r#"use crate::{count, AnalysisState};
+
+pub fn report(state: &AnalysisState) -> usize {
+    crate::helper(state) + count(state)
+}
+"#;
+
+    fn sample_crate() -> Result<TempDir> {
+        let dir = TempDir::new()?;
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"sample\"\n",
+        )?;
+        std::fs::create_dir_all(dir.path().join("src/models"))?;
+        std::fs::write(dir.path().join("src/lib.rs"), LIB)?;
+        std::fs::write(dir.path().join("src/report.rs"), REPORT)?;
+        std::fs::write(dir.path().join("src/models/mod.rs"), "")?;
+        Ok(dir)
+    }
+
+    fn change<'a>(changes: &'a [FileChange], dir: &TempDir, file: &str) -> &'a FileChange {
+        let path = normalize_path(&dir.path().join(file));
+        changes
+            .iter()
+            .find(|c| c.path == path)
+            .unwrap_or_else(|| panic!("no change for {}", file))
+    }
+
+    fn contents<'a>(changes: &'a [FileChange], dir: &TempDir, file: &str) -> &'a str {
+        change(changes, dir, file)
+            .contents
+            .as_deref()
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_move_method_into_new_nested_module() -> Result<()> {
+        let dir = sample_crate()?;
+        let plan = plan_extract_to_module(
+            &dir.path().join("src/lib.rs"),
+            &["AnalysisState::get_functions".to_string()],
+            &dir.path().join("src/models/get_functions.rs"),
+        )?;
+
+        assert_eq!(plan.module_path, "crate::models::get_functions");
+        assert_eq!(plan.moved_items, vec!["AnalysisState::get_functions"]);
+
+        let target = change(&plan.changes, &dir, "src/models/get_functions.rs");
+        assert!(target.created);
+        assert_eq!(
+            target.contents.as_deref().unwrap_or_default(),
+            r#"use crate::{AnalysisState, FunctionInfo};
+
+impl AnalysisState {
+    pub fn get_functions(&self) -> Vec<&FunctionInfo> {
+        self.functions.values().collect()
+    }
+}
+"#
+        );
+
+        let lib = contents(&plan.changes, &dir, "src/lib.rs");
+        assert!(lib.contains(
+            "        Self { functions: HashMap::new() }\n    }\n}\n\n// Counts functions"
+        ));
+        assert!(!lib.contains("get_functions"));
+
+        assert_eq!(
+            contents(&plan.changes, &dir, "src/models/mod.rs"),
+            "pub mod get_functions;\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_move_private_items_updates_uses_and_visibility() -> Result<()> {
+        let dir = sample_crate()?;
+        let plan = plan_extract_to_module(
+            &dir.path().join("src/lib.rs"),
+            &["helper".to_string(), "Counter".to_string()],
+            &dir.path().join("src/util.rs"),
+        )?;
+
+        let util = contents(&plan.changes, &dir, "src/util.rs");
+        assert!(util
+            .contains("use crate::AnalysisState;\n\n// Counts functions\npub(crate) fn helper("));
+        assert!(util.contains("pub(crate) struct Counter {\n    pub(crate) count: usize,\n}"));
+
+        let lib = contents(&plan.changes, &dir, "src/lib.rs");
+        assert!(lib.starts_with("use std::collections::HashMap;\nuse crate::util::{Counter, helper};\n\npub mod models;\nmod report;\nmod util;\n"));
+        assert!(!lib.contains("fn helper"));
+        assert!(lib.contains("}\n\npub fn count("));
+
+        let report = contents(&plan.changes, &dir, "src/report.rs");
+        assert!(report.contains("crate::util::helper(state) + count(state)"));
+
+        Transaction::new("test").stage_all(plan.changes.clone()).commit(dir.path())?;
+        assert!(dir.path().join("src/util.rs").is_file());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_move_stays_within_the_owning_target() -> Result<()> {
+        let dir = sample_crate()?;
+        let main = "mod report;\n\nfn helper() {}\n\nfn main() {\n    crate::helper();\n    crate::report::run();\n}\n";
+        std::fs::write(dir.path().join("src/main.rs"), main)?;
+        std::fs::create_dir_all(dir.path().join("src/bin"))?;
+        std::fs::write(
+            dir.path().join("src/bin/tool.rs"),
+            "mod tool_util;\n\nfn parse() {}\n\nfn main() {\n    parse();\n    tool_util::run();\n}\n",
+        )?;
+        std::fs::write(
+            dir.path().join("src/bin/tool_util.rs"),
+            "pub fn run() {\n    crate::parse();\n}\n",
+        )?;
+
+        // The binary's own `crate::helper` is not the library's
+        let plan = plan_extract_to_module(
+            &dir.path().join("src/lib.rs"),
+            &["helper".to_string()],
+            &dir.path().join("src/util.rs"),
+        )?;
+        assert!(plan
+            .changes
+            .iter()
+            .all(|c| !c.path.ends_with("main.rs") && !c.path.starts_with(normalize_path(&dir.path().join("src/bin")))));
+        assert!(contents(&plan.changes, &dir, "src/report.rs").contains("crate::util::helper(state)"));
+
+        // A binary's modules are relative to its own root
+        let plan = plan_extract_to_module(
+            &dir.path().join("src/bin/tool.rs"),
+            &["parse".to_string()],
+            &dir.path().join("src/bin/parsing.rs"),
+        )?;
+        assert_eq!(plan.module_path, "crate::parsing");
+        assert!(contents(&plan.changes, &dir, "src/bin/tool.rs").contains("mod parsing;"));
+        assert!(contents(&plan.changes, &dir, "src/bin/tool_util.rs").contains("crate::parsing::parse();"));
+        assert!(plan.changes.iter().all(|c| !c.path.ends_with("lib.rs")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_move_rewrites_code_paths_and_reexports_public_items() -> Result<()> {
+        let dir = sample_crate()?;
+        std::fs::write(
+            dir.path().join("src/report.rs"),
+            "use crate::AnalysisState;\n\n/// Wraps `crate::helper`\npub fn report(state: &AnalysisState) -> String {\n    // crate::helper counts functions\n    format!(\"crate::helper: {}\", crate::helper(state) + crate::count(state))\n}\n",
+        )?;
+        let plan = plan_extract_to_module(
+            &dir.path().join("src/lib.rs"),
+            &["helper".to_string(), "count".to_string()],
+            &dir.path().join("src/util.rs"),
+        )?;
+
+        let report = contents(&plan.changes, &dir, "src/report.rs");
+        assert!(report.contains("/// Wraps `crate::helper`\n"));
+        assert!(report.contains("    // crate::helper counts functions\n"));
+        assert!(report.contains(
+            "format!(\"crate::helper: {}\", crate::util::helper(state) + crate::util::count(state))"
+        ));
+
+        // `count` is part of the crate's public API, `helper` is not
+        let lib = contents(&plan.changes, &dir, "src/lib.rs");
+        assert!(lib.starts_with("use std::collections::HashMap;\npub use crate::util::count;\n\npub mod models;"));
+        assert!(!lib.contains("util::helper"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_items_are_rejected() -> Result<()> {
+        let dir = sample_crate()?;
+        let result = plan_extract_to_module(
+            &dir.path().join("src/lib.rs"),
+            &["missing".to_string()],
+            &dir.path().join("src/util.rs"),
+        );
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_collapse_module_inline_merges_children() -> Result<()> {
+        let dir = sample_crate()?;
+        std::fs::write(
+            dir.path().join("src/models/mod.rs"),
+            "//! Models\n\npub mod user;\n#[cfg(test)]\nmod tests;\n",
+        )?;
+        std::fs::create_dir_all(dir.path().join("src/models/user"))?;
+        std::fs::write(
+            dir.path().join("src/models/user/mod.rs"),
+            "pub struct User;\n\nmod id;\n",
+        )?;
+        std::fs::write(
+            dir.path().join("src/models/user/id.rs"),
+            "pub type Id = u64;\n",
+        )?;
+        std::fs::write(dir.path().join("src/models/tests.rs"), "")?;
+
+        let plan = plan_collapse_module(
+            &dir.path().join("src/models/mod.rs"),
+            CollapseLayout::Inline,
+        )?;
+        assert_eq!(plan.merged_files.len(), 3);
+        assert_eq!(
+            contents(&plan.changes, &dir, "src/models.rs"),
+            "//! Models\n\npub mod user {\n    pub struct User;\n\n    mod id {\n        pub type Id = u64;\n    }\n}\n#[cfg(test)]\nmod tests {}\n"
+        );
+
+        Transaction::new("test").stage_all(plan.changes.clone()).commit(dir.path())?;
+        assert!(dir.path().join("src/models.rs").is_file());
+        assert!(!dir.path().join("src/models").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_collapse_module_refuses_ambiguous_layouts() -> Result<()> {
+        let dir = sample_crate()?;
+        let mod_file = dir.path().join("src/models/mod.rs");
+
+        std::fs::write(dir.path().join("src/models/stray.rs"), "")?;
+        assert!(plan_collapse_module(&mod_file, CollapseLayout::Inline).is_err());
+        // Undeclared files are harmless when the folder stays
+        let plan = plan_collapse_module(&mod_file, CollapseLayout::Flat)?;
+        assert_eq!(plan.changes.len(), 2);
+
+        std::fs::write(&mod_file, "#[path = \"other.rs\"]\nmod stray;\n")?;
+        assert!(plan_collapse_module(&mod_file, CollapseLayout::Flat).is_err());
+
+        std::fs::write(&mod_file, "")?;
+        std::fs::write(dir.path().join("src/models.rs"), "")?;
+        assert!(plan_collapse_module(&mod_file, CollapseLayout::Flat).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_use_tree_groups_siblings() {
+        let leaf = |path: &str| UseLeaf {
+            path: path.split("::").map(str::to_string).collect(),
+            alias: None,
+            is_glob: false,
+        };
+        assert_eq!(
+            render_use_tree(&[leaf("crate::a::B"), leaf("crate::a::C")]),
+            "crate::a::{B, C}"
+        );
+        assert_eq!(
+            render_use_tree(&[leaf("std::fmt"), leaf("std::io::Read")]),
+            "{std::fmt, std::io::Read}"
+        );
+    }
+}
@@ -3,13 +3,27 @@
 //! This module provides perfect import suggestions by hooking into rustc's name resolution
 //! and leveraging the compiler's actual resolution logic.
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::process::Command;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 #[cfg(windows)]
 use std::os::windows::process::ExitStatusExt;
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+use crate::builtins;
 use crate::cache::IncrementalCache;
+use crate::crate_index::{lib_root, module_path_string, target_roots, CrateIndex, IndexedItem, IndexedMacro};
+use crate::dependency_index::{dependency_items, SourceDirs};
+use crate::manifest::{workspace_dependencies, PackageManifest, Workspace};
+use crate::module_ops;
+use crate::preview;
+use crate::TextEdit;
+use crate::reexports::{CrateGraph, PathFilter};
+use crate::rustdoc_json::RustdocCrate;
+use crate::std_catalog;
+use crate::syntax::{type_key, SourceFile};
+use std::collections::{HashMap, HashSet};
 
 /// Information about an importable item
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,12 +36,39 @@ pub struct ImportableItem {
     pub kind: ItemKind,
     /// Where this item is defined (crate name for external)
     pub source: ItemSource,
-    /// Whether this is a public item
-    pub is_public: bool,
+    /// Declared visibility of the item
+    pub visibility: ItemVisibility,
     /// Documentation for this item
     pub docs: Option<String>,
     /// Whether this is a macro
     pub is_macro: bool,
+    /// Set when the item is `#[deprecated]`
+    #[serde(default)]
+    pub deprecation: Option<Deprecation>,
+    /// Whether using the item requires an unstable feature
+    #[serde(default)]
+    pub unstable: bool,
+    /// Methods and implementations, for traits
+    #[serde(default)]
+    pub trait_info: Option<TraitInfo>,
+}
+
+/// What a trait brings into scope when imported
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraitInfo {
+    /// Names of the methods the trait declares
+    pub methods: Vec<String>,
+    /// Last path segment of each type the trait is implemented for, `_` for blanket impls
+    pub implemented_for: Vec<String>,
+}
+
+/// A `#[deprecated]` notice
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deprecation {
+    /// Version the item was deprecated in
+    pub since: Option<String>,
+    /// Why, and what to use instead
+    pub note: Option<String>,
 }
 
 /// Different kinds of items that can be imported
@@ -42,10 +83,30 @@ pub enum ItemKind {
     Static,
     TypeAlias,
     Union,
+    /// Function-like macro: `macro_rules!` or `#[proc_macro]`
     Macro,
+    /// Derive macro, e.g. `Serialize` in `#[derive(Serialize)]`
+    DeriveMacro,
+    /// Attribute macro, e.g. `tokio::main` in `#[tokio::main]`
+    AttributeMacro,
+    /// Primitive type, e.g. `u8`
+    Primitive,
+    /// Enum variant, e.g. `Some`
+    Variant,
+    /// Generic parameter of an enclosing item
+    GenericParam,
+    /// Local variable or parameter
+    Local,
     Unknown,
 }
 
+impl ItemKind {
+    /// Whether this is any kind of macro
+    pub fn is_macro(self) -> bool {
+        matches!(self, ItemKind::Macro | ItemKind::DeriveMacro | ItemKind::AttributeMacro)
+    }
+}
+
 /// Source of an item (std lib, external, local)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ItemSource {
@@ -61,6 +122,56 @@ pub enum ItemSource {
     Compiler,
 }
 
+/// Declared visibility of an item
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ItemVisibility {
+    /// `pub`
+    #[default]
+    Public,
+    /// `pub(crate)`
+    Crate,
+    /// `pub(super)`
+    Super,
+    /// `pub(in path)`
+    Restricted { path: String },
+    /// No visibility, or `pub(self)`
+    Private,
+}
+
+impl ItemVisibility {
+    /// Whether a declaration in `declared_in` with this visibility can be named from `module`
+    /// (both relative to the crate root)
+    pub fn reaches(&self, declared_in: &[String], module: &[String]) -> bool {
+        match self {
+            ItemVisibility::Public | ItemVisibility::Crate => true,
+            ItemVisibility::Private => module.starts_with(declared_in),
+            ItemVisibility::Super => {
+                module.starts_with(&declared_in[..declared_in.len().saturating_sub(1)])
+            }
+            ItemVisibility::Restricted { path } => {
+                let path: Vec<String> = path.split("::").map(str::to_string).collect();
+                absolute_use_path(declared_in, &path).is_some_and(|absolute| {
+                    let scope: Vec<String> = absolute.split("::").skip(1).map(str::to_string).collect();
+                    module.starts_with(&scope)
+                })
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ItemVisibility {
+    /// The visibility as written, `pub(self)` for private items
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ItemVisibility::Public => write!(f, "pub"),
+            ItemVisibility::Crate => write!(f, "pub(crate)"),
+            ItemVisibility::Super => write!(f, "pub(super)"),
+            ItemVisibility::Restricted { path } => write!(f, "pub(in {})", path),
+            ItemVisibility::Private => write!(f, "pub(self)"),
+        }
+    }
+}
+
 /// Result of name resolution for a project
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NameResolutionResult {
@@ -72,6 +183,9 @@ pub struct NameResolutionResult {
     pub matches: Vec<ImportMatch>,
     /// Items that might be useful based on usage patterns
     pub suggestions: Vec<ImportableItem>,
+    /// Dependencies whose public API may be incomplete in `items`, because some of their
+    /// items are declared by macro invocations that are not expanded
+    pub partially_indexed: Vec<String>,
 }
 
 /// A potential match for an unresolved type
@@ -83,6 +197,9 @@ pub struct ImportMatch {
     pub confidence: f64,
     /// Why this matches (exact name, similar name, etc.)
     pub match_type: MatchType,
+    /// Other paths the item can be imported by, shortest first
+    #[serde(default)]
+    pub other_paths: Vec<String>,
 }
 
 /// Type of match
@@ -91,7 +208,93 @@ pub enum MatchType {
     ExactName,
     EditDistance { distance: usize },
     TypeMatches,
+    /// Exact name, and the item is already imported elsewhere in the workspace
     UsageBased,
+    /// A trait that declares the method called on a receiver
+    ProvidesMethod { method: String },
+}
+
+/// Ranked matches for one unresolved name, best first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NameMatches {
+    /// The unresolved name
+    pub name: String,
+    /// How the name is used, which limits the kinds of item matched
+    pub position: NamePosition,
+    pub matches: Vec<ImportMatch>,
+    /// Local items that would match but are not visible from the requesting module, best first
+    pub hidden: Vec<VisibilityFix>,
+}
+
+/// A match the requesting module cannot see, and how to make it visible
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VisibilityFix {
+    pub matched: ImportMatch,
+    /// Declarations to widen as (`crate::` path, new visibility), enclosing modules first
+    pub widen: Vec<(String, ItemVisibility)>,
+    /// Edits applying the new visibilities
+    pub edits: Vec<TextEdit>,
+}
+
+/// A name that needs an import, with where it appears
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnresolvedName {
+    pub name: String,
+    pub position: NamePosition,
+}
+
+impl UnresolvedName {
+    /// Read a name as given to `find_matches_for_types`: `name!` is a macro, anything else a type
+    pub fn parse(text: &str) -> Self {
+        match text.strip_suffix('!') {
+            Some(name) => Self { name: name.to_string(), position: NamePosition::Macro },
+            None => Self { name: text.to_string(), position: NamePosition::Type },
+        }
+    }
+}
+
+/// Syntactic position of an unresolved name, which decides the kinds of item that fit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NamePosition {
+    /// A type, e.g. `HashMap` in `let map: HashMap<K, V>`
+    Type,
+    /// A trait bound, e.g. `Display` in `T: Display` or `impl Display`
+    TraitBound,
+    /// A called function or constructor, e.g. `read_to_string(path)`
+    Call,
+    /// A macro invocation, e.g. `info` in `info!()`
+    Macro,
+    /// A derive, e.g. `Serialize` in `#[derive(Serialize)]`
+    Derive,
+    /// An attribute, e.g. `main` in `#[tokio::main]`
+    Attribute,
+    /// A pattern, e.g. `Some` in `Some(x)` or `Point` in `Point { x, y }`
+    Pattern,
+    /// A segment qualifying a longer path, e.g. `io` in `io::Result` or `HashMap` in `HashMap::new()`
+    PathPrefix,
+    /// Position not known, or a plain value
+    Any,
+}
+
+/// Which of a local item's paths to suggest when `pub use` re-exports give it several
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PathPreference {
+    /// Fewest segments, e.g. `crate::IncrementalCache` over `crate::cache::IncrementalCache`
+    #[default]
+    Shortest,
+    /// Where the item is declared, when the requesting module can name it there
+    Canonical,
+}
+
+/// What the workspace says about which imports are likely
+#[derive(Debug, Clone, Default)]
+pub struct RankingContext {
+    /// Crate names of the workspace's direct dependencies
+    pub direct_dependencies: HashSet<String>,
+    /// How many `use` declarations in the requesting crate import each path
+    pub usage: HashMap<String, usize>,
+    /// Edition of the package, the oldest one for a virtual workspace; decides the prelude
+    pub edition: u16,
 }
 
 /// Name resolution engine
@@ -104,6 +307,18 @@ pub struct NameResolver {
     include_builtins: bool,
     /// Maximum number of suggestions to return
     max_suggestions: usize,
+    /// Which path of a re-exported local item to suggest
+    path_preference: PathPreference,
+    /// `library` directory of `rust-src`, for the std catalog
+    std_library: Option<PathBuf>,
+    /// `rustdoc --output-format json` files, preferred over indexing the crates they document
+    rustdoc_json: Vec<PathBuf>,
+}
+
+impl Default for NameResolver {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl NameResolver {
@@ -114,6 +329,9 @@ impl NameResolver {
             include_externals: true,
             include_builtins: true,
             max_suggestions: 50,
+            path_preference: PathPreference::default(),
+            std_library: std_catalog::library_path(),
+            rustdoc_json: Vec::new(),
         }
     }
 
@@ -140,6 +358,24 @@ impl NameResolver {
         self
     }
 
+    /// Suggest the shortest or the declared path of re-exported local items
+    pub fn with_path_preference(mut self, preference: PathPreference) -> Self {
+        self.path_preference = preference;
+        self
+    }
+
+    /// Use the standard library sources in `library` (or the built-in list when `None`)
+    pub fn with_std_library(mut self, library: Option<PathBuf>) -> Self {
+        self.std_library = library;
+        self
+    }
+
+    /// Take the items of the crates documented in these rustdoc JSON files from the files
+    pub fn with_rustdoc_json(mut self, files: Vec<PathBuf>) -> Self {
+        self.rustdoc_json = files;
+        self
+    }
+
     /// Resolve names for a project
     pub fn resolve_project<P: AsRef<Path>>(&self, workspace_root: P) -> Result<NameResolutionResult> {
         let workspace_root = workspace_root.as_ref();
@@ -155,7 +391,8 @@ impl NameResolver {
         }
 
         // Run actual resolution
-        let result = self.resolve_project_impl(workspace_root)?;
+        let requester = default_requester(workspace_root)?;
+        let result = self.resolve_project_impl(workspace_root, requester.as_ref())?;
 
         // Cache the result
         if let Some(ref cache) = self.cache {
@@ -178,39 +415,273 @@ impl NameResolver {
         Ok(result)
     }
 
-    /// Find matches for unresolved types
+    /// Find matches for unresolved types (or macros, written `name!`), grouped per name
     pub fn find_matches_for_types(
         &self,
         unresolved_types: &[String],
         workspace_root: &Path,
-    ) -> Result<Vec<ImportMatch>> {
+    ) -> Result<Vec<NameMatches>> {
+        let names: Vec<UnresolvedName> = unresolved_types.iter().map(|text| UnresolvedName::parse(text)).collect();
+        self.find_matches(&names, workspace_root)
+    }
+
+    /// Find matches for the name at a 1-based line/column of a file, limited to the kinds of
+    /// item that fit how the name is used there
+    ///
+    /// Returns `None` when there is no name at the position.
+    pub fn find_matches_at(
+        &self,
+        file_path: &Path,
+        line: u32,
+        column: u32,
+        workspace_root: &Path,
+    ) -> Result<Option<NameMatches>> {
+        let source = SourceFile::read(file_path)?;
+        let Some(name) = source.name_at(line, column) else {
+            return Ok(None);
+        };
+        let requester = file_requester(workspace_root, file_path)?;
+        let mut module = requester.module.clone();
+        module.extend(source.scope_at(line, column).module_path);
+        let resolution = self.resolve_at(workspace_root, &requester, file_path, line, column)?;
+        Ok(self
+            .rank_matches(&[name], &resolution, Some(&requester), &module, workspace_root)?
+            .pop())
+    }
+
+    /// Find matches for unresolved names, best first and grouped per name
+    ///
+    /// Without a position to ask from, names resolve against the package's library (or its
+    /// first binary), and local items must be visible from anywhere in that crate.
+    pub fn find_matches(
+        &self,
+        names: &[UnresolvedName],
+        workspace_root: &Path,
+    ) -> Result<Vec<NameMatches>> {
+        let requester = default_requester(workspace_root)?;
         let resolution = self.resolve_project(workspace_root)?;
-        let mut matches = Vec::new();
-
-        for unresolved_type in unresolved_types {
-            // Search all items
-            for item in &resolution.items {
-                let (confidence, match_type) = self.calculate_match_score(unresolved_type, item);
-                
-                if confidence > 0.3 { // Threshold for relevance
-                    matches.push(ImportMatch {
-                        item: item.clone(),
-                        confidence,
-                        match_type,
+        self.rank_matches(names, &resolution, requester.as_ref(), &[], workspace_root)
+    }
+
+    /// Resolve names for the crate a file belongs to, with `in_scope_at_pos` filled for a
+    /// 1-based line/column of the file
+    pub fn resolve_project_at(
+        &self,
+        workspace_root: &Path,
+        file_path: &Path,
+        line: u32,
+        column: u32,
+    ) -> Result<NameResolutionResult> {
+        let requester = file_requester(workspace_root, file_path)?;
+        self.resolve_at(workspace_root, &requester, file_path, line, column)
+    }
+
+    fn resolve_at(
+        &self,
+        workspace_root: &Path,
+        requester: &Requester,
+        file_path: &Path,
+        line: u32,
+        column: u32,
+    ) -> Result<NameResolutionResult> {
+        let mut result = self.resolve_project_impl(workspace_root, Some(requester))?;
+        result.in_scope_at_pos = self.scope_items(&result.items, requester, file_path, line, column)?;
+        Ok(result)
+    }
+
+    /// Every name visible at a 1-based line/column of a file, innermost first
+    ///
+    /// Covers locals, generic parameters, items of enclosing blocks, imports (globs expanded
+    /// against the known items), items of the enclosing module and the edition's prelude,
+    /// each under the name the position can write it by. Items of ancestor modules follow
+    /// with `super::` paths as their names.
+    pub fn in_scope_at(
+        &self,
+        file_path: &Path,
+        line: u32,
+        column: u32,
+        workspace_root: &Path,
+    ) -> Result<Vec<ImportableItem>> {
+        Ok(self.resolve_project_at(workspace_root, file_path, line, column)?.in_scope_at_pos)
+    }
+
+    /// Rank the resolved items against each name, leaving out items `in_scope_at_pos` already
+    /// reaches by name; the requesting crate's items are suggested under the path
+    /// `path_preference` picks among those `module` can name them by, and those it cannot see
+    /// are offered with a visibility fix
+    fn rank_matches(
+        &self,
+        names: &[UnresolvedName],
+        resolution: &NameResolutionResult,
+        requester: Option<&Requester>,
+        module: &[String],
+        workspace_root: &Path,
+    ) -> Result<Vec<NameMatches>> {
+        let context = self.ranking_context(workspace_root, requester)?;
+        let index = requester.map(|requester| &requester.index);
+        let known: HashMap<&str, &ImportableItem> = resolution
+            .items
+            .iter()
+            .map(|item| (item.full_path.as_str(), item))
+            .collect();
+        let paths = index.map(|index| local_paths(index, module)).unwrap_or_default();
+        let reachable: HashSet<&str> = resolution
+            .in_scope_at_pos
+            .iter()
+            .filter(|item| !item.name.contains("::"))
+            .map(|item| item.full_path.as_str())
+            .collect();
+
+        names
+            .iter()
+            .map(|unresolved| {
+                let candidates: Vec<ImportMatch> = resolution
+                    .items
+                    .iter()
+                    .filter(|item| !reachable.contains(item.full_path.as_str()))
+                    // Builtins and the prelude never need an import
+                    .filter(|item| {
+                        !(self.include_builtins && builtins::in_scope_by_default(item, context.edition))
+                    })
+                    .filter_map(|item| {
+                        let (item, other_paths) = self.preferred_path(item, module, &paths, &known);
+                        let (confidence, match_type) = self.calculate_match_score(unresolved, &item, &context);
+                        // Threshold for relevance
+                        (confidence > 0.3).then_some(ImportMatch {
+                            item,
+                            confidence,
+                            match_type,
+                            other_paths,
+                        })
+                    })
+                    .collect();
+
+                // Items reached through a re-export are visible whatever their declaration is
+                let (mut matches, mut hidden): (Vec<_>, Vec<_>) =
+                    candidates.into_iter().partition(|candidate| {
+                        paths.contains_key(&candidate.item.full_path)
+                            || visible_from(&candidate.item, module, &known)
                     });
+                // Only the project's own items can be made visible
+                hidden.retain(|candidate| {
+                    matches!(candidate.item.source, ItemSource::Local { .. })
+                });
+                best_first(&mut matches, self.max_suggestions);
+                best_first(&mut hidden, self.max_suggestions);
+
+                let mut fixes = Vec::new();
+                if let Some(index) = index {
+                    for candidate in hidden {
+                        if let Some(fix) = visibility_fix(candidate, module, index)? {
+                            fixes.push(fix);
+                        }
+                    }
                 }
-            }
+
+                Ok(NameMatches {
+                    name: unresolved.name.clone(),
+                    position: unresolved.position,
+                    matches,
+                    hidden: fixes,
+                })
+            })
+            .collect()
+    }
+
+    /// A local item under the path `path_preference` picks among those `module` names it by,
+    /// with the other paths; other items as they are
+    fn preferred_path(
+        &self,
+        item: &ImportableItem,
+        module: &[String],
+        paths: &HashMap<String, Vec<String>>,
+        known: &HashMap<&str, &ImportableItem>,
+    ) -> (ImportableItem, Vec<String>) {
+        let mut item = item.clone();
+        let Some(found) = paths.get(&item.full_path) else {
+            return (item, Vec::new());
+        };
+        let keep_declared = self.path_preference == PathPreference::Canonical
+            && visible_from(&item, module, known);
+        if !keep_declared {
+            item.full_path = found[0].clone();
         }
+        let other_paths = found
+            .iter()
+            .filter(|path| **path != item.full_path)
+            .cloned()
+            .collect();
+        (item, other_paths)
+    }
 
-        // Sort by confidence and limit
-        matches.sort_by(|a, b| a.confidence.partial_cmp(&b.confidence).unwrap());
-        matches.truncate(self.max_suggestions);
-        
+    /// Traits whose import would make `receiver.method()` resolve, best first
+    ///
+    /// `receiver` is the receiver's type as rustc prints it, e.g. `&mut File`. Traits
+    /// implemented for that type rank above blanket impls, which rank above traits only
+    /// known to declare the method (their impl may live in another crate).
+    pub fn find_traits_for_method(
+        &self,
+        receiver: &str,
+        method: &str,
+        workspace_root: &Path,
+    ) -> Result<Vec<ImportMatch>> {
+        let resolution = self.resolve_project(workspace_root)?;
+        let requester = default_requester(workspace_root)?;
+        let context = self.ranking_context(workspace_root, requester.as_ref())?;
+        let receiver = receiver_key(receiver);
+        let known: HashMap<&str, &ImportableItem> = resolution
+            .items
+            .iter()
+            .map(|item| (item.full_path.as_str(), item))
+            .collect();
+
+        let mut matches: Vec<ImportMatch> = resolution
+            .items
+            .iter()
+            .filter(|item| visible_from(item, &[], &known))
+            .filter_map(|item| {
+                let info = item.trait_info.as_ref()?;
+                if !info.methods.iter().any(|name| name == method) {
+                    return None;
+                }
+                let implemented = |key: &str| info.implemented_for.iter().any(|ty| ty == key);
+                let impl_fit = if receiver.as_deref().is_some_and(implemented) {
+                    1.0
+                } else if implemented("_") {
+                    0.8
+                } else {
+                    0.5
+                };
+                let used = context.usage.get(&item.full_path).is_some_and(|&count| count > 0);
+                let usage_fit = if used { 1.0 } else { 0.9 };
+                Some(ImportMatch {
+                    item: item.clone(),
+                    confidence: impl_fit * source_fit(&item.source, &context) * usage_fit,
+                    match_type: MatchType::ProvidesMethod { method: method.to_string() },
+                    other_paths: Vec::new(),
+                })
+            })
+            .collect();
+
+        best_first(&mut matches, self.max_suggestions);
         Ok(matches)
     }
 
     /// Get all items from standard and core libraries
+    ///
+    /// Items come from the `rust-src` catalog when the sources are installed, and from a
+    /// short built-in list otherwise.
     pub fn get_std_items(&self) -> Result<Vec<ImportableItem>> {
+        let catalog = self.std_library.as_ref().and_then(|library| {
+            std_catalog::load_catalog(library, &std_catalog::cache_dir(), &self.get_rustc_version()).ok()
+        });
+        Ok(catalog.unwrap_or_else(|| self.builtin_std_items()))
+    }
+
+    // Private implementation methods
+
+    /// The most common std and core items, for toolchains without `rust-src`
+    fn builtin_std_items(&self) -> Vec<ImportableItem> {
         let mut items = Vec::new();
 
         // Standard library collections
@@ -220,63 +691,84 @@ impl NameResolver {
                 name: "HashMap".to_string(),
                 kind: ItemKind::Struct,
                 source: ItemSource::Std,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("A hash map implemented with quadratic probing and SIMD lookup".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
             ImportableItem {
                 full_path: "std::collections::HashSet".to_string(),
                 name: "HashSet".to_string(),
                 kind: ItemKind::Struct,
                 source: ItemSource::Std,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("A hash set implemented as a HashMap where the value is ()".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
             ImportableItem {
                 full_path: "std::collections::BTreeMap".to_string(),
                 name: "BTreeMap".to_string(),
                 kind: ItemKind::Struct,
                 source: ItemSource::Std,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("A map based on a B-Tree".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
             ImportableItem {
                 full_path: "std::collections::BTreeSet".to_string(),
                 name: "BTreeSet".to_string(),
                 kind: ItemKind::Struct,
                 source: ItemSource::Std,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("A set based on a B-Tree".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
             ImportableItem {
                 full_path: "std::collections::VecDeque".to_string(),
                 name: "VecDeque".to_string(),
                 kind: ItemKind::Struct,
                 source: ItemSource::Std,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("A double-ended queue implemented with a growable ring buffer".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
             ImportableItem {
                 full_path: "std::collections::LinkedList".to_string(),
                 name: "LinkedList".to_string(),
                 kind: ItemKind::Struct,
                 source: ItemSource::Std,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("A doubly-linked list with owned nodes".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
             ImportableItem {
                 full_path: "std::collections::BinaryHeap".to_string(),
                 name: "BinaryHeap".to_string(),
                 kind: ItemKind::Struct,
                 source: ItemSource::Std,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("A priority queue implemented with a binary heap".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
             // Sync primitives
             ImportableItem {
@@ -284,27 +776,36 @@ impl NameResolver {
                 name: "Arc".to_string(),
                 kind: ItemKind::Struct,
                 source: ItemSource::Std,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("Atomically Reference Counted pointer".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
             ImportableItem {
                 full_path: "std::sync::Mutex".to_string(),
                 name: "Mutex".to_string(),
                 kind: ItemKind::Struct,
                 source: ItemSource::Std,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("A mutual exclusion primitive useful for protecting shared data".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
             ImportableItem {
                 full_path: "std::sync::RwLock".to_string(),
                 name: "RwLock".to_string(),
                 kind: ItemKind::Struct,
                 source: ItemSource::Std,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("A reader-writer lock".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
             // Common traits
             ImportableItem {
@@ -312,27 +813,36 @@ impl NameResolver {
                 name: "Clone".to_string(),
                 kind: ItemKind::Trait,
                 source: ItemSource::Std,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("A common trait for the ability to explicitly duplicate an object".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
             ImportableItem {
                 full_path: "std::fmt::Display".to_string(),
                 name: "Display".to_string(),
                 kind: ItemKind::Trait,
                 source: ItemSource::Std,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("Format trait for an empty format, {}".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
             ImportableItem {
                 full_path: "std::fmt::Debug".to_string(),
                 name: "Debug".to_string(),
                 kind: ItemKind::Trait,
                 source: ItemSource::Std,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("Format trait for the ? format".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
             // IO types
             ImportableItem {
@@ -340,18 +850,24 @@ impl NameResolver {
                 name: "Result".to_string(),
                 kind: ItemKind::TypeAlias,
                 source: ItemSource::Std,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("A specialized Result type for I/O operations".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
             ImportableItem {
                 full_path: "std::fs::File".to_string(),
                 name: "File".to_string(),
                 kind: ItemKind::Struct,
                 source: ItemSource::Std,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("A reference to an open file on the filesystem".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
             // Path types
             ImportableItem {
@@ -359,18 +875,24 @@ impl NameResolver {
                 name: "Path".to_string(),
                 kind: ItemKind::Struct,
                 source: ItemSource::Std,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("A slice of a path".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
             ImportableItem {
                 full_path: "std::path::PathBuf".to_string(),
                 name: "PathBuf".to_string(),
                 kind: ItemKind::Struct,
                 source: ItemSource::Std,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("An owned, mutable path".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
         ]);
 
@@ -381,59 +903,73 @@ impl NameResolver {
                 name: "Option".to_string(),
                 kind: ItemKind::Enum,
                 source: ItemSource::Core,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("The Option type".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
             ImportableItem {
                 full_path: "core::result::Result".to_string(),
                 name: "Result".to_string(),
                 kind: ItemKind::Enum,
                 source: ItemSource::Core,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("The Result type".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
             ImportableItem {
                 full_path: "core::marker::Copy".to_string(),
                 name: "Copy".to_string(),
                 kind: ItemKind::Trait,
                 source: ItemSource::Core,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("Types whose values can be duplicated simply by copying bits".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
             ImportableItem {
                 full_path: "core::marker::Send".to_string(),
                 name: "Send".to_string(),
                 kind: ItemKind::Trait,
                 source: ItemSource::Core,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("Types that can be transferred across thread boundaries".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
             ImportableItem {
                 full_path: "core::marker::Sync".to_string(),
                 name: "Sync".to_string(),
                 kind: ItemKind::Trait,
                 source: ItemSource::Core,
-                is_public: true,
+                visibility: ItemVisibility::Public,
                 docs: Some("Types for which it is safe to share references between threads".to_string()),
                 is_macro: false,
+                deprecation: None,
+                unstable: false,
+                trait_info: None,
             },
         ]);
 
-        // Add common external crate items if enabled
-        if self.include_externals {
-            items.extend_from_slice(&self.get_common_external_items());
-        }
-
-        Ok(items)
+        items
     }
 
-    /// Private implementation methods
-
-    fn resolve_project_impl(&self, workspace_root: &Path) -> Result<NameResolutionResult> {
+    /// Every item known to the crate `requester` resolves against (to the workspace's
+    /// dependencies only, without one)
+    fn resolve_project_impl(
+        &self,
+        workspace_root: &Path,
+        requester: Option<&Requester>,
+    ) -> Result<NameResolutionResult> {
         // In a real implementation, this would:
         // 1. Build a custom rustc driver
         // 2. Hook into the name resolution pass
@@ -445,174 +981,259 @@ impl NameResolver {
         // 2. Parse the source files
         // 3. Combine with std/core library info
 
-        let mut all_items = self.get_std_items()?;
+        // Crates documented by rustdoc JSON come from the compiler's own view; the other
+        // sources only fill in what the files don't cover
+        let mut all_items = Vec::new();
+        let mut documented = HashSet::new();
+        let local_crate = requester.and_then(|requester| requester.lib_name.as_deref());
+        for file in &self.rustdoc_json {
+            let krate = RustdocCrate::load(file)?;
+            all_items.extend(krate.items(local_crate == Some(krate.name.as_str())));
+            documented.insert(krate.name);
+        }
+
+        if !documented.contains("std") {
+            all_items.extend(self.get_std_items()?);
+        }
+        if self.include_builtins {
+            all_items.extend(builtins::compiler_items());
+        }
 
         // Get local items from the project
-        let local_items = self.get_local_project_items(workspace_root)?;
-        all_items.extend(local_items);
+        let known: HashSet<String> = all_items.iter().map(|item| item.full_path.clone()).collect();
+        let local_items = requester.map(local_items).unwrap_or_default();
+        all_items.extend(local_items.into_iter().filter(|item| !known.contains(&item.full_path)));
+
+        // Public APIs of the dependencies, read from the registry or vendored sources
+        let mut partially_indexed = Vec::new();
+        if self.include_externals {
+            let sources = SourceDirs::discover(workspace_root);
+            let cache_dir = std_catalog::cache_dir();
+            let dependencies = dependency_items(workspace_root, &sources, Some(&cache_dir))?;
+            all_items.extend(dependencies.items.into_iter().filter(|item| match &item.source {
+                ItemSource::External { crate_name } => !documented.contains(crate_name),
+                _ => true,
+            }));
+            partially_indexed = dependencies
+                .partially_indexed
+                .into_iter()
+                .filter(|name| !documented.contains(name))
+                .collect();
+        }
 
         Ok(NameResolutionResult {
             items: all_items,
             in_scope_at_pos: vec![],
             matches: vec![],
             suggestions: vec![],
+            partially_indexed,
         })
     }
 
-    fn get_local_project_items(&self, workspace_root: &Path) -> Result<Vec<ImportableItem>> {
-        // Use cargo metadata to get project structure
-        let output = Command::new("cargo")
-            .args(&["metadata", "--format-version=1", "--no-deps"])
-            .current_dir(workspace_root)
-            .output()?;
+    /// Names visible at a position of a file, innermost first; see `in_scope_at`
+    fn scope_items(
+        &self,
+        items: &[ImportableItem],
+        requester: &Requester,
+        file_path: &Path,
+        line: u32,
+        column: u32,
+    ) -> Result<Vec<ImportableItem>> {
+        let lexical = SourceFile::read(file_path)?.scope_at(line, column);
+        let (index, edition) = (&requester.index, requester.edition);
+        let mut module = requester.module.clone();
+        module.extend(lexical.module_path.iter().cloned());
+        let module_string = module_path_string(&module);
+        let scoped = |name: &str, kind: ItemKind| ImportableItem {
+            full_path: name.to_string(),
+            name: name.to_string(),
+            kind,
+            source: ItemSource::Local {
+                module_path: module_string.clone(),
+            },
+            visibility: ItemVisibility::Private,
+            docs: None,
+            is_macro: kind.is_macro(),
+            deprecation: None,
+            unstable: false,
+            trait_info: None,
+        };
 
-        if !output.status.success() {
-            return Err(anyhow!("Failed to get cargo metadata: {}",
-                String::from_utf8_lossy(&output.stderr)));
+        // Items of the module itself, and of its ancestors under `super::` paths
+        let traits = CrateGraph::new(vec![("crate".to_string(), index)]).trait_infos();
+        let mut declared = Vec::new();
+        let mut ancestors = Vec::new();
+        for (i, item) in index.items.iter().enumerate() {
+            if !module.starts_with(&item.module_path) {
+                continue;
+            }
+            let depth = module.len() - item.module_path.len();
+            let mut entry = local_item(item.clone(), traits.get(&(0, i)).cloned());
+            if depth == 0 {
+                declared.push(entry);
+            } else {
+                entry.name = "super::".repeat(depth) + &entry.name;
+                ancestors.push((depth, entry));
+            }
         }
+        ancestors.sort_by_key(|(depth, _)| *depth);
 
-        let _metadata: serde_json::Value = serde_json::from_slice(&output.stdout)?;
-        
-        // In a real implementation, we would:
-        // 1. Parse source files to extract public items
-        // 2. Use rustc's API to resolve modules
-        // 3. Index items by name for fast lookup
-        
-        // For now, return empty list
-        Ok(Vec::new())
-    }
+        let known: HashMap<&str, &ImportableItem> =
+            items.iter().map(|item| (item.full_path.as_str(), item)).collect();
+        let declared_names: HashSet<&str> = declared.iter().map(|item| item.name.as_str()).collect();
+        let mut imported = Vec::new();
+        let mut globbed = Vec::new();
+        for leaf in lexical.imports.iter().rev() {
+            let mut path = leaf.path.clone();
+            // A path may start at an item of the module itself
+            if path.first().is_some_and(|first| declared_names.contains(first.as_str())) {
+                path.insert(0, "self".to_string());
+            }
+            let Some(absolute) = absolute_use_path(&module, &path) else {
+                continue;
+            };
+            if leaf.is_glob {
+                let prefix = absolute + "::";
+                globbed.extend(
+                    items
+                        .iter()
+                        .filter(|item| {
+                            item.full_path
+                                .strip_prefix(&prefix)
+                                .is_some_and(|name| !name.contains("::"))
+                        })
+                        .filter(|item| visible_from(item, &module, &known))
+                        .cloned(),
+                );
+            } else if let Some(name) = leaf.visible_name() {
+                let mut entry = match known.get(absolute.as_str()) {
+                    Some(item) => (*item).clone(),
+                    None => unlisted_item(&absolute, items),
+                };
+                entry.name = name.to_string();
+                imported.push(entry);
+            }
+        }
 
-    fn get_common_external_items(&self) -> Vec<ImportableItem> {
-        vec![
-            // serde
-            ImportableItem {
-                full_path: "serde::Serialize".to_string(),
-                name: "Serialize".to_string(),
-                kind: ItemKind::Trait,
-                source: ItemSource::External { crate_name: "serde".to_string() },
-                is_public: true,
-                docs: Some("A data structure that can be serialized into any data format supported by Serde".to_string()),
-                is_macro: false,
-            },
-            ImportableItem {
-                full_path: "serde::Deserialize".to_string(),
-                name: "Deserialize".to_string(),
-                kind: ItemKind::Trait,
-                source: ItemSource::External { crate_name: "serde".to_string() },
-                is_public: true,
-                docs: Some("A data structure that can be deserialized from any data format supported by Serde".to_string()),
-                is_macro: false,
-            },
-            // tokio
-            ImportableItem {
-                full_path: "tokio::join".to_string(),
-                name: "join".to_string(),
-                kind: ItemKind::Macro,
-                source: ItemSource::External { crate_name: "tokio".to_string() },
-                is_public: true,
-                docs: Some("Poll multiple futures concurrently".to_string()),
-                is_macro: true,
-            },
-            ImportableItem {
-                full_path: "tokio::spawn".to_string(),
-                name: "spawn".to_string(),
-                kind: ItemKind::Function,
-                source: ItemSource::External { crate_name: "tokio".to_string() },
-                is_public: true,
-                docs: Some("Spawn a task onto the Tokio runtime".to_string()),
-                is_macro: false,
-            },
-            // clap
-            ImportableItem {
-                full_path: "clap::Parser".to_string(),
-                name: "Parser".to_string(),
-                kind: ItemKind::Trait,
-                source: ItemSource::External { crate_name: "clap".to_string() },
-                is_public: true,
-                docs: Some("Parse command-line arguments by parsing a struct".to_string()),
-                is_macro: false,
-            },
-            // tracing
-            ImportableItem {
-                full_path: "tracing::info".to_string(),
-                name: "info".to_string(),
-                kind: ItemKind::Macro,
-                source: ItemSource::External { crate_name: "tracing".to_string() },
-                is_public: true,
-                docs: Some("Create an INFO level event".to_string()),
-                is_macro: true,
-            },
-            ImportableItem {
-                full_path: "tracing::debug".to_string(),
-                name: "debug".to_string(),
-                kind: ItemKind::Macro,
-                source: ItemSource::External { crate_name: "tracing".to_string() },
-                is_public: true,
-                docs: Some("Create a DEBUG level event".to_string()),
-                is_macro: true,
-            },
-            // uuid
-            ImportableItem {
-                full_path: "uuid::Uuid".to_string(),
-                name: "Uuid".to_string(),
-                kind: ItemKind::Struct,
-                source: ItemSource::External { crate_name: "uuid".to_string() },
-                is_public: true,
-                docs: Some("Universally Unique Identifiers (UUIDs)".to_string()),
-                is_macro: false,
-            },
-            // chrono
-            ImportableItem {
-                full_path: "chrono::DateTime".to_string(),
-                name: "DateTime".to_string(),
-                kind: ItemKind::Struct,
-                source: ItemSource::External { crate_name: "chrono".to_string() },
-                is_public: true,
-                docs: Some("ISO 8601 combined date and time with time zone".to_string()),
-                is_macro: false,
-            },
-            // regex
-            ImportableItem {
-                full_path: "regex::Regex".to_string(),
-                name: "Regex".to_string(),
-                kind: ItemKind::Struct,
-                source: ItemSource::External { crate_name: "regex".to_string() },
-                is_public: true,
-                docs: Some("A compiled regular expression".to_string()),
-                is_macro: false,
-            },
-        ]
-    }
+        let innermost = lexical
+            .locals
+            .iter()
+            .rev()
+            .map(|name| scoped(name, ItemKind::Local))
+            .chain(lexical.generics.iter().rev().map(|name| scoped(name, ItemKind::GenericParam)))
+            .chain(lexical.items.iter().rev().map(|(name, kind)| scoped(name, *kind)))
+            .collect::<Vec<_>>();
 
-    fn calculate_match_score(&self, search: &str, item: &ImportableItem) -> (f64, MatchType) {
-        // Exact name match
-        if search == item.name {
-            return (1.0, MatchType::ExactName);
-        }
+        // `macro_rules!` macros in textual scope, which shadow any macro named by path; later
+        // definitions shadow earlier ones
+        let textual: Vec<ImportableItem> = index
+            .macros_in_scope(&module, line as usize)
+            .into_iter()
+            .rev()
+            .map(textual_macro)
+            .collect();
+        // Exported macros of `#[macro_use] extern crate`s, as if glob-imported
+        let macro_use: Vec<ImportableItem> = items
+            .iter()
+            .filter(|item| {
+                item.is_macro
+                    && index.macro_use_crates.iter().any(|krate| {
+                        item.full_path == format!("{}::{}", krate.replace('-', "_"), item.name)
+                    })
+            })
+            .cloned()
+            .collect();
 
-        // Check if search is a prefix
-        if item.name.starts_with(search) {
-            return (0.8, MatchType::EditDistance { distance: 0 });
+        // Builtins, the prelude and the macros `std` exports at its root
+        let mut defaults = Vec::new();
+        if self.include_builtins {
+            defaults = builtins::prelude_items(edition, items);
+            defaults.extend(
+                items
+                    .iter()
+                    .filter(|item| builtins::in_scope_by_default(item, edition))
+                    .cloned(),
+            );
         }
 
-        // Check if search is a suffix
-        if item.name.ends_with(search) {
-            return (0.7, MatchType::EditDistance { distance: 0 });
-        }
+        // Explicit names shadow glob imports, which shadow the prelude
+        let mut seen = HashSet::new();
+        Ok(innermost
+            .into_iter()
+            .chain(textual)
+            .chain(imported)
+            .chain(declared)
+            .chain(globbed)
+            .chain(macro_use)
+            .chain(defaults)
+            .chain(ancestors.into_iter().map(|(_, item)| item))
+            .filter(|item| seen.insert(item.name.clone()))
+            .collect())
+    }
+
+    /// Direct dependencies of the workspace and paths the requesting crate imports, for
+    /// ranking matches
+    fn ranking_context(
+        &self,
+        workspace_root: &Path,
+        requester: Option<&Requester>,
+    ) -> Result<RankingContext> {
+        let direct_dependencies = workspace_dependencies(workspace_root)?
+            .into_iter()
+            .map(|dependency| dependency.name)
+            .collect();
 
-        // Calculate edit distance
-        let distance = edit_distance(search, &item.name);
-        if distance <= 2 && item.name.len() > 0 {
-            let score = 1.0 - (distance as f64 / item.name.len() as f64);
-            return (score * 0.6, MatchType::EditDistance { distance });
+        let mut usage = HashMap::new();
+        let imports = requester.iter().flat_map(|requester| &requester.index.reexports);
+        for import in imports.filter(|import| !import.leaf.is_glob) {
+            if let Some(path) = absolute_use_path(&import.module_path, &import.leaf.path) {
+                *usage.entry(path).or_insert(0) += 1;
+            }
         }
 
-        // Check if search contains parts of the path
-        if item.full_path.to_lowercase().contains(&search.to_lowercase()) {
-            return (0.4, MatchType::TypeMatches);
+        let edition = match requester {
+            Some(requester) => requester.edition,
+            None => workspace_edition(workspace_root)?,
+        };
+        Ok(RankingContext {
+            direct_dependencies,
+            usage,
+            edition,
+        })
+    }
+
+    /// Score how likely `item` is the import an unresolved name needs (0.0 - 1.0)
+    ///
+    /// Name similarity is weighed by how well the item's kind fits the name's position,
+    /// where it comes from (std, a direct dependency or the project itself), how deep its
+    /// path is, and whether the workspace already imports it elsewhere.
+    fn calculate_match_score(
+        &self,
+        unresolved: &UnresolvedName,
+        item: &ImportableItem,
+        context: &RankingContext,
+    ) -> (f64, MatchType) {
+        let (similarity, match_type) = name_similarity(&unresolved.name, item);
+        if similarity == 0.0 {
+            return (0.0, match_type);
         }
 
-        (0.0, MatchType::TypeMatches)
+        let depth = item.full_path.split("::").count().saturating_sub(2).min(6);
+        let depth_fit = 1.0 - 0.05 * depth as f64;
+        let used = context.usage.get(&item.full_path).is_some_and(|&count| count > 0);
+        let usage_fit = if used { 1.0 } else { 0.9 };
+        let status_fit = if item.deprecation.is_some() || item.unstable { 0.8 } else { 1.0 };
+
+        let match_type = match match_type {
+            MatchType::ExactName if used => MatchType::UsageBased,
+            other => other,
+        };
+        let fit = kind_fit(unresolved.position, item.kind)
+            * source_fit(&item.source, context)
+            * depth_fit
+            * usage_fit
+            * status_fit;
+        (similarity * fit, match_type)
     }
 
     fn get_rustc_version(&self) -> String {
@@ -629,6 +1250,504 @@ impl NameResolver {
     }
 }
 
+/// The crate a request resolves against: one library or binary target of a local package
+///
+/// The crate's own items are named by `crate::` paths. A binary also sees its package's
+/// library, but only the library's public API and under the library's crate name.
+struct Requester {
+    index: CrateIndex,
+    /// Module the request comes from
+    module: Vec<String>,
+    edition: u16,
+    /// Crate name of the library, when the requesting crate is a package's library
+    lib_name: Option<String>,
+    /// Crate name and index of the package's library, when the requesting crate is one of
+    /// the package's binaries
+    library: Option<(String, CrateIndex)>,
+}
+
+/// The crate requests without a file resolve against: the library of the package at
+/// `workspace_root` (of the first member of a virtual workspace), else its first binary
+fn default_requester(workspace_root: &Path) -> Result<Option<Requester>> {
+    let workspace = Workspace::load(workspace_root)?;
+    let package = workspace
+        .packages
+        .iter()
+        .find(|package| package.manifest_path.parent() == Some(workspace_root))
+        .or_else(|| workspace.packages.first());
+    match package {
+        Some(package) => package_requester(package, |_| Some(Vec::new())),
+        None => Ok(None),
+    }
+}
+
+/// The crate a file belongs to, with the file's module path in it
+///
+/// A file outside every target of the workspace is indexed as a crate root of its own.
+fn file_requester(workspace_root: &Path, file: &Path) -> Result<Requester> {
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let file = canonical(file);
+    let workspace = Workspace::load(workspace_root)?;
+    for package in &workspace.packages {
+        let requester = package_requester(package, |index| {
+            index
+                .modules
+                .iter()
+                .filter(|module| canonical(&module.file) == file)
+                .map(|module| module.path.clone())
+                .min_by_key(Vec::len)
+        })?;
+        if let Some(requester) = requester {
+            return Ok(requester);
+        }
+    }
+    Ok(Requester {
+        index: CrateIndex::build(&file)?,
+        module: Vec::new(),
+        edition: 2021,
+        lib_name: None,
+        library: None,
+    })
+}
+
+/// The first target of `package` in which `locate` finds the requesting module
+fn package_requester(
+    package: &PackageManifest,
+    locate: impl Fn(&CrateIndex) -> Option<Vec<String>>,
+) -> Result<Option<Requester>> {
+    let lib = lib_root(package);
+    let mut targets = Vec::new();
+    for root in target_roots(package) {
+        targets.push((lib.as_ref() == Some(&root), CrateIndex::build(&root)?));
+    }
+
+    let Some((position, module)) = targets
+        .iter()
+        .enumerate()
+        .find_map(|(i, (_, index))| Some((i, locate(index)?)))
+    else {
+        return Ok(None);
+    };
+    let (is_lib, index) = targets.remove(position);
+    let lib_name = library_name(package);
+    let library = if is_lib {
+        None
+    } else {
+        targets
+            .into_iter()
+            .find(|(is_lib, _)| *is_lib)
+            .map(|(_, index)| (lib_name.clone(), index))
+    };
+    Ok(Some(Requester {
+        index,
+        module,
+        edition: package_edition(package),
+        lib_name: is_lib.then_some(lib_name),
+        library,
+    }))
+}
+
+/// Items a requesting crate can import: its own under `crate::` paths, and those of its
+/// package's library under the library's crate name
+fn local_items(requester: &Requester) -> Vec<ImportableItem> {
+    let traits = CrateGraph::new(vec![("crate".to_string(), &requester.index)]).trait_infos();
+    let mut seen = HashSet::new();
+    let mut items: Vec<ImportableItem> = requester
+        .index
+        .items
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| seen.insert(item.path()))
+        .map(|(i, item)| local_item(item.clone(), traits.get(&(0, i)).cloned()))
+        .collect();
+
+    if let Some((name, index)) = &requester.library {
+        let graph = CrateGraph::new(vec![(name.clone(), index)]);
+        let traits = graph.trait_infos();
+        let filter = PathFilter {
+            crate_visible: false,
+            stable_only: false,
+            from: None,
+        };
+        let mut library: Vec<ImportableItem> = graph
+            .public_paths(0, filter)
+            .into_iter()
+            .filter_map(|((_, i), paths)| {
+                let item = &index.items[i];
+                let path = paths.into_iter().next()?;
+                let (name, module) = path.split_last()?;
+                Some(ImportableItem {
+                    full_path: path.join("::"),
+                    name: name.clone(),
+                    kind: item.kind,
+                    source: ItemSource::Local {
+                        module_path: module.join("::"),
+                    },
+                    visibility: ItemVisibility::Public,
+                    docs: item.docs.clone(),
+                    is_macro: item.kind.is_macro(),
+                    deprecation: None,
+                    unstable: false,
+                    trait_info: traits.get(&(0, i)).cloned(),
+                })
+            })
+            .collect();
+        library.sort_by(|a, b| a.full_path.cmp(&b.full_path));
+        items.extend(library);
+    }
+    items
+}
+
+/// Edition of the package at `workspace_root`, or the oldest of a virtual workspace's members
+fn workspace_edition(workspace_root: &Path) -> Result<u16> {
+    let workspace = Workspace::load(workspace_root)?;
+    let own = workspace
+        .packages
+        .iter()
+        .find(|package| package.manifest_path.parent() == Some(workspace_root));
+    Ok(match own {
+        Some(package) => package_edition(package),
+        None => workspace.packages.iter().map(package_edition).min().unwrap_or(2021),
+    })
+}
+
+/// Edition a package is compiled with; an inherited `edition.workspace = true` counts as 2021
+fn package_edition(package: &PackageManifest) -> u16 {
+    let edition = package.table.get("package").and_then(|table| table.get("edition"));
+    match edition {
+        Some(edition) => edition.as_str().and_then(|e| e.parse().ok()).unwrap_or(2021),
+        None => 2015,
+    }
+}
+
+/// An item of the local crate as an importable item
+fn local_item(item: IndexedItem, trait_info: Option<TraitInfo>) -> ImportableItem {
+    ImportableItem {
+        full_path: item.path(),
+        name: item.name,
+        kind: item.kind,
+        source: ItemSource::Local {
+            module_path: module_path_string(&item.module_path),
+        },
+        visibility: item.visibility,
+        docs: item.docs,
+        is_macro: item.kind.is_macro(),
+        deprecation: None,
+        unstable: false,
+        trait_info,
+    }
+}
+
+/// A `macro_rules!` macro in textual scope, under its `crate::` path when it is exported
+fn textual_macro(indexed: &IndexedMacro) -> ImportableItem {
+    ImportableItem {
+        full_path: if indexed.exported {
+            format!("crate::{}", indexed.name)
+        } else {
+            indexed.name.clone()
+        },
+        name: indexed.name.clone(),
+        kind: ItemKind::Macro,
+        source: ItemSource::Local {
+            module_path: module_path_string(&indexed.module_path),
+        },
+        visibility: if indexed.exported {
+            ItemVisibility::Public
+        } else {
+            ItemVisibility::Private
+        },
+        docs: indexed.docs.clone(),
+        is_macro: true,
+        deprecation: None,
+        unstable: false,
+        trait_info: None,
+    }
+}
+
+/// Stand-in for an imported path no item source lists, e.g. a module of a dependency
+fn unlisted_item(path: &str, items: &[ImportableItem]) -> ImportableItem {
+    let (parent, name) = path.rsplit_once("::").unwrap_or(("", path));
+    let prefix = format!("{}::", path);
+    let kind = if items.iter().any(|item| item.full_path.starts_with(&prefix)) {
+        ItemKind::Module
+    } else {
+        ItemKind::Unknown
+    };
+    let source = match path.split("::").next().unwrap_or_default() {
+        "crate" => ItemSource::Local {
+            module_path: parent.to_string(),
+        },
+        "std" | "alloc" => ItemSource::Std,
+        "core" => ItemSource::Core,
+        crate_name => ItemSource::External {
+            crate_name: crate_name.to_string(),
+        },
+    };
+    ImportableItem {
+        full_path: path.to_string(),
+        name: name.to_string(),
+        kind,
+        source,
+        visibility: ItemVisibility::Public,
+        docs: None,
+        is_macro: false,
+        deprecation: None,
+        unstable: false,
+        trait_info: None,
+    }
+}
+
+/// Sort matches best first, among equals the shorter path, and keep the first `max` paths
+fn best_first(matches: &mut Vec<ImportMatch>, max: usize) {
+    matches.sort_by(|a, b| {
+        b.confidence
+            .total_cmp(&a.confidence)
+            .then_with(|| a.item.full_path.len().cmp(&b.item.full_path.len()))
+            .then_with(|| a.item.full_path.cmp(&b.item.full_path))
+    });
+    matches.dedup_by(|a, b| a.item.full_path == b.item.full_path);
+    matches.truncate(max);
+}
+
+/// Whether `item` can be named from `module` of the local crate: other crates' items must be
+/// public, and the crate's own items and every module enclosing them must reach `module`
+fn visible_from(
+    item: &ImportableItem,
+    module: &[String],
+    known: &HashMap<&str, &ImportableItem>,
+) -> bool {
+    if !matches!(item.source, ItemSource::Local { .. }) {
+        return item.visibility == ItemVisibility::Public;
+    }
+    let Some(path) = item.full_path.strip_prefix("crate::") else {
+        return true;
+    };
+    let segments: Vec<String> = path.split("::").map(str::to_string).collect();
+    let declared_in = &segments[..segments.len() - 1];
+    item.visibility.reaches(declared_in, module)
+        && (1..=declared_in.len()).all(|depth| {
+            let module_path = format!("crate::{}", segments[..depth].join("::"));
+            known.get(module_path.as_str()).is_none_or(|module_item| {
+                module_item.visibility.reaches(&segments[..depth - 1], module)
+            })
+        })
+}
+
+/// Every path the crate's own items can be named by from `module` under their declared name,
+/// shortest first and keyed by the item's declaration path
+fn local_paths(index: &CrateIndex, module: &[String]) -> HashMap<String, Vec<String>> {
+    let filter = PathFilter {
+        crate_visible: true,
+        stable_only: false,
+        from: Some(module),
+    };
+    let mut paths = HashMap::new();
+    let graph = CrateGraph::new(vec![("crate".to_string(), index)]);
+    for ((_, i), found) in graph.public_paths(0, filter) {
+        let item = &index.items[i];
+        let found: Vec<String> = found
+            .iter()
+            .filter(|path| path.last() == Some(&item.name))
+            .map(|path| path.join("::"))
+            .collect();
+        if !found.is_empty() {
+            paths.entry(item.path()).or_insert(found);
+        }
+    }
+    paths
+}
+
+/// How to make a local item `module` cannot see visible there: each declaration that does not
+/// reach `module` is widened to `pub(super)` when that suffices, else to `pub(crate)`
+fn visibility_fix(
+    matched: ImportMatch,
+    module: &[String],
+    index: &CrateIndex,
+) -> Result<Option<VisibilityFix>> {
+    let Some(item) = index
+        .items
+        .iter()
+        .find(|item| item.path() == matched.item.full_path)
+    else {
+        return Ok(None);
+    };
+
+    // Enclosing modules first, then the item itself
+    let mut declarations: Vec<&IndexedItem> = (1..=item.module_path.len())
+        .filter_map(|depth| {
+            index.items.iter().find(|module_item| {
+                module_item.kind == ItemKind::Module
+                    && module_item.module_path == item.module_path[..depth - 1]
+                    && module_item.name == item.module_path[depth - 1]
+            })
+        })
+        .collect();
+    declarations.push(item);
+
+    let mut widen = Vec::new();
+    let mut per_file: Vec<(&Path, Vec<(&IndexedItem, ItemVisibility)>)> = Vec::new();
+    for declaration in declarations {
+        if declaration.visibility.reaches(&declaration.module_path, module) {
+            continue;
+        }
+        let parent = &declaration.module_path[..declaration.module_path.len().saturating_sub(1)];
+        let visibility = if module.starts_with(parent) {
+            ItemVisibility::Super
+        } else {
+            ItemVisibility::Crate
+        };
+        widen.push((declaration.path(), visibility.clone()));
+        match per_file.iter_mut().find(|(file, _)| *file == declaration.file) {
+            Some((_, changes)) => changes.push((declaration, visibility)),
+            None => per_file.push((&declaration.file, vec![(declaration, visibility)])),
+        }
+    }
+
+    let mut edits = Vec::new();
+    for (file, changes) in per_file {
+        let source = SourceFile::read(file)?;
+        // Inline modules between the file's own module and the declaration
+        let file_module = index
+            .modules
+            .iter()
+            .filter(|indexed| indexed.file == file)
+            .map(|indexed| indexed.path.len())
+            .min()
+            .unwrap_or(0);
+        let mut byte_edits = Vec::new();
+        for (declaration, visibility) in changes {
+            let depth = file_module.min(declaration.module_path.len());
+            let inline_path = &declaration.module_path[depth..];
+            byte_edits.push(module_ops::visibility_edit(
+                &source,
+                inline_path,
+                &declaration.name,
+                &visibility.to_string(),
+            )?);
+        }
+        let after = module_ops::apply_edits(&source.text, byte_edits);
+        edits.extend(preview::text_edits(&file.to_string_lossy(), &source.text, &after));
+    }
+
+    Ok(Some(VisibilityFix {
+        matched,
+        widen,
+        edits,
+    }))
+}
+
+/// How likely an import from `source` is to be the one wanted, before looking at the name
+fn source_fit(source: &ItemSource, context: &RankingContext) -> f64 {
+    match source {
+        ItemSource::Std | ItemSource::Local { .. } | ItemSource::Compiler => 1.0,
+        // `std` re-exports `core`, and its paths are the ones people write
+        ItemSource::Core => 0.95,
+        ItemSource::External { crate_name } if context.direct_dependencies.contains(crate_name) => 0.95,
+        // Not in Cargo.toml, so the import alone won't compile
+        ItemSource::External { .. } => 0.5,
+    }
+}
+
+/// The `type_key` of a receiver type as rustc prints it, `None` for types without one
+/// (`impl Trait`, closures)
+fn receiver_key(receiver: &str) -> Option<String> {
+    syn::parse_str::<syn::Type>(receiver).ok().as_ref().and_then(type_key)
+}
+
+/// How closely an item's name matches the searched name
+fn name_similarity(search: &str, item: &ImportableItem) -> (f64, MatchType) {
+    // Exact name match
+    if search == item.name {
+        return (1.0, MatchType::ExactName);
+    }
+
+    // Check if search is a prefix
+    if item.name.starts_with(search) {
+        return (0.8, MatchType::EditDistance { distance: 0 });
+    }
+
+    // Check if search is a suffix
+    if item.name.ends_with(search) {
+        return (0.7, MatchType::EditDistance { distance: 0 });
+    }
+
+    // Calculate edit distance
+    let distance = edit_distance(search, &item.name);
+    if distance <= 2 && !item.name.is_empty() {
+        let score = 1.0 - (distance as f64 / item.name.len() as f64);
+        return (score * 0.6, MatchType::EditDistance { distance });
+    }
+
+    // Check if search contains parts of the path
+    if item.full_path.to_lowercase().contains(&search.to_lowercase()) {
+        return (0.4, MatchType::TypeMatches);
+    }
+
+    (0.0, MatchType::TypeMatches)
+}
+
+/// How well an item of `kind` fits a name in `position`, 0.0 for kinds that cannot be used there
+fn kind_fit(position: NamePosition, kind: ItemKind) -> f64 {
+    use ItemKind::*;
+    match (position, kind) {
+        (NamePosition::Any, Unknown) => 0.7,
+        (NamePosition::Any, _) => 1.0,
+        (_, Unknown) => 0.5,
+        (NamePosition::Type, Struct | Enum | Union | TypeAlias | Primitive) => 1.0,
+        // `dyn Trait`
+        (NamePosition::Type, Trait) => 0.8,
+        (NamePosition::TraitBound, Trait) => 1.0,
+        (NamePosition::Call, Function) => 1.0,
+        // Tuple struct constructors
+        (NamePosition::Call, Struct) => 0.8,
+        (NamePosition::Macro, Macro) => 1.0,
+        (NamePosition::Derive, DeriveMacro) => 1.0,
+        (NamePosition::Attribute, AttributeMacro) => 1.0,
+        (NamePosition::Pattern, Struct) => 1.0,
+        (NamePosition::Pattern, Constant) => 0.8,
+        (NamePosition::Call | NamePosition::Pattern, Variant) => 1.0,
+        (NamePosition::PathPrefix, Module | Enum) => 1.0,
+        // Associated items, e.g. `HashMap::new()` or `Default::default()`
+        (NamePosition::PathPrefix, Struct | Union | Trait | TypeAlias | Primitive) => 0.8,
+        _ => 0.0,
+    }
+}
+
+/// The path a `use` in `module_path` imports, with `self` and `super` made absolute
+fn absolute_use_path(module_path: &[String], path: &[String]) -> Option<String> {
+    let (first, rest) = path.split_first()?;
+    let mut absolute = match first.as_str() {
+        "self" | "super" => {
+            let mut module = vec!["crate".to_string()];
+            module.extend_from_slice(module_path);
+            if first == "super" {
+                module.pop();
+            }
+            module
+        }
+        _ => vec![first.clone()],
+    };
+    for segment in rest {
+        if segment == "super" {
+            absolute.pop();
+        } else {
+            absolute.push(segment.clone());
+        }
+    }
+    Some(absolute.join("::"))
+}
+
+/// Crate name of a package's library, as used in paths
+fn library_name(package: &PackageManifest) -> String {
+    package
+        .table
+        .get("lib")
+        .and_then(|lib| lib.get("name"))
+        .and_then(|name| name.as_str())
+        .unwrap_or(&package.name)
+        .replace('-', "_")
+}
+
 /// Calculate the edit distance between two strings
 fn edit_distance(a: &str, b: &str) -> usize {
     let a = a.chars().collect::<Vec<_>>();
@@ -645,11 +1764,11 @@ fn edit_distance(a: &str, b: &str) -> usize {
 
     let mut dp = vec![vec![0; n + 1]; m + 1];
 
-    for i in 0..=m {
-        dp[i][0] = i;
+    for (i, row) in dp.iter_mut().enumerate() {
+        row[0] = i;
     }
-    for j in 0..=n {
-        dp[0][j] = j;
+    for (j, cell) in dp[0].iter_mut().enumerate() {
+        *cell = j;
     }
 
     for i in 1..=m {
@@ -671,34 +1790,80 @@ fn edit_distance(a: &str, b: &str) -> usize {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::{hermetic_resolver, TestProject};
+
+    fn item(full_path: &str, kind: ItemKind, source: ItemSource) -> ImportableItem {
+        ImportableItem {
+            full_path: full_path.to_string(),
+            name: full_path.rsplit("::").next().unwrap().to_string(),
+            kind,
+            source,
+            visibility: ItemVisibility::Public,
+            docs: None,
+            is_macro: kind.is_macro(),
+            deprecation: None,
+            unstable: false,
+            trait_info: None,
+        }
+    }
 
     #[test]
     fn test_calculate_match_score() {
         let resolver = NameResolver::new();
-        let item = ImportableItem {
-            full_path: "std::collections::HashMap".to_string(),
-            name: "HashMap".to_string(),
-            kind: ItemKind::Struct,
-            source: ItemSource::Std,
-            is_public: true,
-            docs: None,
-            is_macro: false,
-        };
+        let context = RankingContext::default();
+        let item = item("std::collections::HashMap", ItemKind::Struct, ItemSource::Std);
 
         // Exact match
-        let (score, match_type) = resolver.calculate_match_score("HashMap", &item);
-        assert_eq!(score, 1.0);
+        let (exact, match_type) = resolver.calculate_match_score(&UnresolvedName::parse("HashMap"), &item, &context);
+        assert!(exact > 0.8);
         assert!(matches!(match_type, MatchType::ExactName));
 
         // Edit distance match
-        let (score, match_type) = resolver.calculate_match_score("HashMapp", &item);
-        assert!(score > 0.5);
+        let (score, match_type) = resolver.calculate_match_score(&UnresolvedName::parse("HashMapp"), &item, &context);
+        assert!(score > 0.3 && score < exact);
         assert!(matches!(match_type, MatchType::EditDistance { distance: 1 }));
     }
 
     #[test]
-    fn test_get_std_items() -> Result<()> {
+    fn test_ranking_weighs_context() {
         let resolver = NameResolver::new();
+        let mut context = RankingContext::default();
+        context.direct_dependencies.insert("serde".to_string());
+        context.usage.insert("std::fmt::Result".to_string(), 3);
+        let score = |name: &UnresolvedName, item: &ImportableItem| resolver.calculate_match_score(name, item, &context);
+        let external = |krate: &str| ItemSource::External { crate_name: krate.to_string() };
+
+        // Already imported elsewhere in the workspace
+        let result = UnresolvedName::parse("Result");
+        let (used, match_type) = score(&result, &item("std::fmt::Result", ItemKind::TypeAlias, ItemSource::Std));
+        assert!(matches!(match_type, MatchType::UsageBased));
+        assert!(used > score(&result, &item("std::io::Result", ItemKind::TypeAlias, ItemSource::Std)).0);
+
+        // Kind against position
+        let bound = UnresolvedName { name: "Serialize".to_string(), position: NamePosition::TraitBound };
+        let derive = UnresolvedName { name: "Serialize".to_string(), position: NamePosition::Derive };
+        let as_trait = item("serde::Serialize", ItemKind::Trait, external("serde"));
+        let as_macro = item("serde::Serialize", ItemKind::DeriveMacro, external("serde"));
+        assert!(score(&bound, &as_trait).0 > score(&bound, &as_macro).0);
+        assert!(score(&derive, &as_macro).0 > score(&derive, &as_trait).0);
+
+        // Std over a direct dependency over a crate that isn't one, shallow paths over deep ones
+        let map = UnresolvedName::parse("HashMap");
+        let std_map = score(&map, &item("std::collections::HashMap", ItemKind::Struct, ItemSource::Std)).0;
+        let dep_map = score(&map, &item("serde::collections::HashMap", ItemKind::Struct, external("serde"))).0;
+        let other_map = score(&map, &item("hashbrown::HashMap", ItemKind::Struct, external("hashbrown"))).0;
+        let deep_map = score(&map, &item("std::collections::hash::map::HashMap", ItemKind::Struct, ItemSource::Std)).0;
+        assert!(std_map > dep_map && dep_map > other_map);
+        assert!(std_map > deep_map);
+
+        let mut deprecated = item("std::collections::HashMap", ItemKind::Struct, ItemSource::Std);
+        deprecated.deprecation = Some(Deprecation { since: None, note: None });
+        assert!(score(&map, &deprecated).0 < std_map);
+    }
+
+    #[test]
+    fn test_get_std_items() -> Result<()> {
+        let resolver = NameResolver::new().with_std_library(None);
         let items = resolver.get_std_items()?;
         
         // Check some common items exist
@@ -711,6 +1876,349 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_local_project_items() -> Result<()> {
+        let project = TestProject::new()?;
+        project.write("src/lib.rs", "pub mod models;\n")?;
+        project.write("src/models.rs", "pub struct User;\nfn helper() {}\n")?;
+
+        let resolver = hermetic_resolver();
+        let items = local_items(&default_requester(project.path())?.unwrap());
+
+        let user = items.iter().find(|item| item.name == "User").unwrap();
+        assert_eq!(user.full_path, "crate::models::User");
+        assert_eq!(user.visibility, ItemVisibility::Public);
+        assert!(matches!(&user.source, ItemSource::Local { module_path } if module_path == "crate::models"));
+        assert_eq!(items.iter().find(|item| item.name == "helper").unwrap().visibility, ItemVisibility::Private);
+
+        let groups = resolver.find_matches_for_types(&["User".to_string()], project.path())?;
+        assert!(groups[0].matches.iter().any(|m| m.item.full_path == "crate::models::User"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_binaries_see_the_library_by_crate_name() -> Result<()> {
+        let project = TestProject::with_manifest("[package]\nname = \"my-app\"\n")?;
+        project.write("src/lib.rs", "pub mod models;\nmod internal;\n")?;
+        project.write("src/models.rs", "pub struct User;\n")?;
+        project.write("src/internal.rs", "pub struct Secret;\n")?;
+        project.write("src/cli.rs", "pub struct Args;\n")?;
+        let main = project.write(
+            "src/main.rs",
+            "mod cli;\n\nfn main() {\n    let u: User = todo!();\n    let a: Args = todo!();\n    let s: Secret = todo!();\n}\n",
+        )?;
+
+        let resolver = hermetic_resolver();
+        let paths = |line: u32| -> Result<Vec<String>> {
+            let group = resolver.find_matches_at(&main, line, 12, project.path())?.unwrap();
+            Ok(group.matches.into_iter().map(|m| m.item.full_path).collect())
+        };
+
+        // The library's public API, under its crate name; the binary's own items under `crate::`
+        assert_eq!(paths(4)?, vec!["my_app::models::User"]);
+        assert_eq!(paths(5)?, vec!["crate::cli::Args"]);
+        assert!(paths(6)?.is_empty());
+
+        // The library does not see the binary's items
+        let groups = resolver.find_matches_for_types(&["Args".to_string(), "User".to_string()], project.path())?;
+        assert!(groups[0].matches.is_empty());
+        assert_eq!(groups[1].matches[0].item.full_path, "crate::models::User");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_matches_are_grouped_best_first() -> Result<()> {
+        let project = TestProject::new()?;
+        project.write("src/lib.rs", "pub mod models;\npub mod api;\n")?;
+        project.write("src/models.rs", "pub struct User;\npub struct UserId;\npub fn user() {}\n")?;
+        project.write("src/api.rs", "use crate::models::User;\n")?;
+
+        let resolver = hermetic_resolver();
+        let groups = resolver.find_matches_for_types(&["User".to_string(), "UserId".to_string()], project.path())?;
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].name, "User");
+        assert_eq!(groups[1].name, "UserId");
+
+        let best = &groups[0].matches[0];
+        assert_eq!(best.item.full_path, "crate::models::User");
+        assert!(matches!(best.match_type, MatchType::UsageBased));
+        assert!(groups[0].matches.windows(2).all(|pair| pair[0].confidence >= pair[1].confidence));
+        assert_eq!(groups[1].matches[0].item.full_path, "crate::models::UserId");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_matches_at_position_fit_the_syntactic_role() -> Result<()> {
+        let project = TestProject::new()?;
+        project.write("src/lib.rs", "pub mod log;\npub mod shapes;\npub mod widgets;\nmod usage;\n")?;
+        project.write(
+            "src/log.rs",
+            "#[macro_export]\nmacro_rules! info { () => {} }\npub fn info() {}\npub struct Info;\n",
+        )?;
+        project.write("src/shapes.rs", "pub trait Render {}\n")?;
+        project.write("src/widgets.rs", "pub struct Render;\n")?;
+        let usage = project.write(
+            "src/usage.rs",
+            "fn run() {\n    info!();\n}\n\nstruct Thing;\nimpl Render for Thing {}\n",
+        )?;
+
+        let resolver = hermetic_resolver();
+        let paths = |line: u32, column: u32| -> Result<Vec<String>> {
+            let group = resolver.find_matches_at(&usage, line, column, project.path())?.unwrap();
+            Ok(group.matches.into_iter().map(|m| m.item.full_path).collect())
+        };
+
+        assert_eq!(paths(2, 5)?, vec!["crate::info"]);
+        assert_eq!(paths(6, 6)?, vec!["crate::shapes::Render"]);
+        assert!(resolver.find_matches_at(&usage, 4, 1, project.path())?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_in_scope_at_position() -> Result<()> {
+        let project = TestProject::with_manifest("[package]\nname = \"app\"\nedition = \"2021\"\n")?;
+        project.write("src/lib.rs", "pub mod models;\npub mod api;\npub struct Root;\n")?;
+        project.write("src/models.rs", "pub struct User;\npub struct UserId;\nfn helper() {}\n")?;
+        let api = project.write(
+            "src/api.rs",
+            "use crate::models::*;\nuse std::collections::HashMap as Map;\n\n\
+             pub fn handler<T>(input: T) {\n    let count = 1;\n    let user: Use = todo();\n}\n",
+        )?;
+
+        let resolver = hermetic_resolver();
+        let scope = resolver.in_scope_at(&api, 6, 15, project.path())?;
+        let find = |name: &str| scope.iter().find(|item| item.name == name);
+
+        assert_eq!(find("count").unwrap().kind, ItemKind::Local);
+        assert!(find("input").is_some());
+        assert_eq!(find("T").unwrap().kind, ItemKind::GenericParam);
+        assert_eq!(find("Map").unwrap().full_path, "std::collections::HashMap");
+        assert_eq!(find("UserId").unwrap().full_path, "crate::models::UserId");
+        assert_eq!(find("handler").unwrap().kind, ItemKind::Function);
+        assert_eq!(find("super::Root").unwrap().full_path, "crate::Root");
+        assert!(find("TryFrom").is_some() && find("Some").is_some());
+        // Private items of another module are not glob-imported
+        assert!(find("helper").is_none());
+        // Locals come before everything they could shadow
+        assert_eq!(scope[0].name, "count");
+
+        // Suggestions leave out what is already reachable
+        let group = resolver.find_matches_at(&api, 6, 15, project.path())?.unwrap();
+        assert_eq!(group.name, "Use");
+        assert!(!group.matches.iter().any(|m| m.item.full_path.starts_with("crate::models::User")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hidden_matches_offer_to_widen_visibility() -> Result<()> {
+        let project = TestProject::new()?;
+        project.write("src/lib.rs", "pub mod shapes;\nmod usage;\n")?;
+        let shapes = project.write(
+            "src/shapes.rs",
+            "pub(crate) struct Circle;\nstruct Square;\nmod detail {\n    pub struct Triangle;\n}\n",
+        )?;
+        let usage = project.write(
+            "src/usage.rs",
+            "fn run() {\n    let c = Circle;\n    let s = Square;\n    let t = Triangle;\n}\n",
+        )?;
+
+        let resolver = hermetic_resolver();
+        let group = |line: u32| -> Result<NameMatches> {
+            Ok(resolver.find_matches_at(&usage, line, 13, project.path())?.unwrap())
+        };
+
+        let circle = group(2)?;
+        assert_eq!(circle.matches[0].item.full_path, "crate::shapes::Circle");
+        assert!(circle.hidden.is_empty());
+
+        // Private to `shapes`: not suggested, but can be opened up to its parent
+        let square = group(3)?;
+        assert!(square.matches.is_empty());
+        let fix = &square.hidden[0];
+        assert_eq!(fix.matched.item.full_path, "crate::shapes::Square");
+        assert_eq!(fix.widen, vec![("crate::shapes::Square".to_string(), ItemVisibility::Super)]);
+        assert_eq!(fix.edits.len(), 1);
+        assert_eq!(fix.edits[0].file, shapes.to_string_lossy());
+        assert_eq!(fix.edits[0].new_text, "pub(super) struct Square;\n");
+
+        // `Triangle` is public, the module enclosing it is not
+        let triangle = group(4)?;
+        assert!(triangle.matches.is_empty());
+        assert_eq!(
+            triangle.hidden[0].widen,
+            vec![("crate::shapes::detail".to_string(), ItemVisibility::Super)]
+        );
+        assert_eq!(triangle.hidden[0].edits[0].new_text, "pub(super) mod detail {\n");
+
+        // Private items are visible below the module declaring them, not above
+        let mut square = item(
+            "crate::shapes::Square",
+            ItemKind::Struct,
+            ItemSource::Local { module_path: "crate::shapes".to_string() },
+        );
+        square.visibility = ItemVisibility::Private;
+        assert!(visible_from(&square, &["shapes".to_string()], &HashMap::new()));
+        assert!(!visible_from(&square, &[], &HashMap::new()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reexported_items_use_the_preferred_path() -> Result<()> {
+        let project = TestProject::new()?;
+        project.write(
+            "src/lib.rs",
+            "mod cache;\nmod api;\npub use cache::*;\n\
+             mod store {\n    mod inner {\n        pub struct Blob;\n    }\n    pub use self::inner::Blob;\n}\n\
+             pub use store::Blob as Data;\n",
+        )?;
+        project.write("src/cache.rs", "pub struct IncrementalCache;\n")?;
+        let api = project.write("src/api.rs", "fn run() {\n    let c = IncrementalCache;\n    let b = Blob;\n}\n")?;
+
+        let resolver = hermetic_resolver();
+        let best = |resolver: &NameResolver, line: u32| -> Result<ImportMatch> {
+            let group = resolver.find_matches_at(&api, line, 13, project.path())?.unwrap();
+            assert!(group.hidden.is_empty());
+            Ok(group.matches[0].clone())
+        };
+
+        let cache = best(&resolver, 2)?;
+        assert_eq!(cache.item.full_path, "crate::IncrementalCache");
+        assert_eq!(cache.other_paths, vec!["crate::cache::IncrementalCache"]);
+
+        // `store::inner` is private to `store`, but `store` re-exports `Blob`; the renamed
+        // re-export at the root does not import it as `Blob`
+        let blob = best(&resolver, 3)?;
+        assert_eq!(blob.item.full_path, "crate::store::Blob");
+        assert!(blob.other_paths.is_empty());
+
+        let resolver = resolver.with_path_preference(PathPreference::Canonical);
+        let cache = best(&resolver, 2)?;
+        assert_eq!(cache.item.full_path, "crate::cache::IncrementalCache");
+        assert_eq!(cache.other_paths, vec!["crate::IncrementalCache"]);
+        assert_eq!(best(&resolver, 3)?.item.full_path, "crate::store::Blob");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_macros_from_proc_macro_crates_and_textual_scope() -> Result<()> {
+        let project = TestProject::with_manifest(
+            "[package]\nname = \"app\"\nedition = \"2021\"\n\n[dependencies]\nderives = { path = \"derives\" }\n",
+        )?;
+        project.write("derives/Cargo.toml", "[package]\nname = \"derives\"\n\n[lib]\nproc-macro = true\n")?;
+        project.write(
+            "derives/src/lib.rs",
+            "#[proc_macro_derive(Query)]\npub fn derive_query() {}\n#[proc_macro]\npub fn query() {}\n",
+        )?;
+        let source = "macro_rules! local { () => {}; }\n#[derive(Query)]\nstruct Search;\n\
+                      fn run() {\n    query!();\n    local!();\n}\n";
+        let lib = project.write("src/lib.rs", source)?;
+
+        // The path dependency is read from the project, so externals stay on
+        let resolver = hermetic_resolver().with_externals(true);
+        let paths = |line: u32, column: u32| -> Result<Vec<String>> {
+            let group = resolver.find_matches_at(&lib, line, column, project.path())?.unwrap();
+            Ok(group.matches.into_iter().map(|m| m.item.full_path).collect())
+        };
+
+        // Derives and function-like macros are told apart even when their names are close
+        assert_eq!(paths(2, 10)?, vec!["derives::Query"]);
+        assert_eq!(paths(5, 5)?, vec!["derives::query"]);
+
+        let scope = resolver.in_scope_at(&lib, 6, 5, project.path())?;
+        assert!(scope.iter().any(|item| item.name == "local" && item.kind == ItemKind::Macro));
+        assert!(resolver.in_scope_at(&lib, 1, 1, project.path())?.iter().all(|item| item.name != "local"));
+
+        // `#[macro_use] extern crate` puts the crate's macros in scope everywhere
+        project.write("src/lib.rs", &format!("#[macro_use]\nextern crate derives;\n{}", source))?;
+        assert!(resolver.in_scope_at(&lib, 7, 5, project.path())?.iter().any(|item| item.full_path == "derives::query"));
+        assert!(paths(7, 5)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_builtins_need_no_import() -> Result<()> {
+        let project = TestProject::with_manifest("[package]\nname = \"app\"\nedition = \"2021\"\n")?;
+        project.write("src/lib.rs", "pub struct Local;\n")?;
+
+        let names = ["Option".to_string(), "u8".to_string(), "Result".to_string()];
+        let resolver = hermetic_resolver();
+        let items = resolver.resolve_project(project.path())?.items;
+        assert!(items.iter().any(|item| item.name == "u8" && matches!(item.source, ItemSource::Compiler)));
+
+        let groups = resolver.find_matches_for_types(&names, project.path())?;
+        let paths = |group: &NameMatches| -> Vec<String> {
+            group.matches.iter().map(|m| m.item.full_path.clone()).collect()
+        };
+        assert!(!paths(&groups[0]).contains(&"core::option::Option".to_string()));
+        assert!(groups[1].matches.is_empty());
+        // Only the prelude's `Result` is skipped
+        assert_eq!(paths(&groups[2]), vec!["std::io::Result"]);
+
+        // Without builtins the prelude is suggested like any other item
+        let resolver = resolver.with_builtins(false);
+        let groups = resolver.find_matches_for_types(&names[..1], project.path())?;
+        assert_eq!(groups[0].matches[0].item.full_path, "core::option::Option");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_traits_for_method_rank_by_implementation() -> Result<()> {
+        let project = TestProject::new()?;
+        project.write("src/lib.rs", "pub mod sink;\npub mod ext;\n")?;
+        project.write(
+            "src/sink.rs",
+            "pub struct Sink;\npub trait Emit {\n    fn emit(&self);\n}\n\
+             impl Emit for Sink {\n    fn emit(&self) {}\n}\n",
+        )?;
+        project.write(
+            "src/ext.rs",
+            "pub trait EmitExt {\n    fn emit(&self);\n}\nimpl<T: Clone> EmitExt for T {\n    fn emit(&self) {}\n}\n\
+             pub trait Unrelated {\n    fn emit(&self);\n}\npub trait Other {\n    fn send(&self);\n}\n",
+        )?;
+
+        let resolver = hermetic_resolver();
+        let matches = resolver.find_traits_for_method("&mut Sink", "emit", project.path())?;
+        let paths: Vec<&str> = matches.iter().map(|m| m.item.full_path.as_str()).collect();
+        assert_eq!(paths, vec!["crate::sink::Emit", "crate::ext::EmitExt", "crate::ext::Unrelated"]);
+        assert!(matches!(&matches[0].match_type, MatchType::ProvidesMethod { method } if method == "emit"));
+        assert!(matches[0].confidence > matches[1].confidence);
+
+        // A receiver with no impl of its own only gets blanket and unknown impls
+        let matches = resolver.find_traits_for_method("impl Iterator<Item = u8>", "emit", project.path())?;
+        assert_eq!(matches[0].item.full_path, "crate::ext::EmitExt");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rustdoc_json_items() -> Result<()> {
+        let project = TestProject::with_manifest("[package]\nname = \"mini\"\n")?;
+        project.write("src/lib.rs", "pub struct Local;\n")?;
+
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/rustdoc");
+        let resolver =
+            hermetic_resolver().with_rustdoc_json(vec![fixtures.join("mini.json"), fixtures.join("dep.json")]);
+        let items = resolver.resolve_project(project.path())?.items;
+        let find = |path: &str| items.iter().find(|item| item.full_path == path);
+
+        // The package's own crate is local, with the syntax index filling in the rest
+        assert!(matches!(find("crate::Map").unwrap().source, ItemSource::Local { .. }));
+        assert!(find("crate::Local").is_some());
+        assert!(find("dep::Experimental").is_some_and(|item| item.unstable));
+        assert!(find("std::collections::HashMap").is_some());
+
+        Ok(())
+    }
+
     #[test]
     fn test_edit_distance() {
         assert_eq!(edit_distance("", ""), 0);
@@ -0,0 +1,276 @@
+//! Refactoring previews for Rusty Refactor
+//!
+//! This module turns a staged transaction into the file operations and text edits it would
+//! perform, so the editor can show a reviewable diff before anything touches the disk.
+
+use anyhow::Result;
+use std::fs;
+use std::ops::Range;
+
+use crate::transaction::Transaction;
+use crate::{FileOperation, RefactorPreview, SpanInfo, TextEdit};
+
+/// Above this many line pairs the changed region is reported as one replacement
+const DIFF_LIMIT: usize = 4_000_000;
+
+/// Describe what committing `transaction` would do, without changing any file
+///
+/// A removed file whose contents reappear unchanged at a new path is reported as a rename.
+/// Edits to existing files are minimal line-based replacements; created files get a single
+/// insertion. Ranges refer to the current contents of each file.
+pub fn preview(transaction: &Transaction) -> Result<RefactorPreview> {
+    let mut states = Vec::new();
+    for change in transaction.changes() {
+        let before = match fs::read_to_string(&change.path) {
+            Ok(text) => Some(text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        states.push((change, before));
+    }
+
+    let mut file_operations = Vec::new();
+    let mut edits = Vec::new();
+    let mut renamed_to = Vec::new();
+
+    for (change, before) in &states {
+        if change.contents.is_some() || before.is_none() {
+            continue;
+        }
+        let path = change.path.to_string_lossy().to_string();
+        let destination = states.iter().find(|(other, other_before)| {
+            other_before.is_none() && other.contents == *before && !renamed_to.contains(&other.path)
+        });
+        match destination {
+            Some((other, _)) => {
+                renamed_to.push(other.path.clone());
+                file_operations.push(FileOperation {
+                    kind: "rename".to_string(),
+                    path,
+                    new_path: Some(other.path.to_string_lossy().to_string()),
+                });
+            }
+            None => file_operations.push(FileOperation {
+                kind: "delete".to_string(),
+                path,
+                new_path: None,
+            }),
+        }
+    }
+
+    for (change, before) in &states {
+        let Some(after) = &change.contents else {
+            continue;
+        };
+        if renamed_to.contains(&change.path) {
+            continue;
+        }
+        let file = change.path.to_string_lossy().to_string();
+        if before.is_none() {
+            file_operations.push(FileOperation {
+                kind: "create".to_string(),
+                path: file.clone(),
+                new_path: None,
+            });
+        }
+        edits.extend(text_edits(
+            &file,
+            before.as_deref().unwrap_or_default(),
+            after,
+        ));
+    }
+
+    Ok(RefactorPreview {
+        label: transaction.label().to_string(),
+        file_operations,
+        edits,
+    })
+}
+
+/// Line-based edits turning `before` into `after`
+pub fn text_edits(file: &str, before: &str, after: &str) -> Vec<TextEdit> {
+    let old_lines: Vec<&str> = before.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = after.split_inclusive('\n').collect();
+    let old_offsets = line_offsets(&old_lines);
+
+    line_hunks(&old_lines, &new_lines)
+        .into_iter()
+        .map(|(old, new)| {
+            let start = old_offsets[old.start];
+            let end = old_offsets[old.end];
+            TextEdit {
+                file: file.to_string(),
+                range: span_between(before, start, end),
+                new_text: new_lines[new].concat(),
+            }
+        })
+        .collect()
+}
+
+// Helper functions
+
+/// Byte offset of each line start, plus the text length
+fn line_offsets(lines: &[&str]) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(lines.len() + 1);
+    let mut offset = 0;
+    offsets.push(0);
+    for line in lines {
+        offset += line.len();
+        offsets.push(offset);
+    }
+    offsets
+}
+
+/// 1-based, end-exclusive span between two byte offsets
+pub(crate) fn span_between(text: &str, start: usize, end: usize) -> SpanInfo {
+    let position = |offset: usize| {
+        let before = &text[..offset];
+        let line = before.matches('\n').count() as u32 + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let column = before[line_start..].chars().count() as u32 + 1;
+        (line, column)
+    };
+    let (line_start, column_start) = position(start);
+    let (line_end, column_end) = position(end);
+    SpanInfo {
+        line_start,
+        line_end,
+        column_start,
+        column_end,
+    }
+}
+
+/// Pairs of (old lines, new lines) ranges that differ
+fn line_hunks(old: &[&str], new: &[&str]) -> Vec<(Range<usize>, Range<usize>)> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    if old_mid.is_empty() && new_mid.is_empty() {
+        return Vec::new();
+    }
+    if old_mid.is_empty() || new_mid.is_empty() || old_mid.len() * new_mid.len() > DIFF_LIMIT {
+        return vec![(
+            prefix..prefix + old_mid.len(),
+            prefix..prefix + new_mid.len(),
+        )];
+    }
+
+    // Longest common subsequence lengths of every pair of suffixes
+    let width = new_mid.len() + 1;
+    let mut lengths = vec![0u32; (old_mid.len() + 1) * width];
+    for i in (0..old_mid.len()).rev() {
+        for j in (0..new_mid.len()).rev() {
+            lengths[i * width + j] = if old_mid[i] == new_mid[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut hunks = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let mut open: Option<(usize, usize)> = None;
+    while i < old_mid.len() || j < new_mid.len() {
+        if i < old_mid.len() && j < new_mid.len() && old_mid[i] == new_mid[j] {
+            if let Some((oi, oj)) = open.take() {
+                hunks.push((prefix + oi..prefix + i, prefix + oj..prefix + j));
+            }
+            i += 1;
+            j += 1;
+            continue;
+        }
+        open.get_or_insert((i, j));
+        let skip_old = j == new_mid.len()
+            || (i < old_mid.len() && lengths[(i + 1) * width + j] >= lengths[i * width + j + 1]);
+        if skip_old {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    if let Some((oi, oj)) = open {
+        hunks.push((prefix + oi..prefix + i, prefix + oj..prefix + j));
+    }
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::FileChange;
+    use tempfile::TempDir;
+
+    fn apply(before: &str, edits: &[TextEdit]) -> String {
+        let offset = |line: u32, column: u32| {
+            let start: usize = before
+                .split_inclusive('\n')
+                .take(line as usize - 1)
+                .map(str::len)
+                .sum();
+            start + column as usize - 1
+        };
+        let mut text = before.to_string();
+        for edit in edits.iter().rev() {
+            let start = offset(edit.range.line_start, edit.range.column_start);
+            let end = offset(edit.range.line_end, edit.range.column_end);
+            text.replace_range(start..end, &edit.new_text);
+        }
+        text
+    }
+
+    #[test]
+    fn test_text_edits_are_minimal_and_reproduce_the_result() {
+        let before = "use a;\n\nfn one() {}\n\nfn two() {}\n";
+        let after = "use a;\nuse b;\n\nfn one() {}\n";
+        let edits = text_edits("lib.rs", before, after);
+
+        assert_eq!(edits.len(), 2);
+        assert_eq!(edits[0].new_text, "use b;\n");
+        assert_eq!((edits[0].range.line_start, edits[0].range.line_end), (2, 2));
+        assert_eq!(apply(before, &edits), after);
+
+        let no_newline = text_edits("lib.rs", "fn a() {}", "fn b() {}");
+        assert_eq!(no_newline[0].range.column_end, 10);
+    }
+
+    #[test]
+    fn test_preview_reports_renames_and_leaves_disk_alone() -> Result<()> {
+        let dir = TempDir::new()?;
+        let old = dir.path().join("models.rs");
+        let new = dir.path().join("models/mod.rs");
+        fs::write(&old, "pub struct User;\n")?;
+
+        let mut transaction = Transaction::new("convert");
+        transaction
+            .stage(FileChange {
+                path: new.clone(),
+                contents: Some("pub struct User;\n".to_string()),
+                created: true,
+            })
+            .stage(FileChange {
+                path: old.clone(),
+                contents: None,
+                created: false,
+            });
+
+        let result = preview(&transaction)?;
+        assert_eq!(result.file_operations.len(), 1);
+        assert_eq!(result.file_operations[0].kind, "rename");
+        assert_eq!(
+            result.file_operations[0].new_path.as_deref(),
+            Some(new.to_string_lossy().as_ref())
+        );
+        assert!(result.edits.is_empty());
+        assert!(old.exists() && !new.exists());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,496 @@
+//! Re-export resolution for Rusty Refactor
+//!
+//! This module follows `use` declarations, including globs and renames, through one or more
+//! indexed crates to find every path under which an item can be named from outside the
+//! module that defines it.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::crate_index::{CrateIndex, IndexedReexport};
+use crate::{ItemKind, ItemVisibility, TraitInfo};
+
+/// Limit on nested `use` resolution, guarding against re-export cycles
+const MAX_DEPTH: usize = 16;
+
+/// An item definition: index of its crate in the graph and of the item in that crate
+pub type DefId = (usize, usize);
+
+/// Which declarations a path may pass through
+#[derive(Debug, Clone, Copy)]
+pub struct PathFilter<'m> {
+    /// Also follow `pub(crate)`, `pub(super)` and `pub(in ...)` declarations, for paths
+    /// written inside the crate itself
+    pub crate_visible: bool,
+    /// Skip `#[unstable]` and `#[doc(hidden)]` declarations
+    pub stable_only: bool,
+    /// With `crate_visible`, follow exactly the declarations visible from this module: private
+    /// items are included, private `use` imports are not
+    pub from: Option<&'m [String]>,
+}
+
+impl PathFilter<'_> {
+    fn accepts(
+        &self,
+        visibility: &ItemVisibility,
+        declared_in: &[String],
+        unstable: bool,
+        hidden: bool,
+    ) -> bool {
+        let visible = match (visibility, self.from) {
+            (_, Some(module)) if self.crate_visible => visibility.reaches(declared_in, module),
+            (ItemVisibility::Public, _) => true,
+            (ItemVisibility::Private, _) => false,
+            _ => self.crate_visible,
+        };
+        visible && !(self.stable_only && (unstable || hidden))
+    }
+}
+
+/// What a name or path refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Target {
+    /// A crate's root module
+    Root(usize),
+    Item(DefId),
+}
+
+/// A module: crate index and module path
+type ModulePos = (usize, Vec<String>);
+
+/// Indexed crates that can refer to each other by name
+pub struct CrateGraph<'a> {
+    crates: Vec<GraphCrate<'a>>,
+}
+
+struct GraphCrate<'a> {
+    name: String,
+    index: &'a CrateIndex,
+    /// Item indices per module path
+    items: HashMap<Vec<String>, Vec<usize>>,
+    /// `use` indices per module path
+    uses: HashMap<Vec<String>, Vec<usize>>,
+}
+
+impl<'a> CrateGraph<'a> {
+    /// Build a graph over crates, each given with the name other crates refer to it by
+    pub fn new(crates: Vec<(String, &'a CrateIndex)>) -> Self {
+        let crates = crates
+            .into_iter()
+            .map(|(name, index)| {
+                let mut items: HashMap<Vec<String>, Vec<usize>> = HashMap::new();
+                for (i, item) in index.items.iter().enumerate() {
+                    items.entry(item.module_path.clone()).or_default().push(i);
+                }
+                let mut uses: HashMap<Vec<String>, Vec<usize>> = HashMap::new();
+                for (i, reexport) in index.reexports.iter().enumerate() {
+                    uses.entry(reexport.module_path.clone())
+                        .or_default()
+                        .push(i);
+                }
+                GraphCrate {
+                    name,
+                    index,
+                    items,
+                    uses,
+                }
+            })
+            .collect();
+        Self { crates }
+    }
+
+    /// Every path each item of the graph can be named by, starting from one crate's root
+    ///
+    /// Paths start with the crate's name, or with `crate` when `filter.crate_visible` is set,
+    /// and are listed shortest first.
+    pub fn public_paths(
+        &self,
+        root: usize,
+        filter: PathFilter,
+    ) -> HashMap<DefId, Vec<Vec<String>>> {
+        let prefix = if filter.crate_visible {
+            "crate".to_string()
+        } else {
+            self.crates[root].name.clone()
+        };
+
+        let mut paths: HashMap<DefId, Vec<Vec<String>>> = HashMap::new();
+        let mut seen: HashSet<ModulePos> = HashSet::new();
+        let mut queue: VecDeque<(ModulePos, Vec<String>)> = VecDeque::new();
+        seen.insert((root, Vec::new()));
+        queue.push_back(((root, Vec::new()), vec![prefix]));
+
+        while let Some((module, public)) = queue.pop_front() {
+            for (name, target) in self.module_entries(&module, filter, 0) {
+                let mut path = public.clone();
+                path.push(name);
+                if let Target::Item(def) = target {
+                    let known = paths.entry(def).or_default();
+                    if !known.contains(&path) {
+                        known.push(path.clone());
+                    }
+                }
+                if let Some(inner) = self.module_of(target) {
+                    if seen.insert(inner.clone()) {
+                        queue.push_back((inner, path));
+                    }
+                }
+            }
+        }
+        paths
+    }
+
+    /// Methods of every trait in the graph and the types each is implemented for
+    ///
+    /// Implementing types are `type_key`s, with `_` for blanket impls. A single-segment
+    /// trait path that names nothing in its module is looked up from the crate root too,
+    /// where the standard library glob-imports its prelude.
+    pub fn trait_infos(&self) -> HashMap<DefId, TraitInfo> {
+        let mut infos: HashMap<DefId, TraitInfo> = HashMap::new();
+        for (krate, c) in self.crates.iter().enumerate() {
+            for (i, item) in c.index.items.iter().enumerate() {
+                if item.kind == ItemKind::Trait {
+                    infos.insert(
+                        (krate, i),
+                        TraitInfo {
+                            methods: item.methods.clone(),
+                            implemented_for: Vec::new(),
+                        },
+                    );
+                }
+            }
+        }
+
+        for (krate, c) in self.crates.iter().enumerate() {
+            for imp in &c.index.impls {
+                let mut targets = self.resolve(krate, &imp.module_path, &imp.trait_path, 0);
+                if targets.is_empty() && imp.trait_path.len() == 1 {
+                    targets = self.resolve(krate, &[], &imp.trait_path, 0);
+                }
+                for target in targets {
+                    let Target::Item(def) = target else {
+                        continue;
+                    };
+                    if let Some(info) = infos.get_mut(&def) {
+                        if !info.implemented_for.contains(&imp.self_type) {
+                            info.implemented_for.push(imp.self_type.clone());
+                        }
+                    }
+                }
+            }
+        }
+        for info in infos.values_mut() {
+            info.implemented_for.sort();
+        }
+        infos
+    }
+
+    /// Index of a crate in the graph
+    pub fn crate_index(&self, name: &str) -> Option<usize> {
+        self.crates.iter().position(|c| c.name == name)
+    }
+
+    /// Names a module exposes through `filter`, with what each refers to
+    fn module_entries(
+        &self,
+        (krate, module): &ModulePos,
+        filter: PathFilter,
+        depth: usize,
+    ) -> Vec<(String, Target)> {
+        let c = &self.crates[*krate];
+        let mut entries = Vec::new();
+        if depth > MAX_DEPTH {
+            return entries;
+        }
+
+        for &i in c.items.get(module).into_iter().flatten() {
+            let item = &c.index.items[i];
+            if filter.accepts(&item.visibility, module, item.unstable, item.hidden) {
+                entries.push((item.name.clone(), Target::Item((*krate, i))));
+            }
+        }
+        for &i in c.uses.get(module).into_iter().flatten() {
+            let reexport = &c.index.reexports[i];
+            // A private `use` only imports
+            if reexport.visibility == ItemVisibility::Private
+                || !filter.accepts(
+                    &reexport.visibility,
+                    module,
+                    reexport.unstable,
+                    reexport.hidden,
+                )
+            {
+                continue;
+            }
+            if reexport.leaf.is_glob {
+                for inner in self.use_modules(*krate, module, reexport, depth) {
+                    entries.extend(self.module_entries(&inner, filter, depth + 1));
+                }
+                continue;
+            }
+            let Some(name) = reexport.leaf.visible_name() else {
+                continue;
+            };
+            for target in self.resolve(*krate, module, &reexport.leaf.path, depth + 1) {
+                entries.push((name.to_string(), target));
+            }
+        }
+        entries
+    }
+
+    /// Modules a glob `use` imports from
+    fn use_modules(
+        &self,
+        krate: usize,
+        module: &[String],
+        reexport: &IndexedReexport,
+        depth: usize,
+    ) -> Vec<ModulePos> {
+        let path = &reexport.leaf.path;
+        if path.is_empty() {
+            return Vec::new();
+        }
+        self.resolve(krate, module, path, depth + 1)
+            .into_iter()
+            .filter_map(|target| self.module_of(target))
+            .collect()
+    }
+
+    /// Resolve a `use` path written in `module` of crate `krate`
+    fn resolve(
+        &self,
+        krate: usize,
+        module: &[String],
+        path: &[String],
+        depth: usize,
+    ) -> Vec<Target> {
+        let Some((first, rest)) = path.split_first() else {
+            return Vec::new();
+        };
+        if depth > MAX_DEPTH {
+            return Vec::new();
+        }
+
+        let mut current: Vec<Target> = match first.as_str() {
+            "crate" => vec![Target::Root(krate)],
+            "self" => vec![self.module_target(krate, module)],
+            "super" => {
+                let parent = &module[..module.len().saturating_sub(1)];
+                vec![self.module_target(krate, parent)]
+            }
+            name => {
+                let local = self.lookup(krate, module, name, krate, depth);
+                if local.is_empty() {
+                    self.extern_crate(krate, name)
+                        .map(Target::Root)
+                        .into_iter()
+                        .collect()
+                } else {
+                    local
+                }
+            }
+        };
+
+        for segment in rest {
+            let modules: Vec<ModulePos> = current
+                .iter()
+                .filter_map(|&target| self.module_of(target))
+                .collect();
+            current = modules
+                .iter()
+                .flat_map(|(c, inner)| match segment.as_str() {
+                    "super" => {
+                        vec![self.module_target(*c, &inner[..inner.len().saturating_sub(1)])]
+                    }
+                    _ => self.lookup(*c, inner, segment, krate, depth),
+                })
+                .collect();
+        }
+        current
+    }
+
+    /// Everything `name` refers to in a module, as seen from crate `from`
+    fn lookup(
+        &self,
+        krate: usize,
+        module: &[String],
+        name: &str,
+        from: usize,
+        depth: usize,
+    ) -> Vec<Target> {
+        let c = &self.crates[krate];
+        let visible =
+            |visibility: &ItemVisibility| from == krate || *visibility == ItemVisibility::Public;
+
+        let mut found: Vec<Target> = c
+            .items
+            .get(module)
+            .into_iter()
+            .flatten()
+            .filter(|&&i| c.index.items[i].name == name && visible(&c.index.items[i].visibility))
+            .map(|&i| Target::Item((krate, i)))
+            .collect();
+
+        let uses: Vec<&IndexedReexport> = c
+            .uses
+            .get(module)
+            .into_iter()
+            .flatten()
+            .map(|&i| &c.index.reexports[i])
+            .filter(|reexport| visible(&reexport.visibility))
+            .collect();
+        for reexport in uses.iter().filter(|r| r.leaf.visible_name() == Some(name)) {
+            found.extend(self.resolve(krate, module, &reexport.leaf.path, depth + 1));
+        }
+        if found.is_empty() {
+            for reexport in uses.iter().filter(|r| r.leaf.is_glob) {
+                for (inner_crate, inner) in self.use_modules(krate, module, reexport, depth) {
+                    found.extend(self.lookup(inner_crate, &inner, name, krate, depth + 1));
+                }
+            }
+        }
+        found.dedup();
+        found
+    }
+
+    /// Crate a name refers to from inside crate `krate`: a graph crate or `extern crate` alias
+    fn extern_crate(&self, krate: usize, name: &str) -> Option<usize> {
+        let name = self.crates[krate]
+            .index
+            .extern_crates
+            .iter()
+            .find(|(_, alias)| alias == name)
+            .map_or(name, |(original, _)| original.as_str());
+        self.crate_index(name)
+    }
+
+    /// Target for a module path, the crate root when empty
+    fn module_target(&self, krate: usize, module: &[String]) -> Target {
+        let Some((name, parent)) = module.split_last() else {
+            return Target::Root(krate);
+        };
+        let c = &self.crates[krate];
+        c.items
+            .get(parent)
+            .into_iter()
+            .flatten()
+            .find(|&&i| c.index.items[i].kind == ItemKind::Module && c.index.items[i].name == *name)
+            .map_or(Target::Root(krate), |&i| Target::Item((krate, i)))
+    }
+
+    /// The module a target is, if it is one
+    fn module_of(&self, target: Target) -> Option<ModulePos> {
+        match target {
+            Target::Root(krate) => Some((krate, Vec::new())),
+            Target::Item((krate, i)) => {
+                let item = &self.crates[krate].index.items[i];
+                (item.kind == ItemKind::Module).then(|| {
+                    let mut path = item.module_path.clone();
+                    path.push(item.name.clone());
+                    (krate, path)
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_public_paths_follow_reexport_chains() -> Result<()> {
+        let dir = TempDir::new()?;
+        let src = dir.path().join("src");
+        fs::create_dir_all(src.join("collections/hash"))?;
+        fs::write(
+            src.join("lib.rs"),
+            "pub mod collections;\npub use collections::HashMap as Map;\n",
+        )?;
+        fs::write(
+            src.join("collections/mod.rs"),
+            "mod hash;\npub mod hash_map {\n    pub use super::hash::map::*;\n}\npub use self::hash_map::HashMap;\n",
+        )?;
+        fs::write(src.join("collections/hash/mod.rs"), "pub mod map;\n")?;
+        fs::write(
+            src.join("collections/hash/map.rs"),
+            "pub struct HashMap;\npub struct Entry;\n#[doc(hidden)]\npub struct Raw;\n",
+        )?;
+
+        let index = CrateIndex::build(&src.join("lib.rs"))?;
+        let graph = CrateGraph::new(vec![("mystd".to_string(), &index)]);
+        let filter = PathFilter {
+            crate_visible: false,
+            stable_only: true,
+            from: None,
+        };
+        let paths = graph.public_paths(0, filter);
+        let of = |name: &str| {
+            let i = index
+                .items
+                .iter()
+                .position(|item| item.name == name)
+                .unwrap();
+            paths.get(&(0, i)).cloned().unwrap_or_default()
+        };
+
+        let hash_map: Vec<String> = of("HashMap").iter().map(|p| p.join("::")).collect();
+        assert_eq!(
+            hash_map,
+            vec![
+                "mystd::Map",
+                "mystd::collections::HashMap",
+                "mystd::collections::hash_map::HashMap",
+            ]
+        );
+        assert_eq!(
+            of("Entry")[0].join("::"),
+            "mystd::collections::hash_map::Entry"
+        );
+        assert!(of("Raw").is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_trait_infos_collect_impls() -> Result<()> {
+        let dir = TempDir::new()?;
+        let src = dir.path().join("src");
+        fs::create_dir_all(src.join("io"))?;
+        fs::write(
+            src.join("lib.rs"),
+            "pub mod io;\npub mod fs {\n    pub struct File;\n    \
+             impl crate::io::Write for File {\n        fn write_all(&mut self) {}\n    }\n    \
+             impl crate::io::Write for &File {\n        fn write_all(&mut self) {}\n    }\n}\n",
+        )?;
+        fs::write(
+            src.join("io/mod.rs"),
+            "pub trait Write {\n    fn write_all(&mut self);\n    fn flush(&mut self) {}\n    \
+             const SIZE: usize;\n}\n\
+             pub trait WriteExt {\n    fn write_twice(&mut self);\n}\n\
+             impl<W: Write> WriteExt for W {\n    fn write_twice(&mut self) {}\n}\n\
+             impl Write for Vec<u8> {\n    fn write_all(&mut self) {}\n}\n",
+        )?;
+
+        let index = CrateIndex::build(&src.join("lib.rs"))?;
+        let infos = CrateGraph::new(vec![("mystd".to_string(), &index)]).trait_infos();
+        let of = |name: &str| {
+            let i = index
+                .items
+                .iter()
+                .position(|item| item.name == name)
+                .unwrap();
+            infos.get(&(0, i)).cloned()
+        };
+
+        let write = of("Write").expect("traits have an entry");
+        assert_eq!(write.methods, vec!["write_all", "flush"]);
+        assert_eq!(write.implemented_for, vec!["File", "Vec"]);
+        assert_eq!(of("WriteExt").unwrap().implemented_for, vec!["_"]);
+        assert!(of("File").is_none());
+
+        Ok(())
+    }
+}
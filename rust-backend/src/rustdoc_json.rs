@@ -0,0 +1,459 @@
+//! Rustdoc JSON ingestion for Rusty Refactor
+//!
+//! This module reads the output of `rustdoc --output-format json` and lists a crate's
+//! importable items under the paths the compiler reports for them, re-exports included,
+//! along with their deprecation and stability.
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+
+use crate::syntax::first_paragraph;
+use crate::{Deprecation, ImportableItem, ItemKind, ItemSource, ItemVisibility, TraitInfo};
+
+/// Limit on nested glob re-exports, guarding against cycles
+const MAX_DEPTH: usize = 16;
+
+/// A crate documented by `rustdoc --output-format json`
+#[derive(Debug, Clone)]
+pub struct RustdocCrate {
+    /// Crate name, as written in paths
+    pub name: String,
+    /// Version of the JSON format the file was written in
+    pub format_version: u64,
+    root: String,
+    /// Items of this crate by id
+    index: Map<String, Value>,
+    /// Path and kind of every item the crate refers to, including other crates' items
+    paths: Map<String, Value>,
+}
+
+impl RustdocCrate {
+    /// Read a rustdoc JSON file
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Failed to load {}", path.display()))
+    }
+
+    /// Parse the text of a rustdoc JSON file
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut doc: Value = serde_json::from_str(text)?;
+        let root = doc
+            .get("root")
+            .and_then(id_key)
+            .ok_or_else(|| anyhow!("Not a rustdoc JSON file: no root module"))?;
+        let format_version = doc
+            .get("format_version")
+            .and_then(Value::as_u64)
+            .unwrap_or_default();
+        let index = take_object(&mut doc, "index");
+        let paths = take_object(&mut doc, "paths");
+        let name = index
+            .get(&root)
+            .and_then(|item| item.get("name"))
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("Root module {} is missing from the index", root))?
+            .to_string();
+
+        Ok(Self {
+            name,
+            format_version,
+            root,
+            index,
+            paths,
+        })
+    }
+
+    /// Every item reachable from the crate root, under the shortest path for each name
+    ///
+    /// A `local` crate is the project itself: its paths start with `crate` and items that
+    /// are not public (present when documented with `--document-private-items`) are listed
+    /// too. Otherwise only the public API is listed, under the crate's name.
+    pub fn items(&self, local: bool) -> Vec<ImportableItem> {
+        let prefix = if local { "crate" } else { self.name.as_str() };
+
+        // Path and public flag per (item id, name it is visible under)
+        let mut found: HashMap<(String, String), (Vec<String>, bool)> = HashMap::new();
+        let mut visited = HashSet::from([self.root.clone()]);
+        let mut queue = VecDeque::from([(self.root.clone(), vec![prefix.to_string()], true)]);
+
+        while let Some((module, path, module_public)) = queue.pop_front() {
+            for (name, id, public) in self.module_entries(&module, 0) {
+                let public = public && module_public;
+                if !public && !local {
+                    continue;
+                }
+                let mut item_path = path.clone();
+                item_path.push(name.clone());
+
+                let known = found
+                    .entry((id.clone(), name))
+                    .or_insert((item_path.clone(), public));
+                if public && !known.1 {
+                    *known = (item_path.clone(), public);
+                }
+                if self.is_module(&id) && visited.insert(id.clone()) {
+                    queue.push_back((id, item_path, public));
+                }
+            }
+        }
+
+        let mut items: Vec<ImportableItem> = found
+            .into_iter()
+            .filter_map(|((id, name), (path, public))| {
+                self.importable_item(&id, name, &path, local, public)
+            })
+            .collect();
+        items.sort_by(|a, b| a.full_path.cmp(&b.full_path));
+        items
+    }
+
+    /// Names a module defines or re-exports, with the id each refers to and whether it is `pub`
+    fn module_entries(&self, module: &str, depth: usize) -> Vec<(String, String, bool)> {
+        let mut entries = Vec::new();
+        let Some(Value::Array(ids)) = self
+            .index
+            .get(module)
+            .and_then(|item| inner(item))
+            .and_then(|(_, inner)| inner.get("items"))
+        else {
+            return entries;
+        };
+
+        for id in ids.iter().filter_map(id_key) {
+            let Some(item) = self.index.get(&id) else {
+                continue;
+            };
+            let public = item.get("visibility").and_then(Value::as_str) == Some("public");
+            match inner(item) {
+                Some(("use" | "import", import)) => {
+                    let Some(target) = import.get("id").and_then(id_key) else {
+                        continue;
+                    };
+                    if import.get("is_glob").and_then(Value::as_bool) == Some(true) {
+                        if depth < MAX_DEPTH && self.is_module(&target) {
+                            entries.extend(
+                                self.module_entries(&target, depth + 1)
+                                    .into_iter()
+                                    .filter(|(_, _, inner_public)| *inner_public)
+                                    .map(|(name, id, _)| (name, id, public)),
+                            );
+                        }
+                    } else if let Some(name) = import.get("name").and_then(Value::as_str) {
+                        entries.push((name.to_string(), target, public));
+                    }
+                }
+                Some(_) => {
+                    if let Some(name) = item.get("name").and_then(Value::as_str) {
+                        entries.push((name.to_string(), id, public));
+                    }
+                }
+                None => {}
+            }
+        }
+        entries
+    }
+
+    /// The importable item for an id found at `path`, if it is a kind of item that can be imported
+    fn importable_item(
+        &self,
+        id: &str,
+        name: String,
+        path: &[String],
+        local: bool,
+        public: bool,
+    ) -> Option<ImportableItem> {
+        let defined = self.index.get(id);
+        let kind = match defined {
+            Some(item) => inner(item).and_then(|(kind, details)| {
+                // Proc macros share one item kind, told apart by their details
+                match details.get("kind").and_then(Value::as_str) {
+                    Some("derive") if kind == "proc_macro" => item_kind("proc_derive"),
+                    Some("attr") if kind == "proc_macro" => item_kind("proc_attribute"),
+                    _ => item_kind(kind),
+                }
+            })?,
+            // Re-exported from another crate: only the summary in `paths` is available
+            None => self
+                .paths
+                .get(id)
+                .and_then(|summary| summary.get("kind"))
+                .and_then(Value::as_str)
+                .and_then(item_kind)?,
+        };
+
+        let source = if local {
+            ItemSource::Local {
+                module_path: path[..path.len() - 1].join("::"),
+            }
+        } else {
+            match self.name.as_str() {
+                "std" | "alloc" => ItemSource::Std,
+                "core" => ItemSource::Core,
+                _ => ItemSource::External {
+                    crate_name: self.name.clone(),
+                },
+            }
+        };
+
+        Some(ImportableItem {
+            full_path: path.join("::"),
+            name,
+            kind,
+            source,
+            visibility: if public {
+                ItemVisibility::Public
+            } else {
+                defined.map_or(ItemVisibility::Private, visibility)
+            },
+            docs: defined
+                .and_then(|item| item.get("docs"))
+                .and_then(Value::as_str)
+                .and_then(first_paragraph),
+            is_macro: kind.is_macro(),
+            deprecation: defined.and_then(deprecation),
+            unstable: defined.is_some_and(is_unstable),
+            trait_info: defined.and_then(|item| self.trait_info(item)),
+        })
+    }
+
+    /// Methods of a trait and the types this crate implements it for
+    fn trait_info(&self, item: &Value) -> Option<TraitInfo> {
+        let (_, details) = inner(item).filter(|(kind, _)| *kind == "trait")?;
+        let ids = |key: &str| {
+            details
+                .get(key)
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(id_key)
+                .filter_map(|id| self.index.get(&id))
+        };
+
+        let methods = ids("items")
+            .filter(|method| inner(method).is_some_and(|(kind, _)| kind == "function"))
+            .filter_map(|method| method.get("name").and_then(Value::as_str))
+            .map(str::to_string)
+            .collect();
+        let mut implemented_for: Vec<String> = Vec::new();
+        for imp in ids("implementations") {
+            let key = inner(imp)
+                .and_then(|(_, imp)| imp.get("for"))
+                .and_then(type_key);
+            if let Some(key) = key {
+                if !implemented_for.contains(&key) {
+                    implemented_for.push(key);
+                }
+            }
+        }
+        implemented_for.sort();
+        Some(TraitInfo {
+            methods,
+            implemented_for,
+        })
+    }
+
+    /// Whether an id is a module of this crate
+    fn is_module(&self, id: &str) -> bool {
+        self.index
+            .get(id)
+            .and_then(|item| inner(item))
+            .is_some_and(|(kind, _)| kind == "module")
+    }
+}
+
+// Helper functions
+
+/// Ids are strings in older format versions and integers in newer ones
+fn id_key(id: &Value) -> Option<String> {
+    match id {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// Remove an object-valued field from the document, empty when missing
+fn take_object(doc: &mut Value, key: &str) -> Map<String, Value> {
+    match doc.get_mut(key).map(Value::take) {
+        Some(Value::Object(map)) => map,
+        _ => Map::new(),
+    }
+}
+
+/// Kind of an item and its details, e.g. `("struct", {...})`
+fn inner(item: &Value) -> Option<(&str, &Value)> {
+    let (kind, inner) = item.get("inner")?.as_object()?.iter().next()?;
+    Some((kind.as_str(), inner))
+}
+
+/// Item kind for a rustdoc kind name, `None` for things that cannot be imported
+fn item_kind(kind: &str) -> Option<ItemKind> {
+    Some(match kind {
+        "module" => ItemKind::Module,
+        "struct" => ItemKind::Struct,
+        "enum" => ItemKind::Enum,
+        "union" => ItemKind::Union,
+        "trait" | "trait_alias" => ItemKind::Trait,
+        "function" => ItemKind::Function,
+        "type_alias" | "typedef" => ItemKind::TypeAlias,
+        "constant" => ItemKind::Constant,
+        "static" => ItemKind::Static,
+        "macro" | "proc_macro" => ItemKind::Macro,
+        "proc_derive" => ItemKind::DeriveMacro,
+        "proc_attribute" => ItemKind::AttributeMacro,
+        _ => return None,
+    })
+}
+
+/// The `type_key` of a rustdoc type: `_` for a type parameter, the last path segment otherwise
+fn type_key(ty: &Value) -> Option<String> {
+    let (kind, details) = ty.as_object()?.iter().next()?;
+    match kind.as_str() {
+        "resolved_path" => {
+            let path = details.get("path").and_then(Value::as_str)?;
+            path.rsplit("::").next().map(str::to_string)
+        }
+        "generic" => Some("_".to_string()),
+        "primitive" => details.as_str().map(str::to_string),
+        "borrowed_ref" => details.get("type").and_then(type_key),
+        "slice" | "array" => Some("[]".to_string()),
+        "tuple" if details.as_array().is_some_and(Vec::is_empty) => Some("()".to_string()),
+        _ => None,
+    }
+}
+
+fn deprecation(item: &Value) -> Option<Deprecation> {
+    let deprecation = item.get("deprecation")?.as_object()?;
+    let field = |key: &str| {
+        deprecation
+            .get(key)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    Some(Deprecation {
+        since: field("since"),
+        note: field("note"),
+    })
+}
+
+/// Declared visibility of an item; restricted paths are written from `crate`
+fn visibility(item: &Value) -> ItemVisibility {
+    match item.get("visibility") {
+        Some(Value::String(vis)) => match vis.as_str() {
+            "public" => ItemVisibility::Public,
+            "crate" => ItemVisibility::Crate,
+            _ => ItemVisibility::Private,
+        },
+        Some(Value::Object(vis)) => {
+            let path = vis
+                .get("restricted")
+                .and_then(|restricted| restricted.get("path"))
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            ItemVisibility::Restricted {
+                path: format!("crate{}", path.trim_start_matches("crate")),
+            }
+        }
+        _ => ItemVisibility::Private,
+    }
+}
+
+/// Whether an item is marked `#[unstable]` (only possible in crates using `staged_api`)
+fn is_unstable(item: &Value) -> bool {
+    match item
+        .get("stability")
+        .and_then(|stability| stability.get("level"))
+    {
+        Some(Value::String(level)) => level == "unstable",
+        Some(Value::Object(level)) => level.contains_key("unstable"),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/rustdoc")
+            .join(name)
+    }
+
+    #[test]
+    fn test_rustdoc_items_use_exact_paths() -> Result<()> {
+        let krate = RustdocCrate::load(&fixture("mini.json"))?;
+        assert_eq!(krate.name, "mini");
+
+        let items = krate.items(false);
+        let find = |path: &str| items.iter().find(|item| item.full_path == path);
+
+        // Each name an item is re-exported under gets its shortest path
+        let map = find("mini::Map").expect("renamed re-export");
+        assert_eq!(map.kind, ItemKind::Struct);
+        assert_eq!(map.docs.as_deref(), Some("A hash map."));
+        assert!(find("mini::collections::HashMap").is_some());
+        assert!(find("mini::collections::hash_map::HashMap").is_none());
+        assert!(find("mini::collections::hash_map::Entry").is_some());
+
+        // Glob re-exports of a private module
+        assert!(find("mini::parse_str").is_some());
+        assert_eq!(find("mini::Result").unwrap().kind, ItemKind::TypeAlias);
+        assert!(items.iter().all(|item| !item.full_path.contains("detail")));
+        assert!(items.iter().all(|item| item.name != "Internal"));
+
+        assert!(find("mini::format_value").is_some_and(|item| item.is_macro));
+        let parse = find("mini::parse").unwrap();
+        assert_eq!(parse.docs.as_deref(), Some("Parse a value."));
+        assert_eq!(
+            parse.deprecation,
+            Some(Deprecation {
+                since: Some("0.2.0".to_string()),
+                note: Some("use `parse_str` instead".to_string()),
+            })
+        );
+        assert!(
+            matches!(&parse.source, ItemSource::External { crate_name } if crate_name == "mini")
+        );
+
+        let emit = find("mini::Emit").unwrap().trait_info.clone().unwrap();
+        assert_eq!(emit.methods, vec!["emit", "emit_len"]);
+        assert_eq!(emit.implemented_for, vec!["Map", "str"]);
+        let blanket = find("mini::EmitExt").unwrap().trait_info.clone().unwrap();
+        assert_eq!(blanket.implemented_for, vec!["_"]);
+        assert!(find("mini::Map").unwrap().trait_info.is_none());
+
+        let local = krate.items(true);
+        let hash_map = local
+            .iter()
+            .find(|item| item.full_path == "crate::collections::HashMap")
+            .unwrap();
+        assert!(
+            matches!(&hash_map.source, ItemSource::Local { module_path } if module_path == "crate::collections")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rustdoc_stability_and_foreign_reexports() -> Result<()> {
+        let items = RustdocCrate::load(&fixture("dep.json"))?.items(false);
+        let find = |path: &str| items.iter().find(|item| item.full_path == path).unwrap();
+
+        assert!(!find("dep::Stable").unstable);
+        assert!(find("dep::Experimental").unstable);
+
+        // Re-exported from std, known only through the `paths` summary
+        let set = find("dep::HashSet");
+        assert_eq!(set.kind, ItemKind::Struct);
+        assert!(set.docs.is_none());
+
+        assert!(RustdocCrate::parse("{\"index\": {}}").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,307 @@
+//! Standard library catalog for Rusty Refactor
+//!
+//! This module indexes `std`, `alloc` and `core` from the toolchain's `rust-src` component
+//! and lists every public, stable item under the path users are expected to import it by,
+//! caching the result per toolchain.
+
+use anyhow::{anyhow, Context, Result};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::any::Any;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::crate_index::CrateIndex;
+use crate::reexports::{CrateGraph, DefId, PathFilter};
+use crate::transaction::write_atomic;
+use crate::{ImportableItem, ItemSource, ItemVisibility};
+
+/// Library crates in order of path preference: a `core` item re-exported by `std` is
+/// listed under its `std` path
+const LIBRARY_CRATES: [&str; 3] = ["std", "alloc", "core"];
+
+/// Version of the cached item format, hashed into cache file names so older caches are rebuilt
+pub(crate) const CACHE_VERSION: u32 = 4;
+
+/// Values already loaded by this process, keyed by cache file
+static LOADED: Lazy<Mutex<HashMap<PathBuf, Box<dyn Any + Send>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// The `library` directory of the installed `rust-src` component
+///
+/// Checks `RUST_SRC_PATH` at runtime (either the `rust` source root or its `library`
+/// directory, as rust-analyzer expects), then the path found by `build.rs`, then the
+/// active toolchain's sysroot.
+pub fn library_path() -> Option<PathBuf> {
+    static FOUND: Lazy<Option<PathBuf>> = Lazy::new(|| {
+        let sysroot = Command::new("rustc")
+            .args(["--print", "sysroot"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| {
+                PathBuf::from(String::from_utf8_lossy(&output.stdout).trim())
+                    .join("lib/rustlib/src/rust")
+            });
+
+        std::env::var_os("RUST_SRC_PATH")
+            .map(PathBuf::from)
+            .into_iter()
+            .chain(option_env!("RUST_SRC_PATH").map(PathBuf::from))
+            .chain(sysroot)
+            .flat_map(|path| [path.join("library"), path])
+            .find(|path| path.join("std/src/lib.rs").is_file())
+    });
+    FOUND.clone()
+}
+
+/// Per-user directory the catalog is cached in
+pub fn cache_dir() -> PathBuf {
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("rusty-refactor")
+}
+
+/// Load the catalog for a toolchain from `cache_dir`, building and caching it when missing
+pub fn load_catalog(
+    library: &Path,
+    cache_dir: &Path,
+    toolchain: &str,
+) -> Result<Vec<ImportableItem>> {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    CACHE_VERSION.hash(&mut hasher);
+    toolchain.hash(&mut hasher);
+    library.hash(&mut hasher);
+    let cache_file = cache_dir.join(format!("std-catalog-{:016x}.json", hasher.finish()));
+
+    cached(&cache_file, || build_catalog(library))
+}
+
+/// Value (an item list, say) cached in `cache_file`, built and written there when the file is
+/// missing or unreadable
+pub(crate) fn cached<T>(cache_file: &Path, build: impl FnOnce() -> Result<T>) -> Result<T>
+where
+    T: Serialize + DeserializeOwned + Clone + Send + 'static,
+{
+    if let Some(value) = LOADED
+        .lock()
+        .get(cache_file)
+        .and_then(|loaded| loaded.downcast_ref::<T>())
+    {
+        return Ok(value.clone());
+    }
+
+    let cached = std::fs::read(cache_file)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<T>(&bytes).ok());
+    let value = match cached {
+        Some(value) => value,
+        None => {
+            let value = build()?;
+            let json = serde_json::to_string(&value)?;
+            write_atomic(cache_file, &json).with_context(|| {
+                format!("Failed to write the item cache {}", cache_file.display())
+            })?;
+            value
+        }
+    };
+
+    LOADED
+        .lock()
+        .insert(cache_file.to_path_buf(), Box::new(value.clone()));
+    Ok(value)
+}
+
+/// Index `std`, `alloc` and `core` below `library` and list their public, stable items
+///
+/// Each item appears once, under the shortest path from the most preferred crate that
+/// exposes it, e.g. `std::collections::HashMap` rather than
+/// `std::collections::hash::map::HashMap` or an `alloc` path.
+pub fn build_catalog(library: &Path) -> Result<Vec<ImportableItem>> {
+    let mut indexes = Vec::new();
+    for name in LIBRARY_CRATES {
+        let root = library.join(name).join("src/lib.rs");
+        if root.is_file() {
+            indexes.push((name.to_string(), CrateIndex::build(&root)?));
+        }
+    }
+    if indexes.is_empty() {
+        return Err(anyhow!(
+            "No standard library sources in {}",
+            library.display()
+        ));
+    }
+
+    let graph = CrateGraph::new(
+        indexes
+            .iter()
+            .map(|(name, index)| (name.clone(), index))
+            .collect(),
+    );
+    let filter = PathFilter {
+        crate_visible: false,
+        stable_only: true,
+        from: None,
+    };
+    let mut preferred: HashMap<DefId, Vec<String>> = HashMap::new();
+    for root in 0..indexes.len() {
+        for (def, paths) in graph.public_paths(root, filter) {
+            if let Some(shortest) = paths.into_iter().next() {
+                preferred.entry(def).or_insert(shortest);
+            }
+        }
+    }
+
+    let traits = graph.trait_infos();
+    let mut items: Vec<ImportableItem> = preferred
+        .into_iter()
+        .map(|((krate, i), path)| {
+            let item = &indexes[krate].1.items[i];
+            let source = if path[0] == "core" {
+                ItemSource::Core
+            } else {
+                ItemSource::Std
+            };
+            ImportableItem {
+                full_path: path.join("::"),
+                name: path.last().cloned().unwrap_or_default(),
+                kind: item.kind,
+                source,
+                visibility: ItemVisibility::Public,
+                docs: item.docs.clone(),
+                is_macro: item.kind.is_macro(),
+                deprecation: None,
+                unstable: false,
+                trait_info: traits.get(&(krate, i)).cloned(),
+            }
+        })
+        .collect();
+    items.sort_by(|a, b| a.full_path.cmp(&b.full_path));
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::write;
+    use crate::ItemKind;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// A miniature `library` directory shaped like the real one
+    fn library() -> Result<TempDir> {
+        let dir = TempDir::new()?;
+        let root = dir.path();
+        write(
+            root,
+            "core/src/lib.rs",
+            "#[stable(feature = \"rust1\", since = \"1.0.0\")]\npub mod option;\n\
+             #[unstable(feature = \"core_intrinsics\", issue = \"none\")]\npub mod intrinsics;\n\
+             #[macro_export]\n#[stable(feature = \"rust1\", since = \"1.0.0\")]\n\
+             macro_rules! assert_eq { () => {}; }\n",
+        )?;
+        write(
+            root,
+            "core/src/option.rs",
+            "/// The `Option` type.\n///\n/// See the module docs.\n\
+             #[stable(feature = \"rust1\", since = \"1.0.0\")]\npub enum Option<T> { None, Some(T) }\n",
+        )?;
+        write(
+            root,
+            "core/src/intrinsics.rs",
+            "pub fn abort() {}\n#[stable(feature = \"rust1\", since = \"1.0.0\")]\npub fn transmute() {}\n",
+        )?;
+        write(
+            root,
+            "alloc/src/lib.rs",
+            "#[stable(feature = \"rust1\", since = \"1.0.0\")]\npub mod vec;\n",
+        )?;
+        write(
+            root,
+            "alloc/src/vec.rs",
+            "#[stable(feature = \"rust1\", since = \"1.0.0\")]\npub struct Vec<T>(T);\n",
+        )?;
+        write(
+            root,
+            "std/src/lib.rs",
+            "extern crate alloc as alloc_crate;\n\
+             #[stable(feature = \"rust1\", since = \"1.0.0\")]\npub use core::option;\n\
+             #[stable(feature = \"rust1\", since = \"1.0.0\")]\npub use alloc_crate::vec;\n\
+             #[stable(feature = \"rust1\", since = \"1.0.0\")]\npub use core::assert_eq;\n\
+             #[stable(feature = \"rust1\", since = \"1.0.0\")]\npub mod collections;\n\
+             #[stable(feature = \"rust1\", since = \"1.0.0\")]\npub mod mem {\n    \
+             #[stable(feature = \"rust1\", since = \"1.0.0\")]\n    pub use core::intrinsics::transmute;\n}\n",
+        )?;
+        write(
+            root,
+            "std/src/collections/mod.rs",
+            "mod hash;\n#[stable(feature = \"rust1\", since = \"1.0.0\")]\npub mod hash_map {\n    \
+             #[stable(feature = \"rust1\", since = \"1.0.0\")]\n    pub use super::hash::map::*;\n}\n\
+             #[stable(feature = \"rust1\", since = \"1.0.0\")]\npub use self::hash_map::HashMap;\n",
+        )?;
+        write(
+            root,
+            "std/src/collections/hash/mod.rs",
+            "pub(super) mod map;\n",
+        )?;
+        write(
+            root,
+            "std/src/collections/hash/map.rs",
+            "#[stable(feature = \"rust1\", since = \"1.0.0\")]\npub struct HashMap;\n\
+             #[unstable(feature = \"hash_raw_entry\", issue = \"56167\")]\npub struct RawEntryMut;\n",
+        )?;
+        Ok(dir)
+    }
+
+    #[test]
+    fn test_catalog_lists_stable_items_under_preferred_paths() -> Result<()> {
+        let library = library()?;
+        let items = build_catalog(library.path())?;
+        let find = |path: &str| items.iter().find(|item| item.full_path == path);
+
+        assert!(find("std::collections::HashMap").is_some());
+        assert!(items.iter().filter(|item| item.name == "HashMap").count() == 1);
+
+        let option = find("std::option::Option").expect("core items use their std path");
+        assert_eq!(option.kind, ItemKind::Enum);
+        assert_eq!(option.docs.as_deref(), Some("The `Option` type."));
+        assert!(matches!(option.source, ItemSource::Std));
+        assert!(find("std::vec::Vec").is_some());
+        assert!(find("std::assert_eq").is_some_and(|item| item.is_macro));
+
+        // Unstable items and modules are left out, but stable re-exports of them are kept
+        assert!(items
+            .iter()
+            .all(|item| item.name != "RawEntryMut" && item.name != "abort"));
+        assert!(find("std::mem::transmute").is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_catalog_is_cached_per_toolchain() -> Result<()> {
+        let library = library()?;
+        let cache = TempDir::new()?;
+
+        let first = load_catalog(library.path(), cache.path(), "rustc 1.80.0")?;
+        assert_eq!(fs::read_dir(cache.path())?.count(), 1);
+
+        // Later loads come from the cache even when the sources are gone
+        fs::remove_dir_all(library.path().join("std"))?;
+        let second = load_catalog(library.path(), cache.path(), "rustc 1.80.0")?;
+        assert_eq!(first.len(), second.len());
+
+        assert!(load_catalog(library.path(), cache.path(), "rustc 1.81.0")?
+            .iter()
+            .all(|item| !item.full_path.starts_with("std::")));
+        assert_eq!(fs::read_dir(cache.path())?.count(), 2);
+
+        Ok(())
+    }
+}
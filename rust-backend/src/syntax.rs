@@ -0,0 +1,1291 @@
+//! Syntax analysis for Rusty Refactor
+//!
+//! This module parses Rust source files with `syn` and turns the syntax tree into the
+//! span-annotated records (`FunctionInfo`, `ParamInfo`, ...) that are exposed over N-API.
+
+use anyhow::{anyhow, Result};
+use proc_macro2::Span;
+use std::ops::Range;
+use std::path::Path;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use syn::{
+    Arm, BinOp, Block, Expr, ExprCall, ExprClosure, ExprForLoop, ExprIf, ExprPath, ExprStruct,
+    ExprWhile, FnArg, Generics, ImplItemFn, Item, ItemEnum, ItemExternCrate, ItemFn, ItemImpl,
+    ItemMod, ItemStruct, ItemTrait, ItemType, ItemUnion, ItemUse, Pat, PatIdent, PatStruct,
+    PatTupleStruct, Receiver, ReturnType, Signature, Stmt, Token, TraitBound, TraitItemFn, Type,
+    TypePath, UseTree, Visibility,
+};
+
+use crate::{
+    FunctionInfo, ImplContext, ImportInfo, ItemKind, NamePosition, ParamInfo, SpanInfo, TypeInfo,
+    UnresolvedName,
+};
+
+/// A parsed Rust source file together with its original text
+pub struct SourceFile {
+    /// Original source text
+    pub text: String,
+    /// Parsed syntax tree
+    pub ast: syn::File,
+    /// Byte offset of the parsed content (skips a BOM or shebang line)
+    offset: usize,
+}
+
+impl SourceFile {
+    /// Parse Rust source text
+    pub fn parse(text: impl Into<String>) -> Result<Self> {
+        let text = text.into();
+        let ast = syn::parse_file(&text).map_err(|e| {
+            let start = e.span().start();
+            anyhow!("{}:{}: {}", start.line, start.column + 1, e)
+        })?;
+
+        let bom = if text.starts_with('\u{feff}') { 3 } else { 0 };
+        let offset = bom + ast.shebang.as_ref().map_or(0, |s| s.len());
+
+        Ok(Self { text, ast, offset })
+    }
+
+    /// Read and parse a Rust source file from disk
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let text = std::fs::read_to_string(path.as_ref())?;
+        Self::parse(text)
+    }
+
+    /// Get the byte range of a span within `text`
+    pub fn byte_range(&self, span: Span) -> Range<usize> {
+        let range = span.byte_range();
+        let start = (range.start + self.offset).min(self.text.len());
+        let end = (range.end + self.offset).clamp(start, self.text.len());
+        start..end
+    }
+
+    /// Get the source text covered by a span
+    pub fn snippet(&self, span: Span) -> &str {
+        &self.text[self.byte_range(span)]
+    }
+
+    /// Get the leading whitespace of the line containing a byte offset
+    pub fn line_indent(&self, offset: usize) -> &str {
+        let line_start = self.text[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line = &self.text[line_start..];
+        &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+    }
+
+    /// Collect every free function, inherent method and trait method in the file
+    pub fn functions(&self) -> Vec<FunctionInfo> {
+        let mut collector = FunctionCollector {
+            file: self,
+            impl_context: None,
+            functions: Vec::new(),
+        };
+        collector.visit_file(&self.ast);
+        collector.functions
+    }
+
+    /// Find the innermost function enclosing a 1-based line/column position
+    pub fn function_at(&self, line: u32, column: u32) -> Option<FunctionInfo> {
+        self.functions()
+            .into_iter()
+            .filter(|f| {
+                f.span
+                    .as_ref()
+                    .is_some_and(|span| span_contains(span, line, column))
+            })
+            .max_by_key(|f| {
+                f.span
+                    .as_ref()
+                    .map(|span| (span.line_start, span.column_start))
+            })
+    }
+
+    /// Find the name at a 1-based line/column position and how it is used there
+    ///
+    /// Each segment of a path counts as a name: `io` in `io::Result<()>` qualifies a path,
+    /// while `Result` is used as a type.
+    pub fn name_at(&self, line: u32, column: u32) -> Option<UnresolvedName> {
+        let mut finder = NameFinder {
+            line,
+            column,
+            found: None,
+        };
+        finder.visit_file(&self.ast);
+        finder.found
+    }
+
+    /// Collect every `use` declaration and `extern crate`, one entry per imported path
+    ///
+    /// Declarations inside inline modules and function bodies are included. Spans are those
+    /// of `flatten_use_spanned`; re-exports carry their visibility.
+    pub fn imports(&self) -> Vec<ImportInfo> {
+        let mut collector = ImportCollector {
+            file: self,
+            imports: Vec::new(),
+        };
+        collector.visit_file(&self.ast);
+        collector.imports
+    }
+
+    /// Collect the names a 1-based line/column position sees through the syntax around it
+    ///
+    /// Covers what one file can tell: the `use` declarations of the enclosing module and
+    /// blocks, items declared in enclosing blocks, generic parameters and local bindings.
+    /// Items of the module itself are left to the crate index.
+    pub fn scope_at(&self, line: u32, column: u32) -> LexicalScope {
+        let mut finder = ScopeFinder {
+            line,
+            column,
+            scope: LexicalScope::default(),
+        };
+        finder.declare_uses(&self.ast.items);
+        finder.visit_file(&self.ast);
+        finder.scope
+    }
+
+    /// Collect every struct, enum, trait, union and type alias, including those in inline modules
+    pub fn types(&self) -> Vec<TypeInfo> {
+        let mut collector = TypeCollector {
+            file: self,
+            module_path: Vec::new(),
+            types: Vec::new(),
+        };
+        collector.visit_file(&self.ast);
+        collector.types
+    }
+
+    /// Build a `FunctionInfo` for a signature, using `item_span` for the whole item
+    pub fn function_info(
+        &self,
+        item_span: Span,
+        sig: &Signature,
+        impl_context: Option<ImplContext>,
+    ) -> FunctionInfo {
+        FunctionInfo {
+            name: sig.ident.to_string(),
+            span: Some(span_info(item_span)),
+            signature: self.snippet(sig.span()).to_string(),
+            params: sig.inputs.iter().map(|arg| self.param_info(arg)).collect(),
+            return_type: match &sig.output {
+                ReturnType::Default => "()".to_string(),
+                ReturnType::Type(_, ty) => self.snippet(ty.span()).to_string(),
+            },
+            is_unsafe: sig.unsafety.is_some(),
+            is_async: sig.asyncness.is_some(),
+            impl_context,
+            self_kind: self_kind(sig).to_string(),
+        }
+    }
+
+    /// Describe an `impl` block for the methods it contains
+    pub fn impl_context(&self, node: &ItemImpl) -> ImplContext {
+        let start = node.unsafety.map_or(node.impl_token.span, |u| u.span);
+        ImplContext {
+            kind: "impl".to_string(),
+            target_type: self.snippet(node.self_ty.span()).to_string(),
+            trait_name: node
+                .trait_
+                .as_ref()
+                .map(|(_, path, _)| self.snippet(path.span()).to_string()),
+            header: self
+                .snippet_between(start, node.brace_token.span.open())
+                .to_string(),
+            span: Some(span_info(node.span())),
+        }
+    }
+
+    /// Describe a trait definition for the methods it declares
+    pub fn trait_context(&self, node: &ItemTrait) -> ImplContext {
+        let start = node
+            .unsafety
+            .map(|u| u.span)
+            .or(node.auto_token.map(|a| a.span))
+            .unwrap_or(node.trait_token.span);
+        ImplContext {
+            kind: "trait".to_string(),
+            target_type: node.ident.to_string(),
+            trait_name: None,
+            header: self
+                .snippet_between(start, node.brace_token.span.open())
+                .to_string(),
+            span: Some(span_info(node.span())),
+        }
+    }
+
+    /// Get the trimmed source text from the start of one span to the start of another
+    fn snippet_between(&self, start: Span, end: Span) -> &str {
+        let start = self.byte_range(start).start;
+        let end = self.byte_range(end).start.max(start);
+        self.text[start..end].trim()
+    }
+
+    fn param_info(&self, arg: &FnArg) -> ParamInfo {
+        match arg {
+            FnArg::Receiver(receiver) => ParamInfo {
+                name: "self".to_string(),
+                ty: self.receiver_type(receiver),
+                span: Some(span_info(arg.span())),
+            },
+            FnArg::Typed(pat_type) => ParamInfo {
+                name: self.snippet(pat_type.pat.span()).to_string(),
+                ty: self.snippet(pat_type.ty.span()).to_string(),
+                span: Some(span_info(arg.span())),
+            },
+        }
+    }
+
+    fn receiver_type(&self, receiver: &Receiver) -> String {
+        // `self: Box<Self>` spells out its type, shorthand receivers do not
+        if receiver.colon_token.is_some() {
+            return self.snippet(receiver.ty.span()).to_string();
+        }
+
+        match &receiver.reference {
+            Some((_, lifetime)) => format!(
+                "&{}{}Self",
+                lifetime
+                    .as_ref()
+                    .map(|l| format!("{} ", l))
+                    .unwrap_or_default(),
+                if receiver.mutability.is_some() {
+                    "mut "
+                } else {
+                    ""
+                },
+            ),
+            None => "Self".to_string(),
+        }
+    }
+}
+
+/// Visitor that records every type-like item and the inline module it lives in
+struct TypeCollector<'a> {
+    file: &'a SourceFile,
+    module_path: Vec<String>,
+    types: Vec<TypeInfo>,
+}
+
+impl TypeCollector<'_> {
+    fn push(&mut self, kind: &str, ident: &syn::Ident, generics: &Generics, item_span: Span) {
+        self.types.push(TypeInfo {
+            name: ident.to_string(),
+            span: Some(span_info(item_span)),
+            definition: self.file.snippet(item_span).to_string(),
+            kind: kind.to_string(),
+            generics: generics
+                .params
+                .iter()
+                .map(|param| self.file.snippet(param.span()).to_string())
+                .collect(),
+            module_path: self.module_path.join("::"),
+        });
+    }
+}
+
+impl<'ast> Visit<'ast> for TypeCollector<'_> {
+    fn visit_item_mod(&mut self, node: &'ast ItemMod) {
+        self.module_path.push(node.ident.to_string());
+        visit::visit_item_mod(self, node);
+        self.module_path.pop();
+    }
+
+    fn visit_item_struct(&mut self, node: &'ast ItemStruct) {
+        self.push("struct", &node.ident, &node.generics, node.span());
+        visit::visit_item_struct(self, node);
+    }
+
+    fn visit_item_enum(&mut self, node: &'ast ItemEnum) {
+        self.push("enum", &node.ident, &node.generics, node.span());
+        visit::visit_item_enum(self, node);
+    }
+
+    fn visit_item_trait(&mut self, node: &'ast ItemTrait) {
+        self.push("trait", &node.ident, &node.generics, node.span());
+        visit::visit_item_trait(self, node);
+    }
+
+    fn visit_item_union(&mut self, node: &'ast ItemUnion) {
+        self.push("union", &node.ident, &node.generics, node.span());
+        visit::visit_item_union(self, node);
+    }
+
+    fn visit_item_type(&mut self, node: &'ast ItemType) {
+        self.push("type_alias", &node.ident, &node.generics, node.span());
+        visit::visit_item_type(self, node);
+    }
+}
+
+/// Visitor that finds the path segment at a position and classifies its syntactic role
+struct NameFinder {
+    line: u32,
+    column: u32,
+    found: Option<UnresolvedName>,
+}
+
+impl NameFinder {
+    /// Check a path's segments: the last one is used as `position`, earlier ones qualify it
+    fn check_path(&mut self, path: &syn::Path, position: NamePosition) {
+        let last = path.segments.len().saturating_sub(1);
+        for (i, segment) in path.segments.iter().enumerate() {
+            let position = if i == last {
+                position
+            } else {
+                NamePosition::PathPrefix
+            };
+            self.check_ident(&segment.ident, position);
+        }
+    }
+
+    fn check_ident(&mut self, ident: &syn::Ident, position: NamePosition) {
+        if self.found.is_none() && span_contains(&span_info(ident.span()), self.line, self.column) {
+            self.found = Some(UnresolvedName {
+                name: ident.to_string(),
+                position,
+            });
+        }
+    }
+}
+
+// The role is recorded before visiting children, so a path checked by a more specific
+// visit (e.g. the callee of a call) keeps that role when it is visited again as a plain path.
+impl<'ast> Visit<'ast> for NameFinder {
+    fn visit_type_path(&mut self, node: &'ast TypePath) {
+        self.check_path(&node.path, NamePosition::Type);
+        visit::visit_type_path(self, node);
+    }
+
+    fn visit_trait_bound(&mut self, node: &'ast TraitBound) {
+        self.check_path(&node.path, NamePosition::TraitBound);
+        visit::visit_trait_bound(self, node);
+    }
+
+    fn visit_item_impl(&mut self, node: &'ast ItemImpl) {
+        if let Some((_, path, _)) = &node.trait_ {
+            self.check_path(path, NamePosition::TraitBound);
+        }
+        visit::visit_item_impl(self, node);
+    }
+
+    fn visit_expr_call(&mut self, node: &'ast ExprCall) {
+        if let Expr::Path(func) = node.func.as_ref() {
+            self.check_path(&func.path, NamePosition::Call);
+        }
+        visit::visit_expr_call(self, node);
+    }
+
+    fn visit_expr_path(&mut self, node: &'ast ExprPath) {
+        // A value: constant, static, unit struct or function reference
+        self.check_path(&node.path, NamePosition::Any);
+        visit::visit_expr_path(self, node);
+    }
+
+    fn visit_expr_struct(&mut self, node: &'ast ExprStruct) {
+        self.check_path(&node.path, NamePosition::Type);
+        visit::visit_expr_struct(self, node);
+    }
+
+    fn visit_macro(&mut self, node: &'ast syn::Macro) {
+        self.check_path(&node.path, NamePosition::Macro);
+        // Arguments of expression-like macros such as `vec!` and `println!`
+        if let Ok(args) = node.parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated) {
+            for arg in &args {
+                self.visit_expr(arg);
+            }
+        }
+        visit::visit_macro(self, node);
+    }
+
+    fn visit_attribute(&mut self, node: &'ast syn::Attribute) {
+        self.check_path(node.path(), NamePosition::Attribute);
+        if node.path().is_ident("derive") {
+            let derives =
+                node.parse_args_with(Punctuated::<syn::Path, Token![,]>::parse_terminated);
+            for path in derives.iter().flatten() {
+                self.check_path(path, NamePosition::Derive);
+            }
+        }
+        visit::visit_attribute(self, node);
+    }
+
+    fn visit_pat(&mut self, node: &'ast Pat) {
+        match node {
+            Pat::Path(pat) => self.check_path(&pat.path, NamePosition::Pattern),
+            // A bare name in a pattern may be a unit struct or constant
+            Pat::Ident(pat) if pat.subpat.is_none() => {
+                self.check_ident(&pat.ident, NamePosition::Pattern)
+            }
+            _ => {}
+        }
+        visit::visit_pat(self, node);
+    }
+
+    fn visit_pat_tuple_struct(&mut self, node: &'ast PatTupleStruct) {
+        self.check_path(&node.path, NamePosition::Pattern);
+        visit::visit_pat_tuple_struct(self, node);
+    }
+
+    fn visit_pat_struct(&mut self, node: &'ast PatStruct) {
+        self.check_path(&node.path, NamePosition::Pattern);
+        visit::visit_pat_struct(self, node);
+    }
+}
+
+/// Names visible at a position as far as the surrounding syntax shows, innermost last
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LexicalScope {
+    /// Inline modules enclosing the position, outermost first
+    pub module_path: Vec<String>,
+    /// `use` declarations of the enclosing module and blocks
+    pub imports: Vec<UseLeaf>,
+    /// Items declared in enclosing blocks, e.g. a `fn` nested in a function body
+    pub items: Vec<(String, ItemKind)>,
+    /// Generic parameters of the enclosing items, and `Self` inside `impl` and `trait` blocks
+    pub generics: Vec<String>,
+    /// Parameters, earlier `let` bindings and the bindings of enclosing patterns and closures
+    pub locals: Vec<String>,
+}
+
+/// A single path imported by a `use` declaration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UseLeaf {
+    /// Path segments as written, e.g. `["crate", "cache", "IncrementalCache"]`
+    pub path: Vec<String>,
+    /// Name given with `as`
+    pub alias: Option<String>,
+    /// Whether this is a `path::*` import
+    pub is_glob: bool,
+}
+
+impl UseLeaf {
+    /// The name this import brings into scope (`None` for globs and `as _`)
+    pub fn visible_name(&self) -> Option<&str> {
+        if self.is_glob {
+            return None;
+        }
+        match self.alias.as_deref() {
+            Some("_") => None,
+            Some(alias) => Some(alias),
+            None => self.path.last().map(|s| s.as_str()),
+        }
+    }
+}
+
+impl std::fmt::Display for UseLeaf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path.join("::"))?;
+        if self.is_glob {
+            write!(f, "::*")?;
+        }
+        if let Some(alias) = &self.alias {
+            write!(f, " as {}", alias)?;
+        }
+        Ok(())
+    }
+}
+
+/// Flatten a `use` tree into the individual paths it imports
+pub fn flatten_use(tree: &UseTree) -> Vec<UseLeaf> {
+    flatten_use_spanned(tree)
+        .into_iter()
+        .map(|(leaf, _)| leaf)
+        .collect()
+}
+
+/// Like `flatten_use`, with the span of the largest part of the tree that imports only that
+/// path, as rustc reports unused imports: `std::fmt` in `use std::fmt;`, `Read` in
+/// `use std::io::{Read, Write};`
+pub fn flatten_use_spanned(tree: &UseTree) -> Vec<(UseLeaf, Span)> {
+    fn walk(tree: &UseTree, prefix: &mut Vec<String>, out: &mut Vec<(UseLeaf, Span)>) {
+        let leaf = match tree {
+            UseTree::Path(path) => {
+                let before = out.len();
+                prefix.push(path.ident.to_string());
+                walk(&path.tree, prefix, out);
+                prefix.pop();
+                if out.len() == before + 1 {
+                    out[before].1 = tree.span();
+                }
+                return;
+            }
+            UseTree::Group(group) => {
+                for tree in &group.items {
+                    walk(tree, prefix, out);
+                }
+                return;
+            }
+            // `a::b::{self}` imports `a::b` itself
+            UseTree::Name(name) if name.ident == "self" && !prefix.is_empty() => UseLeaf {
+                path: prefix.clone(),
+                alias: None,
+                is_glob: false,
+            },
+            UseTree::Rename(rename) if rename.ident == "self" && !prefix.is_empty() => UseLeaf {
+                path: prefix.clone(),
+                alias: Some(rename.rename.to_string()),
+                is_glob: false,
+            },
+            UseTree::Name(name) => UseLeaf {
+                path: prefix.iter().cloned().chain([name.ident.to_string()]).collect(),
+                alias: None,
+                is_glob: false,
+            },
+            UseTree::Rename(rename) => UseLeaf {
+                path: prefix.iter().cloned().chain([rename.ident.to_string()]).collect(),
+                alias: Some(rename.rename.to_string()),
+                is_glob: false,
+            },
+            UseTree::Glob(_) => UseLeaf {
+                path: prefix.clone(),
+                alias: None,
+                is_glob: true,
+            },
+        };
+        out.push((leaf, tree.span()));
+    }
+
+    let mut out = Vec::new();
+    walk(tree, &mut Vec::new(), &mut out);
+    out
+}
+
+/// First paragraph of an item's doc comments, joined into one line
+pub fn doc_summary(attrs: &[syn::Attribute]) -> Option<String> {
+    let mut docs = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("doc")) {
+        let syn::Meta::NameValue(meta) = &attr.meta else {
+            continue;
+        };
+        let syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(text),
+            ..
+        }) = &meta.value
+        else {
+            continue;
+        };
+        docs.push(text.value());
+    }
+    first_paragraph(&docs.join("\n"))
+}
+
+/// First paragraph of a doc string, joined into one line
+pub fn first_paragraph(docs: &str) -> Option<String> {
+    let mut lines = Vec::new();
+    for line in docs.lines().map(str::trim) {
+        if line.is_empty() {
+            if lines.is_empty() {
+                continue;
+            }
+            break;
+        }
+        lines.push(line);
+    }
+    (!lines.is_empty()).then(|| lines.join(" "))
+}
+
+/// Classify how a function takes `self`: `self`, `&self`, `&mut self` or `none`
+fn self_kind(sig: &Signature) -> &'static str {
+    match sig.receiver() {
+        None => "none",
+        Some(receiver) => match receiver.ty.as_ref() {
+            Type::Reference(reference) if reference.mutability.is_some() => "&mut self",
+            Type::Reference(_) => "&self",
+            _ => "self",
+        },
+    }
+}
+
+/// Declared name of an item
+pub fn item_name(item: &Item) -> Option<String> {
+    let ident = match item {
+        Item::Fn(item) => &item.sig.ident,
+        Item::Struct(item) => &item.ident,
+        Item::Enum(item) => &item.ident,
+        Item::Union(item) => &item.ident,
+        Item::Trait(item) => &item.ident,
+        Item::TraitAlias(item) => &item.ident,
+        Item::Type(item) => &item.ident,
+        Item::Const(item) => &item.ident,
+        Item::Static(item) => &item.ident,
+        Item::Mod(item) => &item.ident,
+        Item::ExternCrate(item) => &item.ident,
+        Item::Macro(item) => item.ident.as_ref()?,
+        _ => return None,
+    };
+    Some(ident.to_string())
+}
+
+/// Kind of a named item, as `item_name` names it
+pub fn item_kind(item: &Item) -> Option<ItemKind> {
+    Some(match item {
+        Item::Fn(_) => ItemKind::Function,
+        Item::Struct(_) => ItemKind::Struct,
+        Item::Enum(_) => ItemKind::Enum,
+        Item::Union(_) => ItemKind::Union,
+        Item::Trait(_) | Item::TraitAlias(_) => ItemKind::Trait,
+        Item::Type(_) => ItemKind::TypeAlias,
+        Item::Const(_) => ItemKind::Constant,
+        Item::Static(_) => ItemKind::Static,
+        Item::Mod(_) | Item::ExternCrate(_) => ItemKind::Module,
+        Item::Macro(item) if item.ident.is_some() => ItemKind::Macro,
+        _ => return None,
+    })
+}
+
+/// Name a type is matched against impls by: its last path segment, with references and
+/// parentheses stripped, `[]` for slices and arrays and `()` for the unit type
+pub fn type_key(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) => path.path.segments.last().map(|s| s.ident.to_string()),
+        Type::Reference(reference) => type_key(&reference.elem),
+        Type::Paren(paren) => type_key(&paren.elem),
+        Type::Group(group) => type_key(&group.elem),
+        Type::Slice(_) | Type::Array(_) => Some("[]".to_string()),
+        Type::Tuple(tuple) if tuple.elems.is_empty() => Some("()".to_string()),
+        _ => None,
+    }
+}
+
+/// Check whether a 1-based line/column position falls inside a span
+pub fn span_contains(span: &SpanInfo, line: u32, column: u32) -> bool {
+    (span.line_start, span.column_start) <= (line, column)
+        && (line, column) < (span.line_end, span.column_end)
+}
+
+/// Check whether `inner` lies entirely within `outer`
+pub fn span_encloses(outer: &SpanInfo, inner: &SpanInfo) -> bool {
+    (outer.line_start, outer.column_start) <= (inner.line_start, inner.column_start)
+        && (inner.line_end, inner.column_end) <= (outer.line_end, outer.column_end)
+}
+
+/// Convert a span into the 1-based, end-exclusive `SpanInfo` used by rustc diagnostics
+pub fn span_info(span: Span) -> SpanInfo {
+    let start = span.start();
+    let end = span.end();
+    SpanInfo {
+        line_start: start.line as u32,
+        line_end: end.line as u32,
+        column_start: start.column as u32 + 1,
+        column_end: end.column as u32 + 1,
+    }
+}
+
+/// Visitor that records every import it encounters
+struct ImportCollector<'a> {
+    file: &'a SourceFile,
+    imports: Vec<ImportInfo>,
+}
+
+impl ImportCollector<'_> {
+    fn visibility(&self, vis: &Visibility) -> Option<String> {
+        match vis {
+            Visibility::Inherited => None,
+            vis => Some(self.file.snippet(vis.span()).to_string()),
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for ImportCollector<'_> {
+    fn visit_item_use(&mut self, node: &'ast ItemUse) {
+        let visibility = self.visibility(&node.vis);
+        for (leaf, span) in flatten_use_spanned(&node.tree) {
+            self.imports.push(ImportInfo {
+                path: leaf.path.join("::"),
+                alias: leaf.alias,
+                span: Some(span_info(span)),
+                is_glob: leaf.is_glob,
+                confidence: 1.0,
+                visibility: visibility.clone(),
+            });
+        }
+    }
+
+    fn visit_item_extern_crate(&mut self, node: &'ast ItemExternCrate) {
+        let span = match &node.rename {
+            Some((_, rename)) => node.ident.span().join(rename.span()),
+            None => None,
+        };
+        self.imports.push(ImportInfo {
+            path: node.ident.to_string(),
+            alias: node.rename.as_ref().map(|(_, rename)| rename.to_string()),
+            span: Some(span_info(span.unwrap_or(node.ident.span()))),
+            is_glob: false,
+            confidence: 1.0,
+            visibility: self.visibility(&node.vis),
+        });
+    }
+}
+
+/// Visitor that descends only into the nodes enclosing a position, collecting the names
+/// each one brings into scope
+struct ScopeFinder {
+    line: u32,
+    column: u32,
+    scope: LexicalScope,
+}
+
+impl ScopeFinder {
+    fn contains(&self, span: Span) -> bool {
+        span_contains(&span_info(span), self.line, self.column)
+    }
+
+    /// Whether a span ends at or before the position
+    fn precedes(&self, span: Span) -> bool {
+        let end = span.end();
+        (end.line as u32, end.column as u32 + 1) <= (self.line, self.column)
+    }
+
+    fn declare_uses(&mut self, items: &[Item]) {
+        for item in items {
+            if let Item::Use(item_use) = item {
+                self.scope.imports.extend(flatten_use(&item_use.tree));
+            }
+        }
+    }
+
+    fn bind(&mut self, pat: &Pat) {
+        let mut bindings = PatBindings::default();
+        bindings.visit_pat(pat);
+        self.scope.locals.extend(bindings.names);
+    }
+
+    /// Bind the patterns of `let` conditions, including those chained with `&&`
+    fn bind_conditions(&mut self, cond: &Expr) {
+        match cond {
+            Expr::Let(expr_let) => self.bind(&expr_let.pat),
+            Expr::Binary(binary) if matches!(binary.op, BinOp::And(_)) => {
+                self.bind_conditions(&binary.left);
+                self.bind_conditions(&binary.right);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for ScopeFinder {
+    fn visit_item(&mut self, node: &'ast Item) {
+        if !self.contains(node.span()) {
+            return;
+        }
+        // Nested items see neither the locals nor the generic parameters around them
+        self.scope.locals.clear();
+        self.scope.generics.clear();
+        visit::visit_item(self, node);
+    }
+
+    fn visit_item_mod(&mut self, node: &'ast ItemMod) {
+        if let Some((_, items)) = &node.content {
+            self.scope.module_path.push(node.ident.to_string());
+            self.scope.imports.clear();
+            self.scope.items.clear();
+            self.declare_uses(items);
+        }
+        visit::visit_item_mod(self, node);
+    }
+
+    fn visit_item_impl(&mut self, node: &'ast ItemImpl) {
+        self.scope.generics.push("Self".to_string());
+        visit::visit_item_impl(self, node);
+    }
+
+    fn visit_item_trait(&mut self, node: &'ast ItemTrait) {
+        self.scope.generics.push("Self".to_string());
+        visit::visit_item_trait(self, node);
+    }
+
+    fn visit_impl_item(&mut self, node: &'ast syn::ImplItem) {
+        if self.contains(node.span()) {
+            visit::visit_impl_item(self, node);
+        }
+    }
+
+    fn visit_trait_item(&mut self, node: &'ast syn::TraitItem) {
+        if self.contains(node.span()) {
+            visit::visit_trait_item(self, node);
+        }
+    }
+
+    fn visit_generics(&mut self, node: &'ast Generics) {
+        let params = node.type_params().map(|param| &param.ident);
+        let consts = node.const_params().map(|param| &param.ident);
+        self.scope
+            .generics
+            .extend(params.chain(consts).map(|ident| ident.to_string()));
+        visit::visit_generics(self, node);
+    }
+
+    fn visit_fn_arg(&mut self, node: &'ast FnArg) {
+        match node {
+            FnArg::Receiver(_) => self.scope.locals.push("self".to_string()),
+            FnArg::Typed(arg) => self.bind(&arg.pat),
+        }
+        visit::visit_fn_arg(self, node);
+    }
+
+    fn visit_block(&mut self, node: &'ast Block) {
+        if !self.contains(node.span()) {
+            return;
+        }
+        // Items and `use`s of a block are visible throughout it, `let`s only after them
+        for stmt in &node.stmts {
+            if let Stmt::Item(item) = stmt {
+                self.declare_uses(std::slice::from_ref(item));
+                if let (Some(name), Some(kind)) = (item_name(item), item_kind(item)) {
+                    self.scope.items.push((name, kind));
+                }
+            }
+        }
+        for stmt in &node.stmts {
+            if self.contains(stmt.span()) {
+                self.visit_stmt(stmt);
+                break;
+            }
+            match stmt {
+                Stmt::Local(local) if self.precedes(stmt.span()) => self.bind(&local.pat),
+                _ => {}
+            }
+        }
+    }
+
+    fn visit_expr_closure(&mut self, node: &'ast ExprClosure) {
+        if !self.contains(node.span()) {
+            return;
+        }
+        for input in &node.inputs {
+            self.bind(input);
+        }
+        self.visit_expr(&node.body);
+    }
+
+    fn visit_arm(&mut self, node: &'ast Arm) {
+        if !self.contains(node.span()) {
+            return;
+        }
+        if !self.contains(node.pat.span()) {
+            self.bind(&node.pat);
+        }
+        visit::visit_arm(self, node);
+    }
+
+    fn visit_expr_if(&mut self, node: &'ast ExprIf) {
+        if self.contains(node.then_branch.span()) {
+            self.bind_conditions(&node.cond);
+        }
+        visit::visit_expr_if(self, node);
+    }
+
+    fn visit_expr_while(&mut self, node: &'ast ExprWhile) {
+        if self.contains(node.body.span()) {
+            self.bind_conditions(&node.cond);
+        }
+        visit::visit_expr_while(self, node);
+    }
+
+    fn visit_expr_for_loop(&mut self, node: &'ast ExprForLoop) {
+        if self.contains(node.body.span()) {
+            self.bind(&node.pat);
+        }
+        visit::visit_expr_for_loop(self, node);
+    }
+}
+
+/// Visitor that collects the names a pattern binds
+#[derive(Default)]
+struct PatBindings {
+    names: Vec<String>,
+}
+
+impl<'ast> Visit<'ast> for PatBindings {
+    fn visit_pat_ident(&mut self, node: &'ast PatIdent) {
+        // A bare capitalized name such as `None` or `MAX` is a unit variant or constant
+        let capitalized = node.ident.to_string().starts_with(char::is_uppercase);
+        let plain = node.by_ref.is_none() && node.mutability.is_none() && node.subpat.is_none();
+        if !(capitalized && plain) {
+            self.names.push(node.ident.to_string());
+        }
+        visit::visit_pat_ident(self, node);
+    }
+}
+
+/// Visitor that records every function-like item it encounters
+struct FunctionCollector<'a> {
+    file: &'a SourceFile,
+    /// The `impl` or `trait` block currently being visited
+    impl_context: Option<ImplContext>,
+    functions: Vec<FunctionInfo>,
+}
+
+impl FunctionCollector<'_> {
+    /// Visit nested items with a different enclosing block, restoring the current one afterwards
+    fn with_context(&mut self, context: Option<ImplContext>, visit: impl FnOnce(&mut Self)) {
+        let previous = std::mem::replace(&mut self.impl_context, context);
+        visit(self);
+        self.impl_context = previous;
+    }
+}
+
+impl<'ast> Visit<'ast> for FunctionCollector<'_> {
+    fn visit_item_impl(&mut self, node: &'ast ItemImpl) {
+        let context = self.file.impl_context(node);
+        self.with_context(Some(context), |this| visit::visit_item_impl(this, node));
+    }
+
+    fn visit_item_trait(&mut self, node: &'ast ItemTrait) {
+        let context = self.file.trait_context(node);
+        self.with_context(Some(context), |this| visit::visit_item_trait(this, node));
+    }
+
+    fn visit_item_fn(&mut self, node: &'ast ItemFn) {
+        let info = self.file.function_info(node.span(), &node.sig, None);
+        self.functions.push(info);
+        self.with_context(None, |this| visit::visit_item_fn(this, node));
+    }
+
+    fn visit_impl_item_fn(&mut self, node: &'ast ImplItemFn) {
+        let info = self
+            .file
+            .function_info(node.span(), &node.sig, self.impl_context.clone());
+        self.functions.push(info);
+        // Items nested in a method body do not belong to the impl block
+        self.with_context(None, |this| visit::visit_impl_item_fn(this, node));
+    }
+
+    fn visit_trait_item_fn(&mut self, node: &'ast TraitItemFn) {
+        let info = self
+            .file
+            .function_info(node.span(), &node.sig, self.impl_context.clone());
+        self.functions.push(info);
+        self.with_context(None, |this| visit::visit_trait_item_fn(this, node));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+pub async fn fetch(url: &str, retries: u32) -> Result<String, Error> {
+    todo!()
+}
+
+struct Counter { value: u32 }
+
+impl Counter {
+    pub fn increment(&mut self, by: u32) {
+        self.value += by;
+    }
+}
+
+trait Named {
+    unsafe fn name<'a>(&'a self) -> &'a str;
+}
+"#;
+
+    #[test]
+    fn test_functions_cover_free_inherent_and_trait_fns() -> Result<()> {
+        let file = SourceFile::parse(SAMPLE)?;
+        let functions = file.functions();
+
+        let names: Vec<_> = functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["fetch", "increment", "name"]);
+
+        let fetch = &functions[0];
+        assert!(fetch.is_async);
+        assert_eq!(
+            fetch.signature,
+            "async fn fetch(url: &str, retries: u32) -> Result<String, Error>"
+        );
+        assert_eq!(fetch.return_type, "Result<String, Error>");
+        assert_eq!(fetch.params[0].name, "url");
+        assert_eq!(fetch.params[0].ty, "&str");
+        let span = fetch.span.as_ref().unwrap();
+        assert_eq!((span.line_start, span.column_start), (2, 1));
+        assert_eq!((span.line_end, span.column_end), (4, 2));
+
+        let increment = &functions[1];
+        assert_eq!(increment.params[0].ty, "&mut Self");
+        assert_eq!(increment.return_type, "()");
+
+        let name = &functions[2];
+        assert!(name.is_unsafe);
+        assert_eq!(name.params[0].ty, "&'a Self");
+        assert_eq!(name.return_type, "&'a str");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_function_at_reports_impl_context_and_self_kind() -> Result<()> {
+        let file = SourceFile::parse(SAMPLE)?;
+
+        // Inside `self.value += by;`
+        let increment = file.function_at(10, 9).unwrap();
+        assert_eq!(increment.name, "increment");
+        assert_eq!(increment.self_kind, "&mut self");
+        let context = increment.impl_context.unwrap();
+        assert_eq!(context.kind, "impl");
+        assert_eq!(context.target_type, "Counter");
+        assert_eq!(context.header, "impl Counter");
+        assert!(context.trait_name.is_none());
+
+        let name = file.function_at(15, 5).unwrap();
+        assert_eq!(name.self_kind, "&self");
+        assert_eq!(name.impl_context.unwrap().kind, "trait");
+
+        let fetch = file.function_at(3, 5).unwrap();
+        assert_eq!(fetch.self_kind, "none");
+        assert!(fetch.impl_context.is_none());
+
+        // Between items
+        assert!(file.function_at(6, 1).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_function_at_prefers_innermost_function() -> Result<()> {
+        let file = SourceFile::parse(
+            r#"
+impl<T: Clone> Display for Wrapper<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        fn helper(x: u32) -> u32 {
+            x + 1
+        }
+        Ok(())
+    }
+}
+"#,
+        )?;
+
+        let helper = file.function_at(5, 13).unwrap();
+        assert_eq!(helper.name, "helper");
+        assert!(helper.impl_context.is_none());
+
+        let fmt = file.function_at(7, 9).unwrap();
+        let context = fmt.impl_context.unwrap();
+        assert_eq!(context.header, "impl<T: Clone> Display for Wrapper<T>");
+        assert_eq!(context.trait_name.as_deref(), Some("Display"));
+        assert_eq!(context.target_type, "Wrapper<T>");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_types_include_generics_and_inline_modules() -> Result<()> {
+        let file = SourceFile::parse(
+            r#"
+/// A wrapper
+pub struct Wrapper<'a, T: Clone, const N: usize> {
+    items: &'a [T; N],
+}
+
+mod shapes {
+    pub enum Shape { Circle(f64), Square(f64) }
+
+    pub mod raw {
+        pub union Bits { int: u32, float: f32 }
+        pub type Id = u64;
+    }
+}
+
+pub trait Render {}
+"#,
+        )?;
+        let types = file.types();
+
+        let summary: Vec<_> = types
+            .iter()
+            .map(|t| (t.kind.as_str(), t.name.as_str(), t.module_path.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("struct", "Wrapper", ""),
+                ("enum", "Shape", "shapes"),
+                ("union", "Bits", "shapes::raw"),
+                ("type_alias", "Id", "shapes::raw"),
+                ("trait", "Render", ""),
+            ]
+        );
+
+        let wrapper = &types[0];
+        assert_eq!(wrapper.generics, vec!["'a", "T: Clone", "const N: usize"]);
+        assert!(wrapper
+            .definition
+            .starts_with("/// A wrapper\npub struct Wrapper"));
+        assert!(wrapper.definition.ends_with('}'));
+        let span = wrapper.span.as_ref().unwrap();
+        assert_eq!((span.line_start, span.line_end), (2, 5));
+
+        assert_eq!(types[3].definition, "pub type Id = u64;");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_imports_are_flattened_with_spans() -> Result<()> {
+        let text = "extern crate alloc as core_alloc;\nuse std::fmt;\n\
+                    use std::{io::{self, Read as _}, collections::*};\n\
+                    pub(crate) use crate::models::User;\n\
+                    mod inner {\n    use super::Helper;\n}\n";
+        let file = SourceFile::parse(text)?;
+        let imports = file.imports();
+
+        let summary: Vec<_> = imports
+            .iter()
+            .map(|i| {
+                (
+                    i.path.as_str(),
+                    i.alias.as_deref(),
+                    i.is_glob,
+                    i.visibility.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("alloc", Some("core_alloc"), false, None),
+                ("std::fmt", None, false, None),
+                ("std::io", None, false, None),
+                ("std::io::Read", Some("_"), false, None),
+                ("std::collections", None, true, None),
+                ("crate::models::User", None, false, Some("pub(crate)")),
+                ("super::Helper", None, false, None),
+            ]
+        );
+
+        // Each span covers the part of the declaration importing only that path
+        let snippet = |i: usize| {
+            let span = imports[i].span.as_ref().unwrap();
+            let line = text.lines().nth(span.line_start as usize - 1).unwrap();
+            line[span.column_start as usize - 1..span.column_end as usize - 1].to_string()
+        };
+        assert_eq!(snippet(0), "alloc as core_alloc");
+        assert_eq!(snippet(1), "std::fmt");
+        assert_eq!(snippet(2), "self");
+        assert_eq!(snippet(3), "Read as _");
+        assert_eq!(snippet(4), "collections::*");
+        assert_eq!(snippet(6), "super::Helper");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scope_at_collects_bindings_and_imports() -> Result<()> {
+        let text = r#"use std::fmt;
+
+impl<T: Clone> Wrapper<T> {
+    fn visit<const N: usize>(&self, items: &[T]) {
+        use std::io::Write;
+        let (count, mut total) = (0, 0);
+        for item in items {
+            match item.kind() {
+                Some(Kind::Leaf { depth }) => {}
+                None => {}
+            }
+        }
+        let later = 1;
+        fn nested() {}
+    }
+}
+
+mod inner {
+    use super::Helper;
+    fn run() {}
+}
+"#;
+        let file = SourceFile::parse(text)?;
+
+        let scope = file.scope_at(9, 47);
+        assert!(scope.module_path.is_empty());
+        let imports: Vec<String> = scope.imports.iter().map(|leaf| leaf.to_string()).collect();
+        assert_eq!(imports, vec!["std::fmt", "std::io::Write"]);
+        assert_eq!(
+            scope.items,
+            vec![("nested".to_string(), ItemKind::Function)]
+        );
+        assert_eq!(scope.generics, vec!["Self", "T", "N"]);
+        assert_eq!(
+            scope.locals,
+            vec!["self", "items", "count", "total", "item", "depth"]
+        );
+
+        // `None` names a variant, not a binding, and `later` is not bound yet
+        let scope = file.scope_at(10, 25);
+        assert_eq!(scope.locals.last().map(String::as_str), Some("item"));
+        assert!(!scope.locals.iter().any(|name| name == "later"));
+
+        // A module starts afresh
+        let scope = file.scope_at(20, 14);
+        assert_eq!(scope.module_path, vec!["inner"]);
+        assert_eq!(scope.imports[0].to_string(), "super::Helper");
+        assert!(scope.generics.is_empty() && scope.locals.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_error_reports_position() {
+        let err = SourceFile::parse("fn broken( {").err().unwrap();
+        assert!(err.to_string().starts_with("1:"));
+    }
+
+    const ROLES: &str = r#"
+#[derive(Debug, Serialize)]
+struct Config { path: PathBuf }
+
+impl Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        info!("{}", read_to_string(MAX));
+        match self.level() {
+            Some(Level::High) => {}
+            Point { x, .. } => {}
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn run<T: Clone>() -> io::Result<Settings> { Settings { verbose: true } }
+"#;
+
+    /// 1-based line/column of the first occurrence of `needle`
+    fn position_of(text: &str, needle: &str) -> (u32, u32) {
+        let offset = text.find(needle).unwrap();
+        let line = text[..offset].matches('\n').count() + 1;
+        let column = offset - text[..offset].rfind('\n').map_or(0, |i| i + 1) + 1;
+        (line as u32, column as u32)
+    }
+
+    #[test]
+    fn test_name_at_classifies_roles() -> Result<()> {
+        let file = SourceFile::parse(ROLES)?;
+        let role = |needle: &str| {
+            let (line, column) = position_of(ROLES, needle);
+            file.name_at(line, column)
+                .map(|name| (name.name, name.position))
+        };
+        let expect = |needle: &str, name: &str, position: NamePosition| {
+            assert_eq!(role(needle), Some((name.to_string(), position)), "{needle}");
+        };
+
+        expect("Serialize", "Serialize", NamePosition::Derive);
+        expect("PathBuf", "PathBuf", NamePosition::Type);
+        expect("Display", "Display", NamePosition::TraitBound);
+        expect("fmt::Formatter", "fmt", NamePosition::PathPrefix);
+        expect("Formatter", "Formatter", NamePosition::Type);
+        expect("info!", "info", NamePosition::Macro);
+        expect("read_to_string", "read_to_string", NamePosition::Call);
+        expect("MAX", "MAX", NamePosition::Any);
+        expect("Level::", "Level", NamePosition::PathPrefix);
+        expect("High", "High", NamePosition::Pattern);
+        expect("Point", "Point", NamePosition::Pattern);
+        expect("Clone", "Clone", NamePosition::TraitBound);
+        expect("Settings>", "Settings", NamePosition::Type);
+        expect("Settings {", "Settings", NamePosition::Type);
+        assert_eq!(role("    fn fmt"), None);
+
+        Ok(())
+    }
+}
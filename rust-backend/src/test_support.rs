@@ -0,0 +1,56 @@
+//! Test fixtures for Rusty Refactor
+//!
+//! This module holds what the unit tests of several modules share: writing fixture files,
+//! a throwaway Cargo package to run name resolution on, and a resolver that only sees that
+//! package, so tests neither index the installed toolchain nor write to the user's cache.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+use crate::NameResolver;
+
+/// Write `text` to `file` below `dir`, creating the directories it needs
+pub fn write(dir: &Path, file: &str, text: &str) -> Result<PathBuf> {
+    let path = dir.join(file);
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    std::fs::write(&path, text)?;
+    Ok(path)
+}
+
+/// A resolver using the built-in std list and no dependencies
+pub fn hermetic_resolver() -> NameResolver {
+    NameResolver::new()
+        .with_std_library(None)
+        .with_externals(false)
+}
+
+/// A Cargo package in a temporary directory, deleted on drop
+pub struct TestProject {
+    dir: TempDir,
+}
+
+impl TestProject {
+    /// A package named `app` with an empty `src/`
+    pub fn new() -> Result<Self> {
+        Self::with_manifest("[package]\nname = \"app\"\n")
+    }
+
+    /// A package with this `Cargo.toml` and an empty `src/`
+    pub fn with_manifest(manifest: &str) -> Result<Self> {
+        let dir = TempDir::new()?;
+        std::fs::create_dir_all(dir.path().join("src"))?;
+        write(dir.path(), "Cargo.toml", manifest)?;
+        Ok(Self { dir })
+    }
+
+    /// Root directory of the package
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Write a file of the package, returning its full path
+    pub fn write(&self, file: &str, text: &str) -> Result<PathBuf> {
+        write(self.path(), file, text)
+    }
+}
@@ -0,0 +1,396 @@
+//! Workspace edit transactions for Rusty Refactor
+//!
+//! This module applies multi-file refactorings all-or-nothing: every new file is staged next
+//! to its destination and renamed into place, and a failure part-way restores the files that
+//! were already touched. Committed refactorings are recorded in an undo journal under
+//! `.rusty-cache/` so the last one can be reverted.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::cache::CACHE_DIR;
+
+/// Journal file inside the cache directory
+const JOURNAL_FILE: &str = "undo-journal.json";
+
+/// Number of refactorings kept in the journal
+const JOURNAL_LIMIT: usize = 20;
+
+/// A file rewritten, created or removed by a refactoring
+#[derive(Debug, Clone)]
+pub struct FileChange {
+    pub path: PathBuf,
+    /// New contents, or `None` when the file is removed
+    pub contents: Option<String>,
+    pub created: bool,
+}
+
+/// State of one file before and after a refactoring
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileSnapshot {
+    pub path: PathBuf,
+    /// `None` when the file did not exist
+    pub before: Option<String>,
+    /// `None` when the refactoring removed the file
+    pub after: Option<String>,
+}
+
+/// One applied refactoring
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub label: String,
+    pub timestamp: u64,
+    pub files: Vec<FileSnapshot>,
+    /// Directories the refactoring created, outermost first
+    pub created_dirs: Vec<PathBuf>,
+}
+
+/// A staged set of file changes applied as a unit
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    label: String,
+    changes: Vec<FileChange>,
+}
+
+impl Transaction {
+    pub fn new(label: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            changes: Vec::new(),
+        }
+    }
+
+    /// Stage a change, replacing any earlier change to the same file
+    pub fn stage(&mut self, change: FileChange) -> &mut Self {
+        match self.changes.iter_mut().find(|c| c.path == change.path) {
+            Some(existing) => *existing = change,
+            None => self.changes.push(change),
+        }
+        self
+    }
+
+    pub fn stage_all(&mut self, changes: impl IntoIterator<Item = FileChange>) -> &mut Self {
+        for change in changes {
+            self.stage(change);
+        }
+        self
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn changes(&self) -> &[FileChange] {
+        &self.changes
+    }
+
+    /// Apply the changes and record them in the workspace's undo journal
+    pub fn commit(&self, workspace_root: &Path) -> Result<JournalEntry> {
+        let entry = self.apply()?;
+        let recorded =
+            UndoJournal::open(workspace_root).and_then(|mut journal| journal.push(entry.clone()));
+        if let Err(e) = recorded {
+            restore(&entry);
+            return Err(e.context("Failed to record the refactoring; changes were rolled back"));
+        }
+        Ok(entry)
+    }
+
+    /// Apply the changes without journaling them
+    ///
+    /// Either every change lands or the files are left as they were.
+    pub fn apply(&self) -> Result<JournalEntry> {
+        let mut files = Vec::with_capacity(self.changes.len());
+        for change in &self.changes {
+            files.push(FileSnapshot {
+                path: change.path.clone(),
+                before: read_optional(&change.path)?,
+                after: change.contents.clone(),
+            });
+        }
+        let mut entry = JournalEntry {
+            label: self.label.clone(),
+            timestamp: current_timestamp(),
+            files,
+            created_dirs: Vec::new(),
+        };
+
+        // Stage every write next to its destination so the final rename cannot cross devices
+        let mut staged: Vec<Option<PathBuf>> = Vec::with_capacity(self.changes.len());
+        for change in &self.changes {
+            let result = match &change.contents {
+                Some(contents) => {
+                    stage_write(&change.path, contents, &mut entry.created_dirs).map(Some)
+                }
+                None => Ok(None),
+            };
+            match result {
+                Ok(temp) => staged.push(temp),
+                Err(e) => {
+                    discard(&staged, &entry.created_dirs);
+                    return Err(e);
+                }
+            }
+        }
+
+        for (applied, (change, temp)) in self.changes.iter().zip(&staged).enumerate() {
+            let result = match temp {
+                Some(temp) => fs::rename(temp, &change.path)
+                    .with_context(|| format!("Failed to write {}", change.path.display())),
+                None if change.path.exists() => fs::remove_file(&change.path)
+                    .with_context(|| format!("Failed to remove {}", change.path.display())),
+                None => Ok(()),
+            };
+            if let Err(e) = result {
+                let partial = JournalEntry {
+                    files: entry.files[..applied].to_vec(),
+                    ..entry.clone()
+                };
+                restore(&partial);
+                discard(&staged[applied..], &entry.created_dirs);
+                return Err(e);
+            }
+        }
+
+        // Directories emptied by removed files go too; reverting recreates them
+        for change in self.changes.iter().filter(|c| c.contents.is_none()) {
+            if let Some(parent) = change.path.parent() {
+                let _ = fs::remove_dir(parent);
+            }
+        }
+
+        Ok(entry)
+    }
+}
+
+/// The undo journal of a workspace, most recent refactoring last
+pub struct UndoJournal {
+    path: PathBuf,
+    entries: Vec<JournalEntry>,
+}
+
+impl UndoJournal {
+    pub fn open(workspace_root: &Path) -> Result<Self> {
+        let path = workspace_root.join(CACHE_DIR).join(JOURNAL_FILE);
+        let entries = match read_optional(&path)? {
+            Some(json) => serde_json::from_str(&json)
+                .with_context(|| format!("Corrupt undo journal {}", path.display()))?,
+            None => Vec::new(),
+        };
+        Ok(Self { path, entries })
+    }
+
+    pub fn entries(&self) -> &[JournalEntry] {
+        &self.entries
+    }
+
+    pub fn push(&mut self, entry: JournalEntry) -> Result<()> {
+        self.entries.push(entry);
+        if self.entries.len() > JOURNAL_LIMIT {
+            self.entries.drain(..self.entries.len() - JOURNAL_LIMIT);
+        }
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        write_atomic(&self.path, &serde_json::to_string(&self.entries)?)
+    }
+}
+
+/// Undo the most recent journaled refactoring, restoring every file it touched
+///
+/// Refuses when any of those files changed since, so no later edit is lost.
+/// Returns `None` when the journal is empty.
+pub fn revert_last(workspace_root: &Path) -> Result<Option<JournalEntry>> {
+    let mut journal = UndoJournal::open(workspace_root)?;
+    let Some(entry) = journal.entries.last().cloned() else {
+        return Ok(None);
+    };
+
+    for file in &entry.files {
+        if read_optional(&file.path)? != file.after {
+            bail!(
+                "{} changed after '{}'; refusing to revert",
+                file.path.display(),
+                entry.label
+            );
+        }
+    }
+
+    let mut undo = Transaction::new(format!("Revert {}", entry.label));
+    undo.stage_all(entry.files.iter().map(|file| FileChange {
+        path: file.path.clone(),
+        contents: file.before.clone(),
+        created: file.after.is_none(),
+    }));
+    undo.apply()?;
+    for dir in entry.created_dirs.iter().rev() {
+        let _ = fs::remove_dir(dir);
+    }
+
+    journal.entries.pop();
+    journal.save()?;
+    Ok(Some(entry))
+}
+
+/// Write a file by renaming a fully written temporary file over it
+pub fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let temp = stage_write(path, contents, &mut Vec::new())?;
+    fs::rename(&temp, path).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+// Helper functions
+
+/// Write `contents` to a temporary sibling of `path`, creating missing directories
+fn stage_write(path: &Path, contents: &str, created_dirs: &mut Vec<PathBuf>) -> Result<PathBuf> {
+    let parent = path
+        .parent()
+        .with_context(|| format!("{} has no parent directory", path.display()))?;
+    let missing: Vec<PathBuf> = parent
+        .ancestors()
+        .take_while(|dir| !dir.exists())
+        .map(Path::to_path_buf)
+        .collect();
+    fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    created_dirs.extend(missing.into_iter().rev());
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = parent.join(format!(".{}.rusty-tmp-{}", file_name, std::process::id()));
+    fs::write(&temp, contents).with_context(|| format!("Failed to stage {}", path.display()))?;
+    Ok(temp)
+}
+
+/// Best-effort return of each file (and directory) to its `before` state
+fn restore(entry: &JournalEntry) {
+    for file in entry.files.iter().rev() {
+        let _ = match &file.before {
+            Some(contents) => write_atomic(&file.path, contents),
+            None => fs::remove_file(&file.path).map_err(Into::into),
+        };
+    }
+    for dir in entry.created_dirs.iter().rev() {
+        let _ = fs::remove_dir(dir);
+    }
+}
+
+/// Remove staged temporary files and the directories created for them
+fn discard(staged: &[Option<PathBuf>], created_dirs: &[PathBuf]) {
+    for temp in staged.iter().flatten() {
+        let _ = fs::remove_file(temp);
+    }
+    for dir in created_dirs.iter().rev() {
+        let _ = fs::remove_dir(dir);
+    }
+}
+
+fn read_optional(path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+fn current_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(path: PathBuf, contents: &str) -> FileChange {
+        FileChange {
+            created: !path.exists(),
+            path,
+            contents: Some(contents.to_string()),
+        }
+    }
+
+    fn remove(path: PathBuf) -> FileChange {
+        FileChange {
+            path,
+            contents: None,
+            created: false,
+        }
+    }
+
+    #[test]
+    fn test_commit_and_revert_restore_previous_state() -> Result<()> {
+        let dir = TempDir::new()?;
+        let root = dir.path();
+        fs::write(root.join("models.rs"), "pub struct User;\n")?;
+
+        let mut transaction = Transaction::new("Convert models.rs to models/mod.rs");
+        transaction
+            .stage(write(root.join("models/mod.rs"), "pub struct User;\n"))
+            .stage(remove(root.join("models.rs")));
+        transaction.commit(root)?;
+
+        assert!(!root.join("models.rs").exists());
+        assert_eq!(
+            fs::read_to_string(root.join("models/mod.rs"))?,
+            "pub struct User;\n"
+        );
+        assert_eq!(UndoJournal::open(root)?.entries().len(), 1);
+
+        let reverted = revert_last(root)?.expect("journal entry");
+        assert_eq!(reverted.label, "Convert models.rs to models/mod.rs");
+        assert_eq!(
+            fs::read_to_string(root.join("models.rs"))?,
+            "pub struct User;\n"
+        );
+        assert!(!root.join("models").exists());
+        assert!(UndoJournal::open(root)?.entries().is_empty());
+        assert!(revert_last(root)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_apply_leaves_files_untouched() -> Result<()> {
+        let dir = TempDir::new()?;
+        let root = dir.path();
+        fs::write(root.join("lib.rs"), "mod a;\n")?;
+        fs::write(root.join("blocker"), "")?;
+
+        let mut transaction = Transaction::new("broken");
+        transaction
+            .stage(write(root.join("lib.rs"), "mod b;\n"))
+            .stage(write(root.join("blocker/b.rs"), ""));
+        assert!(transaction.commit(root).is_err());
+
+        assert_eq!(fs::read_to_string(root.join("lib.rs"))?, "mod a;\n");
+        let leftovers: Vec<_> = fs::read_dir(root)?
+            .flatten()
+            .map(|e| e.file_name())
+            .collect();
+        assert_eq!(leftovers.len(), 2);
+        assert!(UndoJournal::open(root)?.entries().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_revert_refuses_after_later_edits() -> Result<()> {
+        let dir = TempDir::new()?;
+        let root = dir.path();
+
+        let mut transaction = Transaction::new("Create util.rs");
+        transaction.stage(write(root.join("util.rs"), "fn a() {}\n"));
+        transaction.commit(root)?;
+
+        fs::write(root.join("util.rs"), "fn a() {}\nfn b() {}\n")?;
+        assert!(revert_last(root).is_err());
+        assert!(root.join("util.rs").exists());
+
+        Ok(())
+    }
+}
@@ -227,9 +227,11 @@ export class RefactorFileTool implements vscode.LanguageModelTool<IRefactorFileP
                         analysis.dependencies.used_types
                     );
 
-                    const matches = JSON.parse(importSuggestions);
-                    for (const match of matches) {
-                        if (match.confidence > 0.5) {
+                    // One group of ranked matches per type; take the best of each
+                    const groups = JSON.parse(importSuggestions);
+                    for (const group of groups) {
+                        const match = group.matches[0];
+                        if (match && match.confidence > 0.5) {
                             suggestedImports.push({
                                 type_name: match.item.name,
                                 import_path: match.item.full_path,
@@ -191,8 +191,8 @@ export class ModuleExtractor {
                         cancellable: false
                     }, async () => {
                         await convertModuleToFolder(
+                            workspaceFolder.uri.fsPath,
                             conversionInfo.existing_file_path!,
-                            conversionInfo.target_folder_path,
                             conversionInfo.target_mod_file_path
                         );
                     });
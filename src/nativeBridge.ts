@@ -7,18 +7,27 @@ interface EnhancedOutput {
   file: string;
   suggested_imports: ImportInfo[];
   external_crates: ExternalCrate[];
+  resolved_crates: ResolvedCrate[];
   diagnostics: Diagnostic[];
   unresolved_types: string[];
+  unresolved_methods: UnresolvedMethod[];
   functions: FunctionInfo[];
   types: TypeInfo[];
 }
 
+interface UnresolvedMethod {
+  method: string;
+  receiver: string;
+  span?: SpanInfo;
+}
+
 interface ImportInfo {
   path: string;
   alias?: string;
   span?: SpanInfo;
   is_glob: boolean;
   confidence: number;
+  visibility?: string;
 }
 
 interface FunctionInfo {
@@ -29,6 +38,16 @@ interface FunctionInfo {
   return_type: string;
   is_unsafe: boolean;
   is_async: boolean;
+  impl_context?: ImplContext;
+  self_kind: 'self' | '&self' | '&mut self' | 'none';
+}
+
+interface ImplContext {
+  kind: 'impl' | 'trait';
+  target_type: string;
+  trait_name?: string;
+  header: string;
+  span?: SpanInfo;
 }
 
 interface ParamInfo {
@@ -41,6 +60,9 @@ interface TypeInfo {
   name: string;
   span?: SpanInfo;
   definition: string;
+  kind: 'struct' | 'enum' | 'trait' | 'union' | 'type_alias';
+  generics: string[];
+  module_path: string;
 }
 
 interface SpanInfo {
@@ -53,6 +75,25 @@ interface SpanInfo {
 interface ExternalCrate {
   name: string;
   version: string;
+  kind: 'normal' | 'dev' | 'build';
+  package: string;
+  rename?: string;
+  features: string[];
+  default_features: boolean;
+  optional: boolean;
+  source: string;
+  target?: string;
+  member: string;
+  resolved?: ResolvedCrate;
+}
+
+interface ResolvedCrate {
+  name: string;
+  version: string;
+  source?: string;
+  checksum?: string;
+  dependency_path: string[];
+  duplicate_versions: string[];
 }
 
 interface Diagnostic {
@@ -88,6 +129,50 @@ interface ModuleConversionInfo {
   module_name: string;
 }
 
+interface ModuleCollapseResult {
+  module_file_path: string;
+  merged_files: string[];
+  removed_files: string[];
+}
+
+interface FileOperation {
+  kind: 'create' | 'rename' | 'delete';
+  path: string;
+  new_path?: string;
+}
+
+interface TextEdit {
+  file: string;
+  range: SpanInfo;
+  new_text: string;
+}
+
+interface OrganizedImports {
+  file: string;
+  range?: SpanInfo;
+  new_text: string;
+  removed: string[];
+}
+
+interface RefactorPreview {
+  label: string;
+  file_operations: FileOperation[];
+  edits: TextEdit[];
+}
+
+interface RevertResult {
+  label: string;
+  restored_files: string[];
+}
+
+interface ModuleExtractionResult {
+  module_path: string;
+  target_file: string;
+  moved_items: string[];
+  changed_files: string[];
+  created_files: string[];
+}
+
 interface CacheStatsResult {
   hits: number;
   misses: number;
@@ -175,10 +260,102 @@ export function enhancedCargoCheck(workspaceRoot: string, targetFile: string): P
   }
 }
 
-export function suggestImportsForTypes(workspaceRoot: string, unresolvedTypes: string[]): Promise<string> {
+// `rustdocJson` lists `rustdoc --output-format json` files to take items from;
+// `pathPreference` picks the shortest or the declared path of re-exported crate items
+export function suggestImportsForTypes(
+  workspaceRoot: string,
+  unresolvedTypes: string[],
+  rustdocJson?: string[],
+  pathPreference?: 'shortest' | 'canonical'
+): Promise<string> {
+  try {
+    const native = getNativeModule();
+    return native.suggest_imports_for_types(workspaceRoot, unresolvedTypes, rustdocJson, pathPreference);
+  } catch (e) {
+    return Promise.reject(e);
+  }
+}
+
+// Ranked imports for the name at a 1-based line/column, limited to kinds that fit its use;
+// resolves to the JSON string "null" when there is no name there
+export function suggestImportsAt(
+  workspaceRoot: string,
+  filePath: string,
+  line: number,
+  column: number,
+  rustdocJson?: string[],
+  pathPreference?: 'shortest' | 'canonical'
+): Promise<string> {
+  try {
+    const native = getNativeModule();
+    return native.suggest_imports_at(workspaceRoot, filePath, line, column, rustdocJson, pathPreference);
+  } catch (e) {
+    return Promise.reject(e);
+  }
+}
+
+// Names visible at a 1-based line/column as a JSON list of importable items, innermost first;
+// ancestor modules' items are named by their `super::` path
+export function itemsInScopeAt(
+  workspaceRoot: string,
+  filePath: string,
+  line: number,
+  column: number,
+  rustdocJson?: string[]
+): Promise<string> {
+  try {
+    const native = getNativeModule();
+    return native.items_in_scope_at(workspaceRoot, filePath, line, column, rustdocJson);
+  } catch (e) {
+    return Promise.reject(e);
+  }
+}
+
+// Ranked traits whose import would provide `method` on `receiver` (rustc's E0599)
+export function suggestTraitImports(
+  workspaceRoot: string,
+  receiver: string,
+  method: string,
+  rustdocJson?: string[]
+): Promise<string> {
+  try {
+    const native = getNativeModule();
+    return native.suggest_trait_imports(workspaceRoot, receiver, method, rustdocJson);
+  } catch (e) {
+    return Promise.reject(e);
+  }
+}
+
+// Edits adding `importInfo` to a file, merged into its `use` declarations per rustfmt.toml;
+// empty when the file already has the import
+export function applyImport(filePath: string, importInfo: ImportInfo): Promise<TextEdit[]> {
+  try {
+    const native = getNativeModule();
+    return native.apply_import(filePath, importInfo);
+  } catch (e) {
+    return Promise.reject(e);
+  }
+}
+
+// Rewritten import block of a file: unused imports (per `cargo check`) dropped, the rest
+// deduplicated, sorted and grouped; the file itself is not changed
+export function organizeImports(
+  workspaceRoot: string,
+  filePath: string
+): Promise<OrganizedImports> {
+  try {
+    const native = getNativeModule();
+    return native.organize_imports(workspaceRoot, filePath);
+  } catch (e) {
+    return Promise.reject(e);
+  }
+}
+
+// Every `use` and `extern crate` in a file, flattened to one entry per imported name
+export function getFileImports(filePath: string): Promise<ImportInfo[]> {
   try {
     const native = getNativeModule();
-    return native.suggest_imports_for_types(workspaceRoot, unresolvedTypes);
+    return native.get_file_imports(filePath);
   } catch (e) {
     return Promise.reject(e);
   }
@@ -194,7 +371,13 @@ export function extractFunctionWithTypes(
 ): Promise<ExtractionResult> {
   try {
     const native = getNativeModule();
-    return native.extract_function_with_types(filePath, startLine, startCol, endLine, endCol, functionName);
+    const range: SpanInfo = {
+      line_start: startLine,
+      line_end: endLine,
+      column_start: startCol,
+      column_end: endCol,
+    };
+    return native.extract_function(filePath, range, functionName);
   } catch (e) {
     return Promise.reject(e);
   }
@@ -207,7 +390,7 @@ export function getFunctionAtPosition(
 ): Promise<FunctionInfo | null> {
   try {
     const native = getNativeModule();
-    return native.get_function_at_position(filePath, line, column);
+    return native.get_function_info(filePath, line, column);
   } catch (e) {
     return Promise.reject(e);
   }
@@ -285,15 +468,95 @@ export function checkModuleConversion(
   }
 }
 
-// Convert a module file to a folder structure
+// Convert a module file to a folder structure (undoable with revertLastRefactor)
 export function convertModuleToFolder(
+  workspaceRoot: string,
   existingFilePath: string,
-  targetFolderPath: string,
   targetModFilePath: string
 ): Promise<boolean> {
   try {
     const native = getNativeModule();
-    return native.convert_module_to_folder(existingFilePath, targetFolderPath, targetModFilePath);
+    return native.convert_module_to_folder(workspaceRoot, existingFilePath, targetModFilePath);
+  } catch (e) {
+    return Promise.reject(e);
+  }
+}
+
+// Preview convertModuleToFolder without touching the disk
+export function previewConvertModuleToFolder(
+  existingFilePath: string,
+  targetModFilePath: string
+): Promise<RefactorPreview> {
+  try {
+    const native = getNativeModule();
+    return native.preview_convert_module_to_folder(existingFilePath, targetModFilePath);
+  } catch (e) {
+    return Promise.reject(e);
+  }
+}
+
+// Convert a folder module (foo/mod.rs) back into foo.rs, optionally merging its children inline
+export function convertFolderToModuleFile(
+  workspaceRoot: string,
+  modFilePath: string,
+  mergeChildren: boolean
+): Promise<ModuleCollapseResult> {
+  try {
+    const native = getNativeModule();
+    return native.convert_folder_to_module_file(workspaceRoot, modFilePath, mergeChildren);
+  } catch (e) {
+    return Promise.reject(e);
+  }
+}
+
+// Preview convertFolderToModuleFile without touching the disk
+export function previewConvertFolderToModuleFile(
+  modFilePath: string,
+  mergeChildren: boolean
+): Promise<RefactorPreview> {
+  try {
+    const native = getNativeModule();
+    return native.preview_convert_folder_to_module_file(modFilePath, mergeChildren);
+  } catch (e) {
+    return Promise.reject(e);
+  }
+}
+
+// Move items (or `Type::method` methods) into a module, updating `mod` and `use` declarations
+export function extractToModule(
+  workspaceRoot: string,
+  sourceFile: string,
+  itemNames: string[],
+  targetFile: string
+): Promise<ModuleExtractionResult> {
+  try {
+    const native = getNativeModule();
+    return native.extract_to_module(workspaceRoot, sourceFile, itemNames, targetFile);
+  } catch (e) {
+    return Promise.reject(e);
+  }
+}
+
+// Preview extractToModule without touching the disk
+export function previewExtractToModule(
+  workspaceRoot: string,
+  sourceFile: string,
+  itemNames: string[],
+  targetFile: string
+): Promise<RefactorPreview> {
+  try {
+    const native = getNativeModule();
+    return native.preview_extract_to_module(workspaceRoot, sourceFile, itemNames, targetFile);
+  } catch (e) {
+    return Promise.reject(e);
+  }
+}
+
+// Undo the last module refactoring; resolves to null when the journal is empty
+export function revertLastRefactor(workspaceRoot: string): Promise<RevertResult | null> {
+  try {
+    const native = getNativeModule();
+    return native.revert_last_refactor(workspaceRoot);
   } catch (e) {
     return Promise.reject(e);
   }
@@ -367,20 +630,21 @@ export function getStdLibraryItems(): Promise<string> {
 
 export function findBestImport(
   workspaceRoot: string,
-  typeName: string
+  typeName: string,
+  rustdocJson?: string[]
 ): Promise<string> {
   try {
     const native = getNativeModule();
-    return native.find_best_import(workspaceRoot, typeName);
+    return native.find_best_import(workspaceRoot, typeName, rustdocJson);
   } catch (e) {
     return Promise.reject(e);
   }
 }
 
-export function resolveProjectNames(workspaceRoot: string): Promise<string> {
+export function resolveProjectNames(workspaceRoot: string, rustdocJson?: string[]): Promise<string> {
   try {
     const native = getNativeModule();
-    return native.resolve_project_names(workspaceRoot);
+    return native.resolve_project_names(workspaceRoot, rustdocJson);
   } catch (e) {
     return Promise.reject(e);
   }